@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS games (
+    id UUID PRIMARY KEY,
+    state JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'in_progress',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_games_status ON games (status);
+CREATE INDEX IF NOT EXISTS idx_games_created_at ON games (created_at DESC);
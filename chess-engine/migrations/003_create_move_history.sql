@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS move_history (
+    id SERIAL PRIMARY KEY,
+    game_id UUID NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+    ply INT NOT NULL,
+    uci TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (game_id, ply)
+);
@@ -0,0 +1,17 @@
+-- Supersedes move_history, which was never written to
+DROP TABLE IF EXISTS move_history;
+
+CREATE TABLE IF NOT EXISTS game_moves (
+    id SERIAL PRIMARY KEY,
+    game_id UUID NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+    move_number INT NOT NULL,
+    color TEXT NOT NULL,
+    from_square TEXT NOT NULL,
+    to_square TEXT NOT NULL,
+    san TEXT NOT NULL,
+    uci TEXT NOT NULL,
+    fen_after TEXT NOT NULL,
+    played_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_game_moves_game_id ON game_moves (game_id, id);
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS rating INT NOT NULL DEFAULT 1200;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS rating_deviation INT NOT NULL DEFAULT 350;
+
+CREATE INDEX IF NOT EXISTS idx_users_rating ON users (rating DESC);
@@ -0,0 +1,4 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS peak_rating INT;
+UPDATE users SET peak_rating = rating WHERE peak_rating IS NULL;
+ALTER TABLE users ALTER COLUMN peak_rating SET DEFAULT 1200;
+ALTER TABLE users ALTER COLUMN peak_rating SET NOT NULL;
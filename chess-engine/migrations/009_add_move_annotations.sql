@@ -0,0 +1,2 @@
+-- Engine verdicts on each move, filled in by POST /games/:id/annotate
+ALTER TABLE game_moves ADD COLUMN IF NOT EXISTS annotation TEXT;
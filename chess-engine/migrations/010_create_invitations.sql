@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS invitations (
+    id UUID PRIMARY KEY,
+    from_user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    to_user_id INT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    status TEXT NOT NULL DEFAULT 'pending',
+    game_settings JSONB NOT NULL,
+    -- The game created when the invitation was accepted
+    game_id UUID,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_invitations_to_user ON invitations (to_user_id, status);
+CREATE INDEX IF NOT EXISTS idx_invitations_from_user ON invitations (from_user_id, status);
@@ -0,0 +1,2 @@
+-- Hidden profiles are not served by GET /api/v1/users/:username
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_public BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,4 @@
+-- Closed accounts keep their row so their games and username stay taken
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_active BOOLEAN NOT NULL DEFAULT TRUE;
+-- Granted by hand; there is no endpoint for it
+ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- Base32 TOTP secret; set by 2FA setup, only enforced once confirmed
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+-- Milliseconds from receiving the move request to the move being played
+ALTER TABLE game_moves ADD COLUMN IF NOT EXISTS move_time_ms BIGINT;
@@ -0,0 +1,206 @@
+//! Moderation endpoints. Every route here sits behind `require_admin`.
+
+use crate::api::handlers::ErrorResponse;
+use crate::auth::jwt::Claims;
+use crate::auth::models::UserRole;
+use crate::db;
+use crate::db::users::UserRecord;
+use crate::error_codes;
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use warp::http::StatusCode;
+use warp::Reply;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListUsersQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Only users whose username or email contains this
+    pub search: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UserListResponse {
+    pub users: Vec<UserRecord>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+/// Fields left out are not changed
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateUserRequest {
+    pub is_active: Option<bool>,
+    pub role: Option<UserRole>,
+}
+
+fn error_reply(
+    error: &str,
+    code: &'static str,
+    status: StatusCode,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let error = ErrorResponse {
+        error: error.to_string(),
+        code,
+    };
+    warp::reply::with_status(warp::reply::json(&error), status)
+}
+
+/// Lists every account, including closed ones
+#[utoipa::path(
+    get,
+    path = "/api/v1/admin/users",
+    tag = "admin",
+    params(ListUsersQuery),
+    responses(
+        (status = 200, description = "A page of users", body = UserListResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(admin_id = claims.sub))]
+pub async fn list_users(
+    claims: Claims,
+    query: ListUsersQuery,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let search = query
+        .search
+        .as_deref()
+        .map(str::trim)
+        .filter(|s| !s.is_empty());
+
+    let offset = (page - 1) * per_page;
+    match db::users::list_users(&db_pool, search, per_page, offset).await {
+        Ok((users, total)) => {
+            let response = UserListResponse {
+                users,
+                total,
+                page,
+                per_page,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to list users: {}", e);
+            Ok(error_reply(
+                "Failed to list users",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Opens or closes an account, or changes its role
+#[utoipa::path(
+    patch,
+    path = "/api/v1/admin/users/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "User ID")),
+    request_body = UpdateUserRequest,
+    responses(
+        (status = 200, description = "Updated user", body = UserRecord),
+        (status = 400, description = "Admins cannot demote or deactivate themselves", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = user_id, admin_id = claims.sub))]
+pub async fn update_user(
+    user_id: i32,
+    claims: Claims,
+    request: UpdateUserRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    // Keeps at least one admin able to undo mistakes
+    let demotes_self = request.role.is_some_and(|role| role != UserRole::Admin)
+        || request.is_active == Some(false);
+    if user_id == claims.sub && demotes_self {
+        return Ok(error_reply(
+            "Admins cannot demote or deactivate themselves",
+            error_codes::INVALID_REQUEST,
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    match db::users::update_user(&db_pool, user_id, request.is_active, request.role).await {
+        Ok(Some(user)) => {
+            tracing::info!(user_id, admin_id = claims.sub, "user updated");
+            Ok(warp::reply::with_status(
+                warp::reply::json(&user),
+                StatusCode::OK,
+            ))
+        }
+        Ok(None) => Ok(error_reply(
+            "User not found",
+            error_codes::USER_NOT_FOUND,
+            StatusCode::NOT_FOUND,
+        )),
+        Err(e) => {
+            eprintln!("Failed to update user {}: {}", user_id, e);
+            Ok(error_reply(
+                "Failed to update user",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Permanently deletes a user. Unlike closing an account, this frees the
+/// username; the user's games stay but no longer link to an account.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/admin/users/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "User ID")),
+    responses(
+        (status = 204, description = "User deleted"),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = user_id, admin_id = claims.sub))]
+pub async fn delete_user(
+    user_id: i32,
+    claims: Claims,
+    db_pool: Pool,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    match db::users::delete_user(&db_pool, user_id).await {
+        Ok(true) => {
+            tracing::info!(user_id, admin_id = claims.sub, "user deleted");
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        Ok(false) => Ok(error_reply(
+            "User not found",
+            error_codes::USER_NOT_FOUND,
+            StatusCode::NOT_FOUND,
+        )
+        .into_response()),
+        Err(e) => {
+            eprintln!("Failed to delete user {}: {}", user_id, e);
+            Ok(error_reply(
+                "Failed to delete user",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response())
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::api::handlers::ErrorResponse;
+use crate::auth::middleware::{AuthRejection, ForbiddenRejection};
+use crate::error_codes;
+use std::convert::Infallible;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+/// Turns every rejection into a JSON `ErrorResponse` so clients never
+/// receive warp's plain-text error bodies
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let (status, code, message) = if err.is_not_found() {
+        (
+            StatusCode::NOT_FOUND,
+            error_codes::NOT_FOUND,
+            "Not found".to_string(),
+        )
+    } else if let Some(AuthRejection(message)) = err.find::<AuthRejection>() {
+        (
+            StatusCode::UNAUTHORIZED,
+            error_codes::AUTH_REQUIRED,
+            message.clone(),
+        )
+    } else if let Some(ForbiddenRejection(message)) = err.find::<ForbiddenRejection>() {
+        (
+            StatusCode::FORBIDDEN,
+            error_codes::ADMIN_REQUIRED,
+            message.clone(),
+        )
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        (
+            StatusCode::BAD_REQUEST,
+            error_codes::INVALID_REQUEST,
+            format!("Invalid request body: {}", e),
+        )
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        (
+            StatusCode::BAD_REQUEST,
+            error_codes::INVALID_REQUEST,
+            e.to_string(),
+        )
+    } else if let Some(e) = err.find::<warp::reject::MissingHeader>() {
+        (
+            StatusCode::BAD_REQUEST,
+            error_codes::INVALID_REQUEST,
+            e.to_string(),
+        )
+    } else if let Some(e) = err.find::<warp::reject::InvalidHeader>() {
+        (
+            StatusCode::BAD_REQUEST,
+            error_codes::INVALID_REQUEST,
+            e.to_string(),
+        )
+    } else if let Some(e) = err.find::<warp::reject::PayloadTooLarge>() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            error_codes::PAYLOAD_TOO_LARGE,
+            e.to_string(),
+        )
+    } else if let Some(e) = err.find::<warp::reject::UnsupportedMediaType>() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            error_codes::UNSUPPORTED_MEDIA_TYPE,
+            e.to_string(),
+        )
+    } else if err.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            error_codes::METHOD_NOT_ALLOWED,
+            "Method not allowed".to_string(),
+        )
+    } else {
+        eprintln!("Unhandled rejection: {:?}", err);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            error_codes::INTERNAL_ERROR,
+            "Internal server error".to_string(),
+        )
+    };
+
+    let error = ErrorResponse {
+        error: message,
+        code,
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&error), status))
+}
@@ -1,211 +1,2907 @@
-use crate::chess::{GameState, Move};
+use crate::api::sse::{publish_game_event, GameEvents};
+use crate::api::store::{GameBackend, GameStoreBackend};
+use crate::api::ws::{broadcast_game_state, broadcast_move, GameSubscriptions};
+use crate::auth::jwt::Claims;
+use crate::chess::{
+    notation, ChessError, Color, Engine, GameMode, GameState, GameStatus, Move, MoveAnnotation,
+    PgnMetadata, PgnParser, PieceType, TimeControl, TranspositionTable, Variant,
+};
+use crate::db;
+use crate::error_codes;
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use futures_util::TryStreamExt;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use utoipa::{IntoParams, ToSchema};
 use uuid::Uuid;
-use warp::Reply;
+use warp::hyper::body::Buf;
+use warp::multipart::FormData;
+use warp::{Filter, Reply};
 
-pub type GameStore = Arc<Mutex<HashMap<String, GameState>>>;
+pub type GameStore = Arc<RwLock<HashMap<String, GameState>>>;
 
-#[derive(Serialize, Deserialize)]
+/// Search results shared by every engine the server runs, so positions
+/// seen in earlier requests don't have to be searched again
+pub type EngineTable = Arc<std::sync::Mutex<TranspositionTable>>;
+
+lazy_static! {
+    /// When the server started; initialized from `main` so uptime is accurate
+    pub static ref START_TIME: Instant = Instant::now();
+}
+
+/// Set from `main` when the server terminates TLS itself
+pub static TLS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The in-memory store is a write-through cache over the persistent
+/// `GameStoreBackend`. Loads a game from the backend into the cache if it
+/// isn't there yet.
+pub async fn ensure_cached(game_id: &str, games: &GameStore, store: &dyn GameStoreBackend) {
+    if games.read().await.contains_key(game_id) {
+        return;
+    }
+
+    match store.load_game(game_id).await {
+        Ok(Some(game_state)) => {
+            games
+                .write()
+                .await
+                .entry(game_id.to_string())
+                .or_insert(game_state);
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to load game {}: {}", game_id, e),
+    }
+}
+
+/// Ends unfinished games that haven't been saved for `inactive_for` as
+/// abandoned draws and drops them from the cache. Returns how many games
+/// were expired.
+pub async fn expire_inactive_games(
+    games: &GameStore,
+    store: &dyn GameStoreBackend,
+    inactive_for: chrono::Duration,
+) -> anyhow::Result<usize> {
+    let stale_games = store.list_inactive_games(Utc::now() - inactive_for).await?;
+
+    let mut expired = 0;
+    for (game_id, stale_event_id) in stale_games {
+        let cached = games.read().await.contains_key(&game_id);
+        let loaded = if cached {
+            None
+        } else {
+            store.load_game(&game_id).await?
+        };
+
+        let mut games_map = games.write().await;
+        if let Some(game_state) = loaded {
+            games_map.entry(game_id.clone()).or_insert(game_state);
+        }
+
+        // Anything played since the query moved the event ID on; such a
+        // game is still in use, possibly with its save still in flight
+        let game_state = games_map.get_mut(&game_id).filter(|game_state| {
+            game_state.event_id == stale_event_id && !game_state.is_game_over()
+        });
+        let Some(game_state) = game_state else {
+            continue;
+        };
+
+        // Abandoned in the cache first, so no move can slip in before the save
+        game_state.abandon();
+        let game_state = game_state.clone();
+        drop(games_map);
+
+        store.save_game(&game_id, &game_state).await?;
+        games.write().await.remove(&game_id);
+        expired += 1;
+    }
+
+    Ok(expired)
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct GameResponse {
     pub game_id: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
+    pub code: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorPreference {
+    White,
+    Black,
+    Random,
+}
+
+impl ColorPreference {
+    /// The color to play, picking one at random for `Random`
+    pub fn resolve(self) -> Color {
+        match self {
+            ColorPreference::White => Color::White,
+            ColorPreference::Black => Color::Black,
+            ColorPreference::Random => {
+                if rand::random::<bool>() {
+                    Color::White
+                } else {
+                    Color::Black
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default, Deserialize, ToSchema)]
+pub struct CreateGameRequest {
+    pub starting_fen: Option<String>,
+    pub time_control: Option<TimeControl>,
+    pub color_preference: Option<ColorPreference>,
+    pub opponent_id: Option<i32>,
+    // Competitive by default, but games against a named opponent or from a
+    // custom FEN are always casual; rated games come from invitations
+    pub game_mode: Option<GameMode>,
+    #[serde(default)]
+    pub variant: Variant,
+    // Chess960 starting position 0-959, random if omitted
+    pub position_id: Option<u16>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FenImportRequest {
+    pub fen: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct PgnImportRequest {
+    pub pgn: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PgnImportResponse {
+    pub game_id: String,
+    pub move_count: usize,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct MoveRequest {
+    #[serde(default)]
     pub from: String, // e.g., "e2"
+    #[serde(default)]
     pub to: String,   // e.g., "e4"
     pub promotion: Option<String>, // e.g., "Queen"
+    pub uci: Option<String>, // e.g., "e7e8q", takes precedence over the fields above
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct MovesResponse {
+    pub moves: Vec<String>,
+    pub san: Vec<String>,
+    pub count: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SquareMovesResponse {
+    pub from: String,
+    pub destinations: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FenResponse {
+    pub fen: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct EngineMoveResponse<'a> {
+    pub from: String,
+    pub to: String,
+    pub san: String,
+    pub game: &'a GameState,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ValidateMoveResponse {
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct EngineMoveRequest {
+    pub depth: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AnalyzeRequest {
+    pub depth: Option<u8>,
+    pub num_lines: Option<usize>,
+    pub ply: Option<usize>, // Position before this move; defaults to the current one
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AnnotateRequest {
+    pub depth: Option<u8>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AnnotateResponse {
+    pub game_id: String,
+    pub move_count: usize,
+}
+
+/// Scores are in centipawns from the point of view of the side to move
+#[derive(Serialize, ToSchema)]
+pub struct AnalysisResult {
+    pub fen: String,
+    pub depth: u8,
+    pub best_move: String,
+    pub score: i32,
+    pub principal_variation: Vec<String>,
+    pub played_move: Option<String>,
+    pub candidate_moves: Vec<CandidateMove>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CandidateMove {
+    pub san: String,
+    pub uci: String,
+    pub score: i32,
+    pub delta: Option<i32>, // How much better than the move actually played
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MoveRecord {
+    pub move_number: u32,
+    pub color: Color,
+    #[serde(rename = "move")]
+    pub notation: String,
+    pub uci: String,
+    pub san: String,
+    pub fen_after: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub played_at: Option<DateTime<Utc>>,
+    /// Milliseconds from the server receiving the move to playing it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub move_time_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<MoveAnnotation>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ColorTimes {
+    pub white: u64,
+    pub black: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LongestMove {
+    #[serde(rename = "move")]
+    pub san: String,
+    pub time_ms: u64,
+}
+
+/// How each player used their time. Moves recorded before move times were
+/// tracked are left out.
+#[derive(Serialize, ToSchema)]
+pub struct GameTimeStats {
+    pub white_total_time_ms: u64,
+    pub black_total_time_ms: u64,
+    pub average_move_time_ms: ColorTimes,
+    pub longest_move: Option<LongestMove>,
+}
+
+impl GameTimeStats {
+    fn from_moves(moves: &[db::moves::MoveRecord]) -> Self {
+        let mut totals = [0u64; 2];
+        let mut counts = [0u64; 2];
+        let mut longest_move: Option<LongestMove> = None;
+
+        for record in moves {
+            let Some(time_ms) = record.move_time_ms else {
+                continue;
+            };
+            let time_ms = time_ms.max(0) as u64;
+            let side = match record.color {
+                Color::White => 0,
+                Color::Black => 1,
+            };
+            totals[side] += time_ms;
+            counts[side] += 1;
+            if longest_move.as_ref().is_none_or(|longest| time_ms > longest.time_ms) {
+                longest_move = Some(LongestMove {
+                    san: record.san.clone(),
+                    time_ms,
+                });
+            }
+        }
+
+        let average = |side: usize| totals[side].checked_div(counts[side]).unwrap_or(0);
+        Self {
+            white_total_time_ms: totals[0],
+            black_total_time_ms: totals[1],
+            average_move_time_ms: ColorTimes {
+                white: average(0),
+                black: average(1),
+            },
+            longest_move,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct HistoryResponse {
+    pub moves: Vec<MoveRecord>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    pub format: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiagramQuery {
+    pub format: Option<String>,
+}
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+const STATUS_FILTERS: [&str; 8] = [
+    "in_progress",
+    "check",
+    "checkmate",
+    "resigned",
+    "timeout",
+    "stalemate",
+    "draw",
+    "variant_win",
+];
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LeaderboardResponse {
+    pub players: Vec<db::ratings::LeaderboardEntry>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListGamesQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub status: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GameSummary {
+    pub game_id: String,
+    pub status: GameStatus,
+    pub current_player: Color,
+    pub fullmove_number: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct GameListResponse {
+    pub games: Vec<GameSummary>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+/// Replays a game's history to build a record of every move played
+pub fn build_move_records(game_state: &GameState, format: &str) -> Vec<MoveRecord> {
+    let mut replay = match game_state.initial_position() {
+        Ok(replay) => replay,
+        Err(_) => return Vec::new(),
+    };
+    let mut records = Vec::new();
+
+    for chess_move in &game_state.history {
+        let move_number = replay.fullmove_number;
+        let color = replay.current_player;
+        let san = notation::move_to_san(chess_move, &replay);
+        let uci = notation::move_to_uci(chess_move);
+
+        if replay.make_move(chess_move.clone()).is_err() {
+            break;
+        }
+
+        let notation = format_notation(
+            format,
+            &san,
+            &uci,
+            &notation::square_to_algebraic(chess_move.from),
+            &notation::square_to_algebraic(chess_move.to),
+        );
+
+        records.push(MoveRecord {
+            move_number,
+            color,
+            notation,
+            uci,
+            san,
+            fen_after: replay.to_fen(),
+            played_at: None,
+            move_time_ms: None,
+            annotation: None,
+        });
+    }
+
+    records
+}
+
+fn format_notation(format: &str, san: &str, uci: &str, from: &str, to: &str) -> String {
+    match format {
+        "uci" => uci.to_string(),
+        "coordinate" => format!("{}-{}", from, to),
+        _ => san.to_string(),
+    }
+}
+
+/// Converts rows from `game_moves` into history entries
+fn stored_move_records(stored: Vec<db::moves::MoveRecord>, format: &str) -> Vec<MoveRecord> {
+    stored
+        .into_iter()
+        .map(|record| MoveRecord {
+            move_number: record.move_number as u32,
+            color: record.color,
+            notation: format_notation(
+                format,
+                &record.san,
+                &record.uci,
+                &record.from_square,
+                &record.to_square,
+            ),
+            uci: record.uci,
+            san: record.san,
+            fen_after: record.fen_after,
+            played_at: Some(record.played_at),
+            move_time_ms: record.move_time_ms.map(|ms| ms.max(0) as u64),
+            annotation: record.annotation,
+        })
+        .collect()
+}
+
+/// Appends the game's last move to `game_moves`, with the time taken to
+/// handle it. Failures are logged rather than returned since the move
+/// itself already succeeded.
+async fn record_move(
+    db_pool: &Pool,
+    game_id: &str,
+    move_number: u32,
+    color: Color,
+    san: &str,
+    move_time: Duration,
+    game_state: &GameState,
+) {
+    metrics::MOVES_MADE.inc();
+    if let Some(chess_move) = game_state.history.last() {
+        let result = db::moves::save_move(
+            db_pool,
+            game_id,
+            move_number as i32,
+            color,
+            chess_move,
+            san,
+            &game_state.to_fen(),
+            move_time.as_millis() as i64,
+        )
+        .await;
+        if let Err(e) = result {
+            eprintln!("Failed to save move for game {}: {}", game_id, e);
+        }
+    }
 }
 
 impl MoveRequest {
     pub fn to_move(&self) -> Result<Move, String> {
-        let from = crate::chess::Square::from_algebraic(&self.from)
+        if let Some(ref uci) = self.uci {
+            return notation::uci_to_move(uci.trim())
+                .ok_or_else(|| "Invalid UCI move".to_string());
+        }
+
+        let from = notation::algebraic_to_square(&self.from)
             .ok_or("Invalid source square")?;
-        let to = crate::chess::Square::from_algebraic(&self.to)
+        let to = notation::algebraic_to_square(&self.to)
             .ok_or("Invalid destination square")?;
         
         let mut chess_move = Move::new(from, to);
         
         if let Some(ref promo) = self.promotion {
-            let piece_type = match promo.as_str() {
-                "Queen" => crate::chess::PieceType::Queen,
-                "Rook" => crate::chess::PieceType::Rook,
-                "Bishop" => crate::chess::PieceType::Bishop,
-                "Knight" => crate::chess::PieceType::Knight,
-                _ => return Err("Invalid promotion piece".to_string()),
+            let piece_type = match promo.parse::<PieceType>() {
+                Ok(PieceType::Pawn | PieceType::King) | Err(_) => {
+                    return Err("Invalid promotion piece".to_string());
+                }
+                Ok(piece_type) => piece_type,
             };
             chess_move.promotion = Some(piece_type);
         }
         
-        // Auto-detect castling
-        if (from.rank == 0 || from.rank == 7) && from.file == 4 && (to.file == 6 || to.file == 2) {
-            chess_move.is_castling = true;
-        }
-        
         Ok(chess_move)
     }
 }
 
-pub async fn create_new_game(games: GameStore) -> Result<impl Reply, warp::Rejection> {
-    let game_id = Uuid::new_v4().to_string();
-    let game_state = GameState::new();
-    
-    {
-        let mut games_map = games.lock().unwrap();
-        games_map.insert(game_id.clone(), game_state);
-    }
-    
-    let response = GameResponse { game_id };
-    Ok(warp::reply::with_status(
-        warp::reply::json(&response),
-        warp::http::StatusCode::CREATED,
-    ))
+const DEFAULT_MAX_CONCURRENT_GAMES_PER_USER: usize = 10;
+
+fn max_concurrent_games_per_user() -> usize {
+    std::env::var("MAX_CONCURRENT_GAMES_PER_USER")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_GAMES_PER_USER)
 }
 
-pub async fn get_game_state(
-    game_id: String,
-    games: GameStore,
-) -> Result<impl Reply, warp::Rejection> {
-    let games_map = games.lock().unwrap();
-    
-    if let Some(game_state) = games_map.get(&game_id) {
-        Ok(warp::reply::with_status(
-            warp::reply::json(game_state),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
+/// Caches a new game unless one of `players` already created or plays in
+/// `MAX_CONCURRENT_GAMES_PER_USER` unfinished ones, since every unfinished
+/// game is held in memory. Counting and inserting under one lock keeps
+/// concurrent requests from all slipping under the limit; callers remove
+/// the game again if persisting it fails.
+pub async fn reserve_game(
+    games: &GameStore,
+    players: &[i32],
+    game_id: &str,
+    game_state: GameState,
+) -> Result<(), warp::reply::WithStatus<warp::reply::Json>> {
+    let mut games_map = games.write().await;
+    let limit_reached = players.iter().any(|&user_id| {
+        let active_games = games_map
+            .values()
+            .filter(|game_state| {
+                let involved = game_state.created_by == Some(user_id)
+                    || game_state.color_of(user_id).is_some();
+                involved && !game_state.is_game_over()
+            })
+            .count();
+        active_games >= max_concurrent_games_per_user()
+    });
+
+    if limit_reached {
         let error = ErrorResponse {
-            error: "Game not found".to_string(),
+            error: "Too many active games".to_string(),
+            code: error_codes::TOO_MANY_ACTIVE_GAMES,
         };
-        Ok(warp::reply::with_status(
+        return Err(warp::reply::with_status(
             warp::reply::json(&error),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
+            warp::http::StatusCode::TOO_MANY_REQUESTS,
+        ));
     }
+
+    games_map.insert(game_id.to_string(), game_state);
+    Ok(())
 }
 
-pub async fn make_move(
-    game_id: String,
-    move_request: MoveRequest,
+#[utoipa::path(
+    post,
+    path = "/api/v1/games",
+    tag = "games",
+    request_body(content = CreateGameRequest, description = "Optional; an empty body creates a standard game"),
+    responses(
+        (status = 201, description = "Game created", body = GameResponse),
+        (status = 400, description = "Invalid game settings", body = ErrorResponse),
+        (status = 429, description = "Too many active games", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn create_new_game(
+    claims: Claims,
+    body: warp::hyper::body::Bytes,
     games: GameStore,
+    store: GameBackend,
+    db_pool: Pool,
 ) -> Result<impl Reply, warp::Rejection> {
-    let chess_move = match move_request.to_move() {
-        Ok(m) => m,
-        Err(e) => {
-            let error = ErrorResponse { error: e };
-            return Ok(warp::reply::with_status(
-                warp::reply::json(&error),
-                warp::http::StatusCode::BAD_REQUEST,
-            ));
+    // The body is optional; an empty one creates a standard untimed game
+    let request = if body.is_empty() {
+        CreateGameRequest::default()
+    } else {
+        match serde_json::from_slice::<CreateGameRequest>(&body) {
+            Ok(request) => request,
+            Err(e) => {
+                let error = ErrorResponse {
+                    error: format!("Invalid request body: {}", e),
+                    code: error_codes::INVALID_REQUEST,
+                };
+                return Ok(warp::reply::with_status(
+                    warp::reply::json(&error),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ));
+            }
         }
     };
 
-    let mut games_map = games.lock().unwrap();
-    
-    if let Some(game_state) = games_map.get_mut(&game_id) {
-        match game_state.make_move(chess_move) {
-            Ok(()) => {
-                Ok(warp::reply::with_status(
-                    warp::reply::json(game_state),
-                    warp::http::StatusCode::OK,
-                ))
-            }
+    let setup_error = match (request.variant, request.position_id) {
+        _ if request.game_mode == Some(GameMode::Competitive) && request.starting_fen.is_some() => {
+            Some("A competitive game cannot start from a custom FEN")
+        }
+        (Variant::Chess960, _) if request.starting_fen.is_some() => {
+            Some("A Chess960 game cannot start from a custom FEN")
+        }
+        (Variant::Chess960, Some(id)) if id > 959 => Some("position_id must be between 0 and 959"),
+        (Variant::Antichess, _) if request.starting_fen.is_some() => {
+            Some("An Antichess game cannot start from a custom FEN")
+        }
+        (Variant::Standard | Variant::Antichess, Some(_)) => {
+            Some("position_id is only valid for Chess960 games")
+        }
+        _ => None,
+    };
+    if let Some(error) = setup_error {
+        let error = ErrorResponse {
+            error: error.to_string(),
+            code: error_codes::INVALID_REQUEST,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let mut game_state = match request.starting_fen.as_deref() {
+        None if request.variant == Variant::Chess960 => GameState::new_chess960(
+            request
+                .position_id
+                .unwrap_or_else(|| rand::random::<u16>() % 960),
+        ),
+        None if request.variant == Variant::Antichess => GameState::new_antichess(),
+        Some(fen) => match GameState::from_fen(fen.trim()) {
+            Ok(game_state) => game_state,
             Err(e) => {
                 let error = ErrorResponse {
                     error: e.to_string(),
+                    code: e.error_code(),
                 };
-                Ok(warp::reply::with_status(
+                return Ok(warp::reply::with_status(
                     warp::reply::json(&error),
                     warp::http::StatusCode::BAD_REQUEST,
-                ))
+                ));
             }
+        },
+        None => GameState::new(),
+    };
+
+    if let Some(opponent_id) = request.opponent_id {
+        let opponent_error = if opponent_id == claims.sub {
+            Some("You cannot play against yourself".to_string())
+        } else {
+            match db::users::user_exists(&db_pool, opponent_id).await {
+                Ok(true) => None,
+                Ok(false) => Some("Opponent not found".to_string()),
+                Err(e) => {
+                    eprintln!("Failed to look up user {}: {}", opponent_id, e);
+                    let error = ErrorResponse {
+                        error: "Failed to create game".to_string(),
+                        code: error_codes::INTERNAL_ERROR,
+                    };
+                    return Ok(warp::reply::with_status(
+                        warp::reply::json(&error),
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    ));
+                }
+            }
+        };
+
+        if let Some(error) = opponent_error {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&ErrorResponse {
+                    error,
+                    code: error_codes::INVALID_REQUEST,
+                }),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
         }
-    } else {
+    }
+
+    // Colors are only assigned when asked for or when playing a specific opponent
+    if request.color_preference.is_some() || request.opponent_id.is_some() {
+        let creator_color = request
+            .color_preference
+            .unwrap_or(ColorPreference::Random)
+            .resolve();
+        let (white_player, black_player) = match creator_color {
+            Color::White => (Some(claims.sub), request.opponent_id),
+            Color::Black => (request.opponent_id, Some(claims.sub)),
+        };
+        game_state.white_player = white_player;
+        game_state.black_player = black_player;
+    }
+
+    let game_id = Uuid::new_v4().to_string();
+    game_state.created_by = Some(claims.sub);
+    // An opponent named here never agreed to a rated game, and a custom
+    // position could be set up to decide it, so neither is rated
+    game_state.game_mode = match request.game_mode.unwrap_or_default() {
+        GameMode::Competitive if request.opponent_id.is_some() || request.starting_fen.is_some() => {
+            GameMode::Casual
+        }
+        game_mode => game_mode,
+    };
+    if let Some(time_control) = request.time_control {
+        game_state.set_time_control(time_control);
+    }
+
+    if let Err(reply) = reserve_game(&games, &[claims.sub], &game_id, game_state.clone()).await {
+        return Ok(reply);
+    }
+
+    if let Err(e) = store.save_game(&game_id, &game_state).await {
+        eprintln!("Failed to persist game {}: {}", game_id, e);
+        games.write().await.remove(&game_id);
         let error = ErrorResponse {
-            error: "Game not found".to_string(),
+            error: "Failed to create game".to_string(),
+            code: error_codes::INTERNAL_ERROR,
         };
-        Ok(warp::reply::with_status(
+        return Ok(warp::reply::with_status(
             warp::reply::json(&error),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ));
     }
+
+    metrics::GAMES_CREATED.inc();
+
+    let response = GameResponse { game_id };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        warp::http::StatusCode::CREATED,
+    ))
 }
 
-pub async fn get_legal_moves(
-    game_id: String,
+/// Sets up an analysis board from an arbitrary position
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/from-fen",
+    tag = "games",
+    request_body = FenImportRequest,
+    responses(
+        (status = 201, description = "Analysis board created", body = GameResponse),
+        (status = 400, description = "Invalid FEN", body = ErrorResponse),
+        (status = 429, description = "Too many active games", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn create_game_from_fen(
+    claims: Claims,
+    request: FenImportRequest,
     games: GameStore,
+    store: GameBackend,
 ) -> Result<impl Reply, warp::Rejection> {
-    let games_map = games.lock().unwrap();
-    
-    if let Some(game_state) = games_map.get(&game_id) {
-        let legal_moves = game_state.get_legal_moves();
-        
-        // Convert moves to a more readable format
-        let move_strings: Vec<String> = legal_moves
-            .iter()
-            .map(|m| format!("{}-{}", m.from.to_algebraic(), m.to.to_algebraic()))
-            .collect();
-        
-        #[derive(Serialize)]
-        struct MovesResponse {
-            moves: Vec<String>,
-            count: usize,
+    let mut game_state = match GameState::from_fen(request.fen.trim()) {
+        Ok(game_state) => game_state,
+        Err(e) => {
+            let error = ErrorResponse {
+                error: e.to_string(),
+                code: e.error_code(),
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
         }
-        
-        let response = MovesResponse {
-            count: move_strings.len(),
-            moves: move_strings,
-        };
-        
-        Ok(warp::reply::with_status(
-            warp::reply::json(&response),
-            warp::http::StatusCode::OK,
-        ))
-    } else {
+    };
+
+    let game_id = Uuid::new_v4().to_string();
+    game_state.created_by = Some(claims.sub);
+    game_state.game_mode = GameMode::Analysis;
+
+    if let Err(reply) = reserve_game(&games, &[claims.sub], &game_id, game_state.clone()).await {
+        return Ok(reply);
+    }
+
+    if let Err(e) = store.save_game(&game_id, &game_state).await {
+        eprintln!("Failed to persist game {}: {}", game_id, e);
+        games.write().await.remove(&game_id);
         let error = ErrorResponse {
-            error: "Game not found".to_string(),
+            error: "Failed to create game".to_string(),
+            code: error_codes::INTERNAL_ERROR,
         };
-        Ok(warp::reply::with_status(
+        return Ok(warp::reply::with_status(
             warp::reply::json(&error),
-            warp::http::StatusCode::NOT_FOUND,
-        ))
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ));
     }
-}
 
-pub async fn get_game_fen(
+    metrics::GAMES_CREATED.inc();
+
+    let response = GameResponse { game_id };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        warp::http::StatusCode::CREATED,
+    ))
+}
+
+const MAX_PGN_UPLOAD_BYTES: u64 = 1024 * 1024;
+
+/// Extracts the `pgn` field from either a multipart form or a JSON body.
+/// Yields `None` if a form has no readable `pgn` field.
+pub fn pgn_body() -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+    let form = warp::multipart::form()
+        .max_length(MAX_PGN_UPLOAD_BYTES)
+        .then(pgn_from_form);
+    let json = warp::body::json().map(|request: PgnImportRequest| Some(request.pgn));
+
+    form.or(json).unify()
+}
+
+async fn pgn_from_form(mut form: FormData) -> Option<String> {
+    while let Ok(Some(part)) = form.try_next().await {
+        if part.name() != "pgn" {
+            continue;
+        }
+
+        let data = part
+            .stream()
+            .try_fold(Vec::new(), |mut data, chunk| async move {
+                data.extend_from_slice(chunk.chunk());
+                Ok(data)
+            })
+            .await
+            .ok()?;
+        return String::from_utf8(data).ok();
+    }
+    None
+}
+
+/// Imports a game from PGN as an analysis board positioned after its last move.
+/// Files holding more than one game are rejected.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/import-pgn",
+    tag = "games",
+    request_body(content = PgnImportRequest, description = "JSON, or multipart/form-data with a `pgn` file field"),
+    responses(
+        (status = 201, description = "Game imported", body = PgnImportResponse),
+        (status = 400, description = "Invalid PGN, or more than one game", body = ErrorResponse),
+        (status = 429, description = "Too many active games", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn import_pgn(
+    claims: Claims,
+    pgn: Option<String>,
+    games: GameStore,
+    store: GameBackend,
+) -> Result<impl Reply, warp::Rejection> {
+    let pgn = match pgn {
+        Some(pgn) => pgn,
+        None => {
+            let error = ErrorResponse {
+                error: "Missing pgn field".to_string(),
+                code: error_codes::INVALID_REQUEST,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    let replayed = PgnParser::parse_game(&pgn)
+        .map_err(|e| format!("Invalid PGN: {}", e))
+        .and_then(|game| game.to_game_state().map_err(|e| e.to_string()));
+    let mut game_state = match replayed {
+        Ok(game_state) => game_state,
+        Err(e) => {
+            let error = ErrorResponse {
+                error: e,
+                code: error_codes::INVALID_PGN,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    let game_id = Uuid::new_v4().to_string();
+    game_state.created_by = Some(claims.sub);
+    game_state.game_mode = GameMode::Analysis;
+
+    if let Err(reply) = reserve_game(&games, &[claims.sub], &game_id, game_state.clone()).await {
+        return Ok(reply);
+    }
+
+    if let Err(e) = store.save_game(&game_id, &game_state).await {
+        eprintln!("Failed to persist game {}: {}", game_id, e);
+        games.write().await.remove(&game_id);
+        let error = ErrorResponse {
+            error: "Failed to create game".to_string(),
+            code: error_codes::INTERNAL_ERROR,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+
+    let move_count = game_state.history.len();
+    metrics::GAMES_CREATED.inc();
+
+    let response = PgnImportResponse {
+        game_id,
+        move_count,
+    };
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        warp::http::StatusCode::CREATED,
+    ))
+}
+
+/// Updates both players' ratings once a game between two assigned
+/// players ends decisively. Only competitive games between two distinct
+/// players are rated.
+async fn update_ratings_if_decided(db_pool: &Pool, game_id: &str, game_state: &GameState) {
+    if !game_state.game_mode.is_rated() {
+        return;
+    }
+
+    let winner = match game_state.winner() {
+        Some(winner) => winner,
+        None => return,
+    };
+
+    let (winner_id, loser_id) = match (
+        game_state.player_for(winner),
+        game_state.player_for(winner.opposite()),
+    ) {
+        (Some(winner_id), Some(loser_id)) if winner_id != loser_id => (winner_id, loser_id),
+        _ => return,
+    };
+
+    if let Err(e) = db::ratings::update_ratings(winner_id, loser_id, db_pool).await {
+        eprintln!("Failed to update ratings for game {}: {}", game_id, e);
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/leaderboard",
+    tag = "users",
+    params(LeaderboardQuery),
+    responses(
+        (status = 200, description = "Highest-rated players", body = LeaderboardResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn get_leaderboard(
+    query: LeaderboardQuery,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    match db::ratings::leaderboard(&db_pool, limit).await {
+        Ok(players) => Ok(warp::reply::with_status(
+            warp::reply::json(&LeaderboardResponse { players }),
+            warp::http::StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("Failed to load leaderboard: {}", e);
+            let error = ErrorResponse {
+                error: "Failed to load leaderboard".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}/stats",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Player statistics", body = PlayerStats),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(username = %username))]
+pub async fn get_player_stats(
+    username: String,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    match db::stats::player_stats(&db_pool, &username).await {
+        Ok(Some(stats)) => Ok(warp::reply::with_status(
+            warp::reply::json(&stats),
+            warp::http::StatusCode::OK,
+        )),
+        Ok(None) => {
+            let error = ErrorResponse {
+                error: "User not found".to_string(),
+                code: error_codes::USER_NOT_FOUND,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to load stats for {}: {}", username, e);
+            let error = ErrorResponse {
+                error: "Failed to load player stats".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{username}",
+    tag = "users",
+    params(("username" = String, Path, description = "Username")),
+    responses(
+        (status = 200, description = "Public profile", body = PublicProfile),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(username = %username))]
+pub async fn get_public_profile(
+    username: String,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    match db::stats::public_profile(&db_pool, &username).await {
+        Ok(Some(profile)) => Ok(warp::reply::with_status(
+            warp::reply::json(&profile),
+            warp::http::StatusCode::OK,
+        )),
+        // Hidden profiles look the same as missing ones
+        Ok(None) => {
+            let error = ErrorResponse {
+                error: "User not found".to_string(),
+                code: error_codes::USER_NOT_FOUND,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to load profile for {}: {}", username, e);
+            let error = ErrorResponse {
+                error: "Failed to load profile".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Lists the authenticated user's games, newest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/games",
+    tag = "games",
+    params(ListGamesQuery),
+    responses(
+        (status = 200, description = "A page of the user's games", body = GameListResponse),
+        (status = 400, description = "Invalid status filter", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn list_games(
+    claims: Claims,
+    query: ListGamesQuery,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let status = query.status.as_deref();
+    if let Some(status) = status {
+        if !STATUS_FILTERS.contains(&status) {
+            let error = ErrorResponse {
+                error: format!("Invalid status, expected one of: {}", STATUS_FILTERS.join(", ")),
+                code: error_codes::INVALID_REQUEST,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    }
+
+    let offset = (page - 1) * per_page;
+    let rows = db::games::list_games(&db_pool, claims.sub, status, per_page, offset).await;
+    let total = db::games::count_games(&db_pool, claims.sub, status).await;
+
+    match (rows, total) {
+        (Ok(rows), Ok(total)) => {
+            let games = rows
+                .into_iter()
+                .map(|row| GameSummary {
+                    game_id: row.id,
+                    status: row.state.status,
+                    current_player: row.state.current_player,
+                    fullmove_number: row.state.fullmove_number,
+                    created_at: row.created_at,
+                })
+                .collect();
+
+            let response = GameListResponse {
+                games,
+                total,
+                page,
+                per_page,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Failed to list games: {}", e);
+            let error = ErrorResponse {
+                error: "Failed to list games".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Current game state", body = GameState),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_game_state(
     game_id: String,
     games: GameStore,
+    store: GameBackend,
 ) -> Result<impl Reply, warp::Rejection> {
-    let games_map = games.lock().unwrap();
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
     
     if let Some(game_state) = games_map.get(&game_id) {
-        #[derive(Serialize)]
-        struct FenResponse {
-            fen: String,
-        }
-        
-        let response = FenResponse {
-            fen: game_state.to_fen(),
-        };
-        
         Ok(warp::reply::with_status(
-            warp::reply::json(&response),
+            warp::reply::json(game_state),
             warp::http::StatusCode::OK,
         ))
     } else {
         let error = ErrorResponse {
             error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+/// Checks a move against the current position without applying it
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/validate-move",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    request_body = MoveRequest,
+    responses(
+        (status = 200, description = "Whether the move is legal", body = ValidateMoveResponse),
+        (status = 400, description = "Malformed move", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, from = %move_request.from, to = %move_request.to))]
+pub async fn validate_move(
+    game_id: String,
+    move_request: MoveRequest,
+    games: GameStore,
+    store: GameBackend,
+) -> Result<impl Reply, warp::Rejection> {
+    let _timer = metrics::MOVE_LATENCY.start_timer();
+    let chess_move = match move_request.to_move() {
+        Ok(m) => m,
+        Err(e) => {
+            let response = ValidateMoveResponse {
+                valid: false,
+                reason: Some(e),
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
+
+    let game_state = match games_map.get(&game_id) {
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    let chess_move = game_state.infer_castling(chess_move);
+    let chess_move = game_state.infer_en_passant(chess_move);
+    let result = if game_state.is_game_over() {
+        Err(ChessError::GameOver)
+    } else {
+        game_state.validate_move(&chess_move)
+    };
+
+    match result {
+        Ok(()) => Ok(warp::reply::with_status(
+            warp::reply::json(&ValidateMoveResponse {
+                valid: true,
+                reason: None,
+            }),
+            warp::http::StatusCode::OK,
+        )),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&ValidateMoveResponse {
+                valid: false,
+                reason: Some(e.to_string()),
+            }),
+            warp::http::StatusCode::BAD_REQUEST,
+        )),
+    }
+}
+
+/// Deletes a game. Only the player who created it may do so. A started,
+/// unfinished game against an opponent can't be deleted away; the
+/// creator resigns it instead.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/games/{id}",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Game in progress against an opponent, resigned instead", body = GameState),
+        (status = 204, description = "Game deleted"),
+        (status = 403, description = "Not the game's creator", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn delete_game(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let mut games_map = games.write().await;
+
+    let game_state = match games_map.get_mut(&game_id) {
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            )
+            .into_response());
+        }
+    };
+
+    if game_state.created_by != Some(claims.sub) {
+        let error = ErrorResponse {
+            error: "Only the creator of this game can delete it".to_string(),
+            code: error_codes::NOT_GAME_CREATOR,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::FORBIDDEN,
+        )
+        .into_response());
+    }
+
+    // Walking away from a game under way loses it rather than erasing it
+    let started = !game_state.history.is_empty() && !game_state.is_game_over();
+    let resigning = game_state
+        .color_of(claims.sub)
+        .filter(|_| started && game_state.has_both_players());
+    if let Some(color) = resigning {
+        if let Err(e) = game_state.resign(color) {
+            let error = ErrorResponse {
+                error: e.to_string(),
+                code: e.error_code(),
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            )
+            .into_response());
+        }
+        game_state.advance_event_id();
+        let game_state = game_state.clone();
+        drop(games_map);
+
+        if let Err(e) = store.save_game(&game_id, &game_state).await {
+            eprintln!("Failed to save game {}: {}", game_id, e);
+        }
+        metrics::record_game_completed(&game_state.status);
+        update_ratings_if_decided(&db_pool, &game_id, &game_state).await;
+        broadcast_game_state(&subscriptions, &game_id, &game_state).await;
+        publish_game_event(&events, &game_id, &game_state).await;
+
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&game_state),
+            warp::http::StatusCode::OK,
+        )
+        .into_response());
+    }
+
+    if let Err(e) = store.delete_game(&game_id).await {
+        eprintln!("Failed to delete game {}: {}", game_id, e);
+        let error = ErrorResponse {
+            error: "Failed to delete game".to_string(),
+            code: error_codes::INTERNAL_ERROR,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response());
+    }
+
+    games_map.remove(&game_id);
+
+    Ok(warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT).into_response())
+}
+
+/// Plays a move for the requesting player. Checkmate, stalemate, dead
+/// positions, fivefold repetition and the seventy-five-move rule end the
+/// game straight away; threefold repetition and the fifty-move rule only
+/// make a draw claimable, see `claim-draw`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/moves",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    request_body = MoveRequest,
+    responses(
+        (status = 200, description = "Game state after the move", body = GameState),
+        (status = 400, description = "Illegal move", body = ErrorResponse),
+        (status = 403, description = "Not the player to move", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    skip_all,
+    fields(game_id = %game_id, user_id = claims.sub, from = %move_request.from, to = %move_request.to)
+)]
+pub async fn make_move(
+    game_id: String,
+    claims: Claims,
+    move_request: MoveRequest,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let received_at = Instant::now();
+    let _timer = metrics::MOVE_LATENCY.start_timer();
+    let chess_move = match move_request.to_move() {
+        Ok(m) => m,
+        Err(e) => {
+            let error = ErrorResponse {
+                error: e,
+                code: error_codes::INVALID_MOVE,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let mut games_map = games.write().await;
+    
+    if let Some(game_state) = games_map.get_mut(&game_id) {
+        // Games between two assigned players only accept moves from the side to play
+        if game_state.has_both_players()
+            && game_state.player_for(game_state.current_player) != Some(claims.sub)
+        {
+            let error = ErrorResponse {
+                error: "Only the player to move can make a move in this game".to_string(),
+                code: error_codes::NOT_YOUR_GAME,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+
+        if !game_state.is_participant(claims.sub) {
+            let error = ErrorResponse {
+                error: "You are not a player in this game".to_string(),
+                code: error_codes::NOT_A_PLAYER,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+
+        if !game_state.is_players_turn(claims.sub) {
+            let error = ErrorResponse {
+                error: ChessError::NotYourTurn.to_string(),
+                code: ChessError::NotYourTurn.error_code(),
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+
+        let chess_move = game_state.infer_castling(chess_move);
+        let chess_move = game_state.infer_en_passant(chess_move);
+        let move_number = game_state.fullmove_number;
+        let color = game_state.current_player;
+        let san = notation::move_to_san(&chess_move, game_state);
+        let played = chess_move.clone();
+        let previous = game_state.board.clone();
+
+        // Persisting and broadcasting happen after the lock is released, so
+        // other games don't wait on this one's database round trips
+        match game_state.make_move(chess_move) {
+            Ok(()) => {
+                game_state.advance_event_id();
+                let game_state = game_state.clone();
+                drop(games_map);
+
+                let move_time = received_at.elapsed();
+                tracing::info!(status = %game_state.status, "move played: {}", played);
+                if let Err(e) = store.save_game(&game_id, &game_state).await {
+                    eprintln!("Failed to save game {}: {}", game_id, e);
+                }
+                record_move(&db_pool, &game_id, move_number, color, &san, move_time, &game_state).await;
+                metrics::record_game_completed(&game_state.status);
+                update_ratings_if_decided(&db_pool, &game_id, &game_state).await;
+                broadcast_move(&subscriptions, &game_id, &game_state, &previous).await;
+                publish_game_event(&events, &game_id, &game_state).await;
+
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&game_state),
+                    warp::http::StatusCode::OK,
+                ))
+            }
+            Err(e) => {
+                // A flag fall ends the game even though the move was rejected
+                if matches!(e, ChessError::TimeExpired) {
+                    game_state.advance_event_id();
+                    let game_state = game_state.clone();
+                    drop(games_map);
+
+                    if let Err(e) = store.save_game(&game_id, &game_state).await {
+                        eprintln!("Failed to save game {}: {}", game_id, e);
+                    }
+                    metrics::record_game_completed(&game_state.status);
+                    update_ratings_if_decided(&db_pool, &game_id, &game_state).await;
+                    broadcast_game_state(&subscriptions, &game_id, &game_state).await;
+                    publish_game_event(&events, &game_id, &game_state).await;
+                }
+
+                let error = ErrorResponse {
+                    error: e.to_string(),
+                    code: e.error_code(),
+                };
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&error),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ))
+            }
+        }
+    } else {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
         };
         Ok(warp::reply::with_status(
             warp::reply::json(&error),
             warp::http::StatusCode::NOT_FOUND,
         ))
     }
-}
\ No newline at end of file
+}
+
+/// Takes back the last move of a casual or analysis game
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/undo",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Game state before the last move", body = GameState),
+        (status = 400, description = "No move to take back", body = ErrorResponse),
+        (status = 403, description = "Undo not allowed in this game", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn undo_move(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let mut games_map = games.write().await;
+
+    let game_state = match games_map.get_mut(&game_id) {
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    if !game_state.is_participant(claims.sub) {
+        let error = ErrorResponse {
+            error: "You are not a player in this game".to_string(),
+            code: error_codes::NOT_A_PLAYER,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+
+    if !game_state.game_mode.allows_undo() {
+        let error = ErrorResponse {
+            error: "Moves can only be taken back in casual or analysis games".to_string(),
+            code: error_codes::UNDO_NOT_ALLOWED,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+
+    if let Err(e) = game_state.undo_move() {
+        let error = ErrorResponse {
+            error: e.to_string(),
+            code: e.error_code(),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    game_state.advance_event_id();
+    let game_state = game_state.clone();
+    drop(games_map);
+
+    if let Err(e) = store.save_game(&game_id, &game_state).await {
+        eprintln!("Failed to save game {}: {}", game_id, e);
+    }
+    if let Err(e) = db::moves::delete_last_move(&db_pool, &game_id).await {
+        eprintln!("Failed to delete last move for game {}: {}", game_id, e);
+    }
+    broadcast_game_state(&subscriptions, &game_id, &game_state).await;
+    publish_game_event(&events, &game_id, &game_state).await;
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&game_state),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+/// Applies a resignation or draw action on behalf of a participant, for their
+/// assigned color or the side to move if they don't have one
+#[allow(clippy::too_many_arguments)]
+async fn apply_game_action(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+    action: fn(&mut GameState, Color) -> Result<(), ChessError>,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let mut games_map = games.write().await;
+
+    let game_state = match games_map.get_mut(&game_id) {
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    if !game_state.is_participant(claims.sub) {
+        let error = ErrorResponse {
+            error: "You are not a player in this game".to_string(),
+            code: error_codes::NOT_A_PLAYER,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+
+    let color = game_state
+        .color_of(claims.sub)
+        .unwrap_or(game_state.current_player);
+    if let Err(e) = action(game_state, color) {
+        let error = ErrorResponse {
+            error: e.to_string(),
+            code: e.error_code(),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    game_state.advance_event_id();
+    let game_state = game_state.clone();
+    drop(games_map);
+
+    if let Err(e) = store.save_game(&game_id, &game_state).await {
+        eprintln!("Failed to save game {}: {}", game_id, e);
+    }
+    metrics::record_game_completed(&game_state.status);
+    update_ratings_if_decided(&db_pool, &game_id, &game_state).await;
+    broadcast_game_state(&subscriptions, &game_id, &game_state).await;
+    publish_game_event(&events, &game_id, &game_state).await;
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&game_state),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+/// The requesting player resigns
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/resign",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Game state after resigning", body = GameState),
+        (status = 400, description = "Game already over", body = ErrorResponse),
+        (status = 403, description = "Not a player in this game", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn resign_game(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    apply_game_action(
+        game_id,
+        claims,
+        games,
+        store,
+        subscriptions,
+        events,
+        db_pool,
+        GameState::resign,
+    )
+    .await
+}
+
+/// The requesting player offers a draw, which stands until the opponent plays a move
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/draw-offer",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Game state with the pending offer", body = GameState),
+        (status = 400, description = "Game already over", body = ErrorResponse),
+        (status = 403, description = "Not a player in this game", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn offer_draw(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    apply_game_action(
+        game_id,
+        claims,
+        games,
+        store,
+        subscriptions,
+        events,
+        db_pool,
+        GameState::offer_draw,
+    )
+    .await
+}
+
+/// The requesting player accepts the opponent's pending draw offer
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/draw-accept",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Game state after the draw", body = GameState),
+        (status = 400, description = "No draw offer to accept", body = ErrorResponse),
+        (status = 403, description = "Not a player in this game", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn accept_draw(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    apply_game_action(
+        game_id,
+        claims,
+        games,
+        store,
+        subscriptions,
+        events,
+        db_pool,
+        GameState::accept_draw,
+    )
+    .await
+}
+
+/// The requesting player claims a draw under the fifty-move rule or
+/// threefold repetition on their turn. Neither ends the game on its own;
+/// the seventy-five-move rule and fivefold repetition draw without a claim.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/claim-draw",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Game state after the draw", body = GameState),
+        (status = 400, description = "No draw claim available, or not your turn", body = ErrorResponse),
+        (status = 403, description = "Not a player in this game", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn claim_draw(
+    game_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    apply_game_action(
+        game_id,
+        claims,
+        games,
+        store,
+        subscriptions,
+        events,
+        db_pool,
+        GameState::claim_draw,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/moves",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Legal moves for the side to move", body = MovesResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_legal_moves(
+    game_id: String,
+    games: GameStore,
+    store: GameBackend,
+) -> Result<impl Reply, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
+    
+    if let Some(game_state) = games_map.get(&game_id) {
+        let legal_moves = game_state.get_legal_moves();
+        
+        // Convert moves to a more readable format
+        let move_strings: Vec<String> = legal_moves
+            .iter()
+            .map(|m| {
+                format!(
+                    "{}-{}",
+                    notation::square_to_algebraic(m.from),
+                    notation::square_to_algebraic(m.to)
+                )
+            })
+            .collect();
+        let san_strings: Vec<String> = legal_moves
+            .iter()
+            .map(|m| notation::move_to_san(m, game_state))
+            .collect();
+        
+        let response = MovesResponse {
+            count: move_strings.len(),
+            moves: move_strings,
+            san: san_strings,
+        };
+        
+        Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+/// Destination squares for the current player's piece on `square`
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/legal-moves/{square}",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID"), ("square" = String, Path, description = "Square in algebraic notation, e.g. e2")),
+    responses(
+        (status = 200, description = "Destination squares", body = SquareMovesResponse),
+        (status = 400, description = "Invalid square", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, square = %square))]
+pub async fn get_square_moves(
+    game_id: String,
+    square: String,
+    games: GameStore,
+    store: GameBackend,
+) -> Result<impl Reply, warp::Rejection> {
+    let from = match notation::algebraic_to_square(&square) {
+        Some(from) => from,
+        None => {
+            let error = ErrorResponse {
+                error: "Invalid square".to_string(),
+                code: error_codes::INVALID_REQUEST,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+    };
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
+
+    let game_state = match games_map.get(&game_id) {
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    match game_state.board.get_piece(from) {
+        Some(piece) if piece.color == game_state.current_player => {}
+        _ => {
+            let error = ErrorResponse {
+                error: format!("No {} piece on {}", game_state.current_player, from),
+                code: error_codes::INVALID_REQUEST,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    }
+
+    // Promotions yield several moves to the same square, so dedup destinations
+    let mut destinations: Vec<String> = Vec::new();
+    for chess_move in game_state.get_legal_moves_from(from) {
+        let to = notation::square_to_algebraic(chess_move.to);
+        if !destinations.contains(&to) {
+            destinations.push(to);
+        }
+    }
+
+    let response = SquareMovesResponse {
+        from: notation::square_to_algebraic(from),
+        destinations,
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/fen",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Current position in FEN", body = FenResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_game_fen(
+    game_id: String,
+    games: GameStore,
+    store: GameBackend,
+) -> Result<impl Reply, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
+    
+    if let Some(game_state) = games_map.get(&game_id) {
+        let response = FenResponse {
+            fen: game_state.to_fen(),
+        };
+        
+        Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/history",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID"), HistoryQuery),
+    responses(
+        (status = 200, description = "Every move played", body = HistoryResponse),
+        (status = 400, description = "Invalid format", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_game_history(
+    game_id: String,
+    query: HistoryQuery,
+    games: GameStore,
+    store: GameBackend,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let format = query.format.unwrap_or_else(|| "san".to_string());
+    if !matches!(format.as_str(), "san" | "uci" | "coordinate") {
+        let error = ErrorResponse {
+            error: "Invalid format, expected one of: san, uci, coordinate".to_string(),
+            code: error_codes::INVALID_REQUEST,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
+    
+    if let Some(game_state) = games_map.get(&game_id) {
+        // Games played before moves were recorded individually only have
+        // their history in the game state, so rebuild it from that
+        let moves = match db::moves::load_game_history(&db_pool, &game_id).await {
+            Ok(stored) if stored.len() == game_state.history.len() => {
+                stored_move_records(stored, &format)
+            }
+            Ok(_) => build_move_records(game_state, &format),
+            Err(e) => {
+                eprintln!("Failed to load history for game {}: {}", game_id, e);
+                build_move_records(game_state, &format)
+            }
+        };
+        let response = HistoryResponse { moves };
+        
+        Ok(warp::reply::with_status(
+            warp::reply::json(&response),
+            warp::http::StatusCode::OK,
+        ))
+    } else {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/stats",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "Time used by each player", body = GameTimeStats),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_game_stats(
+    game_id: String,
+    games: GameStore,
+    store: GameBackend,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    if !games.read().await.contains_key(&game_id) {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        ));
+    }
+
+    match db::moves::load_game_history(&db_pool, &game_id).await {
+        Ok(moves) => Ok(warp::reply::with_status(
+            warp::reply::json(&GameTimeStats::from_moves(&moves)),
+            warp::http::StatusCode::OK,
+        )),
+        Err(e) => {
+            eprintln!("Failed to load history for game {}: {}", game_id, e);
+            let error = ErrorResponse {
+                error: "Failed to load game stats".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Plain-text diagram of the current position, for debugging and terminals.
+/// `?format=unicode` draws the pieces with chess symbols. Captured pieces
+/// are listed under the board.
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/ascii",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID"), DiagramQuery),
+    responses(
+        (status = 200, description = "Text diagram of the position", body = String, content_type = "text/plain"),
+        (status = 400, description = "Invalid format", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_game_diagram(
+    game_id: String,
+    query: DiagramQuery,
+    games: GameStore,
+    store: GameBackend,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let unicode = match query.format.as_deref() {
+        None | Some("ascii") => false,
+        Some("unicode") => true,
+        Some(_) => {
+            let error = ErrorResponse {
+                error: "Invalid format, expected one of: ascii, unicode".to_string(),
+                code: error_codes::INVALID_REQUEST,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            )
+            .into_response());
+        }
+    };
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let games_map = games.read().await;
+    let game_state = match games_map.get(&game_id) {
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            )
+            .into_response());
+        }
+    };
+
+    let mut diagram = if unicode {
+        game_state.to_unicode()
+    } else {
+        game_state.to_ascii()
+    };
+    for (capturer, captured_color) in [(Color::White, Color::Black), (Color::Black, Color::White)] {
+        let captured = game_state.get_captured_pieces(captured_color);
+        if !captured.is_empty() {
+            let pieces: String = captured.iter().map(|piece| piece.to_fen_char()).collect();
+            diagram.push_str(&format!("Captured by {}: {}\n", capturer, pieces));
+        }
+    }
+    Ok(warp::reply::with_header(diagram, "Content-Type", "text/plain; charset=utf-8").into_response())
+}
+
+/// Serves the game as a PGN file download, with player names and the
+/// creation date filled in from the database
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/pgn",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 200, description = "The game as a PGN file", body = String, content_type = "application/x-chess-pgn"),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn get_game_pgn(
+    game_id: String,
+    games: GameStore,
+    store: GameBackend,
+    db_pool: Pool,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let game_state = match games.read().await.get(&game_id) {
+        Some(game_state) => game_state.clone(),
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            )
+            .into_response());
+        }
+    };
+
+    let mut metadata = PgnMetadata {
+        site: game_id.clone(),
+        ..PgnMetadata::default()
+    };
+    match db::games::load_game_record(&db_pool, &game_id).await {
+        Ok(Some(record)) => {
+            metadata.date = record.created_at.format("%Y.%m.%d").to_string();
+            if let Some(white) = record.white_username {
+                metadata.white = white;
+            }
+            if let Some(black) = record.black_username {
+                metadata.black = black;
+            }
+        }
+        Ok(None) => {}
+        Err(e) => eprintln!("Failed to load players for game {}: {}", game_id, e),
+    }
+
+    let pgn = game_state.to_pgn(metadata);
+    let reply = warp::reply::with_header(pgn, "Content-Type", "application/x-chess-pgn");
+    let reply = warp::reply::with_header(
+        reply,
+        "Content-Disposition",
+        format!("attachment; filename=\"game-{}.pgn\"", game_id),
+    );
+    Ok(reply.into_response())
+}
+
+fn engine_move_time_ms() -> u64 {
+    std::env::var("ENGINE_MOVE_TIME_MS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5000)
+}
+
+/// When the engine must stop searching: `ENGINE_MOVE_TIME_MS` from now,
+/// or sooner if the side to move is on a clock and can't afford that much
+fn engine_deadline(game_state: &GameState) -> Instant {
+    let move_time_ms = engine_move_time_ms();
+
+    let budget_ms = match game_state.time_control {
+        TimeControl::Classic { increment_ms, .. } => {
+            let elapsed_ms = game_state
+                .clock
+                .last_move_instant
+                .map_or(0, |last_move| last_move.elapsed().as_millis() as u64);
+            let remaining_ms = game_state
+                .clock
+                .remaining_ms(game_state.current_player)
+                .saturating_sub(elapsed_ms);
+
+            // Spread the remaining time over roughly 30 more moves
+            (remaining_ms / 30 + increment_ms)
+                .min(remaining_ms / 2)
+                .min(move_time_ms)
+        }
+        _ => move_time_ms,
+    };
+
+    Instant::now() + Duration::from_millis(budget_ms)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/engine-move",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    request_body = EngineMoveRequest,
+    responses(
+        (status = 200, description = "The engine's move and the resulting game state", body = EngineMoveResponse),
+        (status = 400, description = "Game already over", body = ErrorResponse),
+        (status = 403, description = "Not a player in this game", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+        (status = 409, description = "Not the engine's turn", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn make_engine_move(
+    game_id: String,
+    claims: Claims,
+    request: EngineMoveRequest,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+    events: GameEvents,
+    db_pool: Pool,
+    engine_table: EngineTable,
+) -> Result<impl Reply, warp::Rejection> {
+    let received_at = Instant::now();
+    let depth = request.depth.unwrap_or(3).clamp(1, 8);
+
+    // Search on a snapshot so the store isn't locked while the engine thinks
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let snapshot = {
+        let games_map = games.read().await;
+        games_map.get(&game_id).cloned()
+    };
+
+    let snapshot = match snapshot {
+        Some(game_state) if !game_state.is_participant(claims.sub) => {
+            let error = ErrorResponse {
+                error: "You are not a player in this game".to_string(),
+                code: error_codes::NOT_A_PLAYER,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+        Some(game_state) if !game_state.is_players_turn(claims.sub) => {
+            let error = ErrorResponse {
+                error: ChessError::NotYourTurn.to_string(),
+                code: ChessError::NotYourTurn.error_code(),
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    let deadline = engine_deadline(&snapshot);
+    let engine = Engine::new(depth).with_table(engine_table);
+    let search = tokio::task::spawn_blocking(move || engine.best_move_timed(&snapshot, deadline));
+    let best_move = match search.await {
+        Ok(Some(chess_move)) => chess_move,
+        Ok(None) => {
+            let error = ErrorResponse {
+                error: "No legal moves available".to_string(),
+                code: error_codes::NO_LEGAL_MOVES,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+        Err(_) => {
+            let error = ErrorResponse {
+                error: "Engine search failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let mut games_map = games.write().await;
+
+    if let Some(game_state) = games_map.get_mut(&game_id) {
+        let move_number = game_state.fullmove_number;
+        let color = game_state.current_player;
+        let san = notation::move_to_san(&best_move, game_state);
+        let previous = game_state.board.clone();
+        match game_state.make_move(best_move.clone()) {
+            Ok(()) => {
+                game_state.advance_event_id();
+                let game_state = game_state.clone();
+                drop(games_map);
+
+                let move_time = received_at.elapsed();
+                if let Err(e) = store.save_game(&game_id, &game_state).await {
+                    eprintln!("Failed to save game {}: {}", game_id, e);
+                }
+                record_move(&db_pool, &game_id, move_number, color, &san, move_time, &game_state).await;
+                metrics::record_game_completed(&game_state.status);
+                update_ratings_if_decided(&db_pool, &game_id, &game_state).await;
+                broadcast_move(&subscriptions, &game_id, &game_state, &previous).await;
+                publish_game_event(&events, &game_id, &game_state).await;
+
+                let response = EngineMoveResponse {
+                    from: notation::square_to_algebraic(best_move.from),
+                    to: notation::square_to_algebraic(best_move.to),
+                    san,
+                    game: &game_state,
+                };
+
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&response),
+                    warp::http::StatusCode::OK,
+                ))
+            }
+            Err(e) => {
+                let error = ErrorResponse {
+                    error: e.to_string(),
+                    code: e.error_code(),
+                };
+                Ok(warp::reply::with_status(
+                    warp::reply::json(&error),
+                    warp::http::StatusCode::CONFLICT,
+                ))
+            }
+        }
+    } else {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        ))
+    }
+}
+
+const DEFAULT_ANALYSIS_DEPTH: u8 = 4;
+const MAX_ANALYSIS_DEPTH: u8 = 6;
+const DEFAULT_ANALYSIS_LINES: usize = 3;
+const MAX_ANALYSIS_LINES: usize = 10;
+
+/// Scores the candidate moves in a position of the game. With `ply`, the
+/// position before that move is analyzed and candidates are compared with
+/// the move that was played. Rated games can only be analyzed once over.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/analyze",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    request_body = AnalyzeRequest,
+    responses(
+        (status = 200, description = "Scored candidate moves", body = AnalysisResult),
+        (status = 400, description = "Invalid ply", body = ErrorResponse),
+        (status = 403, description = "Rated game still in progress", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn analyze_game(
+    game_id: String,
+    claims: Claims,
+    request: AnalyzeRequest,
+    games: GameStore,
+    store: GameBackend,
+    engine_table: EngineTable,
+) -> Result<impl Reply, warp::Rejection> {
+    let depth = request
+        .depth
+        .unwrap_or(DEFAULT_ANALYSIS_DEPTH)
+        .clamp(1, MAX_ANALYSIS_DEPTH);
+    let num_lines = request
+        .num_lines
+        .unwrap_or(DEFAULT_ANALYSIS_LINES)
+        .clamp(1, MAX_ANALYSIS_LINES);
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let snapshot = {
+        let games_map = games.read().await;
+        games_map.get(&game_id).cloned()
+    };
+
+    let snapshot = match snapshot {
+        Some(game_state) if game_state.game_mode.is_rated() && !game_state.is_game_over() => {
+            let error = ErrorResponse {
+                error: "Rated games can only be analyzed once they are over".to_string(),
+                code: error_codes::GAME_IN_PROGRESS,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    let (position, played) = match request.ply {
+        None => (snapshot, None),
+        Some(ply) => match (snapshot.position_at(ply), snapshot.history.get(ply)) {
+            (Some(position), Some(played)) => (position, Some(played.clone())),
+            _ => {
+                let error = ErrorResponse {
+                    error: format!("Game has {} moves", snapshot.history.len()),
+                    code: error_codes::INVALID_REQUEST,
+                };
+                return Ok(warp::reply::with_status(
+                    warp::reply::json(&error),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ));
+            }
+        },
+    };
+
+    let deadline = Instant::now() + Duration::from_millis(engine_move_time_ms());
+    let analysis = tokio::task::spawn_blocking(move || {
+        let engine = Engine::new(depth).with_table(engine_table);
+        analyze_position(&engine, &position, played.as_ref(), num_lines, deadline)
+    });
+
+    match analysis.await {
+        Ok(Some(result)) => Ok(warp::reply::with_status(
+            warp::reply::json(&result),
+            warp::http::StatusCode::OK,
+        )),
+        Ok(None) => {
+            let error = ErrorResponse {
+                error: "No legal moves available".to_string(),
+                code: error_codes::NO_LEGAL_MOVES,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::BAD_REQUEST,
+            ))
+        }
+        Err(_) => {
+            let error = ErrorResponse {
+                error: "Engine search failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Compares moves by squares and promotion only, so a move from the
+/// history matches the engine's copy whatever its flags
+fn same_move(a: &Move, b: &Move) -> bool {
+    a.from == b.from && a.to == b.to && a.promotion == b.promotion
+}
+
+fn analyze_position(
+    engine: &Engine,
+    position: &GameState,
+    played: Option<&Move>,
+    num_lines: usize,
+    deadline: Instant,
+) -> Option<AnalysisResult> {
+    let depth = engine.max_depth;
+    let scored = engine.score_moves_timed(position, deadline);
+    let best = scored.first()?;
+
+    let played_score = played.and_then(|played| {
+        scored
+            .iter()
+            .find(|candidate| same_move(&candidate.chess_move, played))
+            .map(|candidate| candidate.score)
+    });
+
+    let mut line_position = position.clone();
+    line_position.time_control = TimeControl::Unlimited;
+    let mut principal_variation = Vec::new();
+    for chess_move in engine.principal_variation(position, &best.chess_move, depth as usize) {
+        principal_variation.push(notation::move_to_san(&chess_move, &line_position));
+        if line_position.make_move(chess_move).is_err() {
+            break;
+        }
+    }
+
+    let candidate_moves = scored
+        .iter()
+        .take(num_lines)
+        .map(|candidate| CandidateMove {
+            san: notation::move_to_san(&candidate.chess_move, position),
+            uci: notation::move_to_uci(&candidate.chess_move),
+            score: candidate.score,
+            delta: played_score.map(|played_score| candidate.score - played_score),
+        })
+        .collect();
+
+    Some(AnalysisResult {
+        fen: position.to_fen(),
+        depth,
+        best_move: notation::move_to_san(&best.chess_move, position),
+        score: best.score,
+        principal_variation,
+        played_move: played.map(|played| notation::move_to_san(played, position)),
+        candidate_moves,
+    })
+}
+
+const DEFAULT_ANNOTATION_DEPTH: u8 = 3;
+const MAX_ANNOTATION_DEPTH: u8 = 5;
+
+/// Starts annotating every move of the game in the background and replies
+/// 202 straight away; annotations show up in the history as they are saved
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/annotate",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    request_body = AnnotateRequest,
+    responses(
+        (status = 202, description = "Annotation started", body = AnnotateResponse),
+        (status = 403, description = "Rated game still in progress", body = ErrorResponse),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id, user_id = claims.sub))]
+pub async fn annotate_game(
+    game_id: String,
+    claims: Claims,
+    request: AnnotateRequest,
+    games: GameStore,
+    store: GameBackend,
+    db_pool: Pool,
+    engine_table: EngineTable,
+) -> Result<impl Reply, warp::Rejection> {
+    let depth = request
+        .depth
+        .unwrap_or(DEFAULT_ANNOTATION_DEPTH)
+        .clamp(1, MAX_ANNOTATION_DEPTH);
+
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+    let snapshot = {
+        let games_map = games.read().await;
+        games_map.get(&game_id).cloned()
+    };
+
+    let snapshot = match snapshot {
+        Some(game_state) if game_state.game_mode.is_rated() && !game_state.is_game_over() => {
+            let error = ErrorResponse {
+                error: "Rated games can only be analyzed once they are over".to_string(),
+                code: error_codes::GAME_IN_PROGRESS,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::FORBIDDEN,
+            ));
+        }
+        Some(game_state) => game_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            ));
+        }
+    };
+
+    let response = AnnotateResponse {
+        game_id: game_id.clone(),
+        move_count: snapshot.history.len(),
+    };
+
+    tokio::spawn(async move {
+        let move_time = Duration::from_millis(engine_move_time_ms());
+        let engine = Engine::new(depth).with_table(engine_table);
+        let annotations =
+            tokio::task::spawn_blocking(move || annotate_moves(&engine, &snapshot, move_time))
+                .await;
+
+        match annotations {
+            Ok(annotations) => {
+                let saved = db::moves::save_annotations(&db_pool, &game_id, &annotations).await;
+                if let Err(e) = saved {
+                    eprintln!("Failed to save annotations for game {}: {}", game_id, e);
+                }
+            }
+            Err(e) => eprintln!("Annotating game {} failed: {}", game_id, e),
+        }
+    });
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        warp::http::StatusCode::ACCEPTED,
+    ))
+}
+
+/// Judges each move of the game by the centipawns it lost against the
+/// engine's best move, searching each position for at most `move_time`
+fn annotate_moves(
+    engine: &Engine,
+    game_state: &GameState,
+    move_time: Duration,
+) -> Vec<Option<MoveAnnotation>> {
+    let mut position = match game_state.initial_position() {
+        Ok(position) => position,
+        Err(_) => return Vec::new(),
+    };
+    position.time_control = TimeControl::Unlimited;
+
+    let mut annotations = Vec::with_capacity(game_state.history.len());
+    for played in &game_state.history {
+        let scored = engine.score_moves_timed(&position, Instant::now() + move_time);
+
+        let annotation = scored.first().and_then(|best| {
+            let played_score = scored
+                .iter()
+                .find(|candidate| same_move(&candidate.chess_move, played))?
+                .score;
+            let only_move = match scored.get(1) {
+                Some(second) => best.score - second.score > 200,
+                None => true,
+            };
+            Some(MoveAnnotation::from_centipawn_loss(best.score - played_score, only_move))
+        });
+        annotations.push(annotation);
+
+        if position.make_move(played.clone()).is_err() {
+            break;
+        }
+    }
+
+    annotations
+}
+
+#[cfg(debug_assertions)]
+#[derive(Deserialize)]
+pub struct PerftRequest {
+    pub fen: Option<String>,
+    pub depth: u8,
+}
+
+#[cfg(debug_assertions)]
+#[tracing::instrument(skip_all)]
+pub async fn run_perft(request: PerftRequest) -> Result<impl Reply, warp::Rejection> {
+    let mut game_state = match request.fen {
+        Some(ref fen) => match GameState::from_fen(fen) {
+            Ok(game_state) => game_state,
+            Err(e) => {
+                let error = ErrorResponse {
+                    error: e.to_string(),
+                    code: e.error_code(),
+                };
+                return Ok(warp::reply::with_status(
+                    warp::reply::json(&error),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ));
+            }
+        },
+        None => GameState::new(),
+    };
+
+    let depth = request.depth.min(6);
+    let fen = game_state.to_fen();
+
+    match tokio::task::spawn_blocking(move || game_state.perft(depth)).await {
+        Ok(nodes) => {
+            #[derive(Serialize)]
+            struct PerftResponse {
+                fen: String,
+                depth: u8,
+                nodes: u64,
+            }
+
+            let response = PerftResponse { fen, depth, nodes };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Err(_) => {
+            let error = ErrorResponse {
+                error: "Perft failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// The database gets this long to answer before the service counts as degraded
+const HEALTH_DB_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub service: &'static str,
+    pub version: &'static str,
+    pub db: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub db_error: Option<String>,
+    pub tls: bool,
+    pub uptime_secs: u64,
+}
+
+/// Reports 200 when the database answers and 503 otherwise, so load
+/// balancers can take the instance out of rotation
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (status = 200, description = "Service and database are up", body = HealthResponse),
+        (status = 503, description = "Database unavailable", body = HealthResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn health_check(db_pool: Pool) -> Result<impl Reply, warp::Rejection> {
+    let db_error = match tokio::time::timeout(HEALTH_DB_TIMEOUT, db::ping(&db_pool)).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some("Timed out waiting for the database".to_string()),
+    };
+
+    let (status, code) = match db_error {
+        None => ("healthy", warp::http::StatusCode::OK),
+        Some(_) => ("degraded", warp::http::StatusCode::SERVICE_UNAVAILABLE),
+    };
+
+    let response = HealthResponse {
+        status,
+        service: "chess-engine",
+        version: env!("CARGO_PKG_VERSION"),
+        db: if db_error.is_none() { "ok" } else { "error" },
+        db_error,
+        tls: TLS_ENABLED.load(Ordering::Relaxed),
+        uptime_secs: START_TIME.elapsed().as_secs(),
+    };
+
+    Ok(warp::reply::with_status(warp::reply::json(&response), code))
+}
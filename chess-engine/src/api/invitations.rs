@@ -0,0 +1,413 @@
+use crate::api::handlers::{reserve_game, ColorPreference, ErrorResponse, GameResponse, GameStore};
+use crate::api::store::GameBackend;
+use crate::auth::jwt::Claims;
+use crate::chess::{Color, GameMode, GameState, TimeControl};
+use crate::db;
+use crate::db::invitations::{InvitationRow, STATUS_ACCEPTED, STATUS_DECLINED};
+use crate::error_codes;
+use crate::metrics;
+use chrono::{DateTime, Duration, Utc};
+use deadpool_postgres::Pool;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::Reply;
+
+/// How long an invitation can be accepted for
+const INVITATION_TTL_HOURS: i64 = 24;
+
+/// The game an invitation proposes, stored as `game_settings`.
+/// `color_preference` is the color the inviting player wants.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct InvitationSettings {
+    pub time_control: Option<TimeControl>,
+    pub color_preference: Option<ColorPreference>,
+    #[serde(default)]
+    pub game_mode: GameMode,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateInvitationRequest {
+    pub to_username: String,
+    #[serde(flatten)]
+    pub settings: InvitationSettings,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InvitationResponse {
+    pub id: String,
+    pub from_username: String,
+    pub to_username: String,
+    pub status: String,
+    #[schema(value_type = Object)]
+    pub game_settings: serde_json::Value,
+    pub game_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<InvitationRow> for InvitationResponse {
+    fn from(row: InvitationRow) -> Self {
+        Self {
+            id: row.id,
+            from_username: row.from_username,
+            to_username: row.to_username,
+            status: row.status,
+            game_settings: row.game_settings,
+            game_id: row.game_id,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct InvitationListResponse {
+    pub incoming: Vec<InvitationResponse>,
+    pub outgoing: Vec<InvitationResponse>,
+}
+
+fn error_reply(
+    error: &str,
+    code: &'static str,
+    status: StatusCode,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let error = ErrorResponse {
+        error: error.to_string(),
+        code,
+    };
+    warp::reply::with_status(warp::reply::json(&error), status)
+}
+
+/// Challenges another player to a game
+#[utoipa::path(
+    post,
+    path = "/api/v1/invitations",
+    tag = "invitations",
+    request_body = CreateInvitationRequest,
+    responses(
+        (status = 201, description = "Invitation sent", body = InvitationResponse),
+        (status = 400, description = "Cannot invite yourself", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub, to_username = %request.to_username))]
+pub async fn create_invitation(
+    claims: Claims,
+    request: CreateInvitationRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let to_user_id = match db::users::find_user_id(&db_pool, request.to_username.trim()).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            return Ok(error_reply(
+                "User not found",
+                error_codes::USER_NOT_FOUND,
+                StatusCode::NOT_FOUND,
+            ));
+        }
+        Err(e) => {
+            eprintln!("Failed to look up user {}: {}", request.to_username, e);
+            return Ok(error_reply(
+                "Failed to create invitation",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    if to_user_id == claims.sub {
+        return Ok(error_reply(
+            "You cannot invite yourself",
+            error_codes::INVALID_REQUEST,
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let game_settings = match serde_json::to_value(&request.settings) {
+        Ok(game_settings) => game_settings,
+        Err(e) => {
+            eprintln!("Failed to serialize invitation settings: {}", e);
+            return Ok(error_reply(
+                "Failed to create invitation",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let created_at = Utc::now();
+    let expires_at = created_at + Duration::hours(INVITATION_TTL_HOURS);
+    let result = db::invitations::create_invitation(
+        &db_pool,
+        claims.sub,
+        to_user_id,
+        &game_settings,
+        expires_at,
+    )
+    .await;
+
+    match result {
+        Ok(id) => {
+            tracing::info!(invitation_id = %id, "invitation sent");
+            let response = InvitationResponse {
+                id,
+                from_username: claims.username,
+                to_username: request.to_username.trim().to_string(),
+                status: db::invitations::STATUS_PENDING.to_string(),
+                game_settings,
+                game_id: None,
+                created_at,
+                expires_at,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                StatusCode::CREATED,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to create invitation: {}", e);
+            Ok(error_reply(
+                "Failed to create invitation",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Pending invitations sent to and by the current user
+#[utoipa::path(
+    get,
+    path = "/api/v1/invitations",
+    tag = "invitations",
+    responses(
+        (status = 200, description = "Pending invitations", body = InvitationListResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn list_invitations(
+    claims: Claims,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    match db::invitations::list_open_invitations(&db_pool, claims.sub).await {
+        Ok(rows) => {
+            let (incoming, outgoing): (Vec<_>, Vec<_>) = rows
+                .into_iter()
+                .partition(|row| row.to_user_id == claims.sub);
+            let response = InvitationListResponse {
+                incoming: incoming.into_iter().map(InvitationResponse::from).collect(),
+                outgoing: outgoing.into_iter().map(InvitationResponse::from).collect(),
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                StatusCode::OK,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to list invitations: {}", e);
+            Ok(error_reply(
+                "Failed to list invitations",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Loads an invitation addressed to `user_id` that can still be answered
+async fn load_open_invitation(
+    db_pool: &Pool,
+    invitation_id: &str,
+    user_id: i32,
+) -> Result<InvitationRow, warp::reply::WithStatus<warp::reply::Json>> {
+    let invitation = match db::invitations::load_invitation(db_pool, invitation_id).await {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => {
+            return Err(error_reply(
+                "Invitation not found",
+                error_codes::INVITATION_NOT_FOUND,
+                StatusCode::NOT_FOUND,
+            ));
+        }
+        Err(e) => {
+            eprintln!("Failed to load invitation {}: {}", invitation_id, e);
+            return Err(error_reply(
+                "Failed to load invitation",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    if invitation.to_user_id != user_id {
+        return Err(error_reply(
+            "Only the invited player can answer this invitation",
+            error_codes::NOT_INVITATION_RECIPIENT,
+            StatusCode::FORBIDDEN,
+        ));
+    }
+    if !invitation.is_open() {
+        return Err(error_reply(
+            "This invitation has already been answered or has expired",
+            error_codes::INVITATION_CLOSED,
+            StatusCode::CONFLICT,
+        ));
+    }
+
+    Ok(invitation)
+}
+
+/// Accepts an invitation, creating the game with both players assigned
+#[utoipa::path(
+    post,
+    path = "/api/v1/invitations/{id}/accept",
+    tag = "invitations",
+    params(("id" = String, Path, description = "Invitation ID")),
+    responses(
+        (status = 201, description = "Game created", body = GameResponse),
+        (status = 403, description = "Not the invited player", body = ErrorResponse),
+        (status = 404, description = "Invitation not found", body = ErrorResponse),
+        (status = 409, description = "Invitation already answered or expired", body = ErrorResponse),
+        (status = 429, description = "Too many active games", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(invitation_id = %invitation_id, user_id = claims.sub))]
+pub async fn accept_invitation(
+    invitation_id: String,
+    claims: Claims,
+    games: GameStore,
+    store: GameBackend,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let invitation = match load_open_invitation(&db_pool, &invitation_id, claims.sub).await {
+        Ok(invitation) => invitation,
+        Err(reply) => return Ok(reply),
+    };
+
+    // Settings were written by create_invitation; fall back to defaults if
+    // they no longer parse rather than stranding the invitation
+    let settings: InvitationSettings =
+        serde_json::from_value(invitation.game_settings.clone()).unwrap_or_default();
+
+    let mut game_state = GameState::new();
+    let inviter_color = settings
+        .color_preference
+        .unwrap_or(ColorPreference::Random)
+        .resolve();
+    let (white_player, black_player) = match inviter_color {
+        Color::White => (invitation.from_user_id, invitation.to_user_id),
+        Color::Black => (invitation.to_user_id, invitation.from_user_id),
+    };
+    game_state.white_player = Some(white_player);
+    game_state.black_player = Some(black_player);
+    game_state.created_by = Some(invitation.from_user_id);
+    game_state.game_mode = settings.game_mode;
+    if let Some(time_control) = settings.time_control {
+        game_state.set_time_control(time_control);
+    }
+
+    let game_id = Uuid::new_v4().to_string();
+    let players = [invitation.from_user_id, invitation.to_user_id];
+    if let Err(reply) = reserve_game(&games, &players, &game_id, game_state.clone()).await {
+        return Ok(reply);
+    }
+
+    if let Err(e) = store.save_game(&game_id, &game_state).await {
+        eprintln!("Failed to persist game {}: {}", game_id, e);
+        games.write().await.remove(&game_id);
+        return Ok(error_reply(
+            "Failed to create game",
+            error_codes::INTERNAL_ERROR,
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+
+    // Only one response can close the invitation; the loser's game is discarded
+    let closed = db::invitations::close_invitation(
+        &db_pool,
+        &invitation_id,
+        STATUS_ACCEPTED,
+        Some(&game_id),
+    )
+    .await;
+    let reply = match closed {
+        Ok(true) => None,
+        Ok(false) => Some(error_reply(
+            "This invitation has already been answered or has expired",
+            error_codes::INVITATION_CLOSED,
+            StatusCode::CONFLICT,
+        )),
+        Err(e) => {
+            eprintln!("Failed to accept invitation {}: {}", invitation_id, e);
+            Some(error_reply(
+                "Failed to accept invitation",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    };
+    if let Some(reply) = reply {
+        games.write().await.remove(&game_id);
+        if let Err(e) = store.delete_game(&game_id).await {
+            eprintln!("Failed to delete game {}: {}", game_id, e);
+        }
+        return Ok(reply);
+    }
+
+    metrics::GAMES_CREATED.inc();
+    tracing::info!(game_id = %game_id, "invitation accepted");
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&GameResponse { game_id }),
+        StatusCode::CREATED,
+    ))
+}
+
+/// Declines an invitation
+#[utoipa::path(
+    post,
+    path = "/api/v1/invitations/{id}/decline",
+    tag = "invitations",
+    params(("id" = String, Path, description = "Invitation ID")),
+    responses(
+        (status = 204, description = "Invitation declined"),
+        (status = 403, description = "Not the invited player", body = ErrorResponse),
+        (status = 404, description = "Invitation not found", body = ErrorResponse),
+        (status = 409, description = "Invitation already answered or expired", body = ErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(invitation_id = %invitation_id, user_id = claims.sub))]
+pub async fn decline_invitation(
+    invitation_id: String,
+    claims: Claims,
+    db_pool: Pool,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    if let Err(reply) = load_open_invitation(&db_pool, &invitation_id, claims.sub).await {
+        return Ok(reply.into_response());
+    }
+
+    match db::invitations::close_invitation(&db_pool, &invitation_id, STATUS_DECLINED, None).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Ok(false) => Ok(error_reply(
+            "This invitation has already been answered or has expired",
+            error_codes::INVITATION_CLOSED,
+            StatusCode::CONFLICT,
+        )
+        .into_response()),
+        Err(e) => {
+            eprintln!("Failed to decline invitation {}: {}", invitation_id, e);
+            Ok(error_reply(
+                "Failed to decline invitation",
+                error_codes::INTERNAL_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response())
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::convert::Infallible;
+use std::io::Write;
+use std::time::Instant;
+use uuid::Uuid;
+use warp::http::header::{
+    HeaderMap, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY,
+};
+use warp::http::{HeaderValue, Method, StatusCode};
+use warp::hyper::Body;
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Responses smaller than this are sent uncompressed unless
+/// `COMPRESSION_MIN_BYTES` says otherwise
+pub const DEFAULT_COMPRESSION_MIN_BYTES: usize = 1024;
+
+/// Reuses the caller's `X-Request-ID` when it is present and well-formed,
+/// otherwise generates a fresh UUID for the request
+pub fn request_id() -> impl Filter<Extract = (String,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: HeaderMap| {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string())
+    })
+}
+
+/// Wraps the routes so every response carries an `X-Request-ID` header
+/// and is logged with its method, path, status and duration.
+/// Use as `routes.with(warp::wrap_fn(with_request_id))`.
+pub fn with_request_id<F, R>(
+    routes: F,
+) -> impl Filter<Extract = (Response,), Error = Infallible> + Clone + Send + Sync + 'static
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply + Send,
+{
+    warp::any()
+        .map(Instant::now)
+        .and(request_id())
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(routes)
+        .map(
+            |started: Instant, id: String, method: Method, path: FullPath, reply: R| {
+                let mut response = reply.into_response();
+                if let Ok(value) = HeaderValue::from_str(&id) {
+                    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+                }
+
+                tracing::info!(
+                    request_id = %id,
+                    method = %method,
+                    path = path.as_str(),
+                    status = response.status().as_u16(),
+                    duration_ms = started.elapsed().as_millis() as u64,
+                    "request completed"
+                );
+
+                response
+            },
+        )
+}
+
+/// Client-supplied IDs end up in logs and response headers, so only
+/// short ASCII tokens are accepted
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Reads `COMPRESSION_MIN_BYTES`, falling back to 1KB
+pub fn compression_min_bytes_from_env() -> usize {
+    std::env::var("COMPRESSION_MIN_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_COMPRESSION_MIN_BYTES)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContentCoding {
+    Brotli,
+    Gzip,
+}
+
+impl ContentCoding {
+    fn as_str(self) -> &'static str {
+        match self {
+            ContentCoding::Brotli => "br",
+            ContentCoding::Gzip => "gzip",
+        }
+    }
+
+    fn encode(self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            ContentCoding::Brotli => {
+                let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+                writer.write_all(data)?;
+                Ok(writer.into_inner())
+            }
+            ContentCoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Wraps the routes so bodies of at least `min_size` bytes are compressed
+/// with brotli or gzip, whichever the client's `Accept-Encoding` prefers.
+/// Use as `routes.with(warp::wrap_fn(move |r| with_compression(r, min_size)))`.
+pub fn with_compression<F, R>(
+    routes: F,
+    min_size: usize,
+) -> impl Filter<Extract = (Response,), Error = Infallible> + Clone + Send + Sync + 'static
+where
+    F: Filter<Extract = (R,), Error = Infallible> + Clone + Send + Sync + 'static,
+    R: Reply + Send,
+{
+    warp::header::headers_cloned()
+        .map(|headers: HeaderMap| {
+            headers
+                .get(ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok())
+                .and_then(negotiate_coding)
+        })
+        .and(routes)
+        .and_then(move |coding: Option<ContentCoding>, reply: R| {
+            let response = reply.into_response();
+            async move {
+                let response = match coding {
+                    Some(coding) => compress_response(response, coding, min_size).await,
+                    None => response,
+                };
+                Ok::<_, Infallible>(response)
+            }
+        })
+}
+
+/// Picks the accepted coding with the highest q-value, preferring
+/// brotli on ties; identity and unknown codings are ignored
+fn negotiate_coding(accept_encoding: &str) -> Option<ContentCoding> {
+    let mut best: Option<(ContentCoding, f32)> = None;
+
+    for entry in accept_encoding.split(',') {
+        let mut params = entry.split(';');
+        let coding = match params
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "br" => ContentCoding::Brotli,
+            "gzip" | "x-gzip" => ContentCoding::Gzip,
+            _ => continue,
+        };
+        let quality = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if quality <= 0.0 {
+            continue;
+        }
+
+        let better = match best {
+            None => true,
+            Some((current, q)) => {
+                quality > q
+                    || (quality == q
+                        && current == ContentCoding::Gzip
+                        && coding == ContentCoding::Brotli)
+            }
+        };
+        if better {
+            best = Some((coding, quality));
+        }
+    }
+
+    best.map(|(coding, _)| coding)
+}
+
+/// Streams (SSE), upgrades and bodiless or already-encoded responses
+/// are passed through untouched
+fn is_compressible(response: &Response) -> bool {
+    let status = response.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        return false;
+    }
+
+    let headers = response.headers();
+    if headers.contains_key(CONTENT_ENCODING) {
+        return false;
+    }
+
+    match headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()) {
+        Some(content_type) => !content_type.starts_with("text/event-stream"),
+        None => false,
+    }
+}
+
+async fn compress_response(response: Response, coding: ContentCoding, min_size: usize) -> Response {
+    if !is_compressible(&response) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match warp::hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    if bytes.len() < min_size {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    match coding.encode(&bytes) {
+        Ok(encoded) => {
+            parts
+                .headers
+                .insert(CONTENT_ENCODING, HeaderValue::from_static(coding.as_str()));
+            parts.headers.remove(CONTENT_LENGTH);
+            parts
+                .headers
+                .append(VARY, HeaderValue::from_static("accept-encoding"));
+            Response::from_parts(parts, Body::from(encoded))
+        }
+        Err(_) => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
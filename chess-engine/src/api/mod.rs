@@ -1,3 +1,11 @@
+pub mod admin;
+pub mod errors;
 pub mod handlers;
+pub mod invitations;
+pub mod middleware;
+pub mod openapi;
+pub mod sse;
+pub mod store;
+pub mod ws;
 
-pub use handlers::*;
\ No newline at end of file
+pub use handlers::*;
@@ -0,0 +1,223 @@
+use crate::api::admin::{self, UpdateUserRequest, UserListResponse};
+use crate::api::handlers::{self, *};
+use crate::api::invitations::{self, *};
+use crate::api::{sse, ws};
+use crate::auth::{handlers as auth_handlers, models::*};
+use crate::chess::{
+    CastlingRights, Clock, Color, GameMode, GameState, GameStatus, Move, MoveAnnotation, PieceType,
+    Square, TimeControl, Variant,
+};
+use crate::db::ratings::LeaderboardEntry;
+use crate::db::stats::{ColorStats, PlayerStats, PublicProfile};
+use crate::db::users::UserRecord;
+use crate::metrics;
+use std::sync::Arc;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::Config;
+use warp::http::{StatusCode, Uri};
+use warp::path::{FullPath, Tail};
+use warp::{Filter, Reply};
+
+/// Where the generated spec is served; the Swagger UI loads it from here
+pub const OPENAPI_JSON_PATH: &str = "/api-docs/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Chess Engine API"),
+    paths(
+        auth_handlers::signup_handler,
+        auth_handlers::verify_email_handler,
+        auth_handlers::login_handler,
+        auth_handlers::totp_verify_handler,
+        auth_handlers::totp_setup_handler,
+        auth_handlers::totp_confirm_handler,
+        auth_handlers::refresh_handler,
+        auth_handlers::logout_handler,
+        auth_handlers::forgot_password_handler,
+        auth_handlers::reset_password_handler,
+        auth_handlers::me_handler,
+        auth_handlers::change_password_handler,
+        auth_handlers::deactivate_account_handler,
+        handlers::get_leaderboard,
+        handlers::get_player_stats,
+        handlers::get_public_profile,
+        handlers::create_new_game,
+        handlers::create_game_from_fen,
+        handlers::import_pgn,
+        handlers::list_games,
+        handlers::get_game_state,
+        handlers::delete_game,
+        handlers::make_move,
+        handlers::validate_move,
+        handlers::get_legal_moves,
+        handlers::get_square_moves,
+        handlers::get_game_fen,
+        handlers::get_game_history,
+        handlers::get_game_stats,
+        handlers::get_game_pgn,
+        handlers::get_game_diagram,
+        ws::game_ws,
+        sse::game_events,
+        handlers::make_engine_move,
+        handlers::analyze_game,
+        handlers::annotate_game,
+        handlers::resign_game,
+        handlers::offer_draw,
+        handlers::accept_draw,
+        handlers::claim_draw,
+        handlers::undo_move,
+        invitations::create_invitation,
+        invitations::list_invitations,
+        invitations::accept_invitation,
+        invitations::decline_invitation,
+        handlers::health_check,
+        admin::list_users,
+        admin::update_user,
+        admin::delete_user,
+        metrics::metrics_handler,
+    ),
+    components(schemas(
+        SignupRequest,
+        LoginRequest,
+        RefreshRequest,
+        ChangePasswordRequest,
+        DeactivateAccountRequest,
+        ForgotPasswordRequest,
+        ResetPasswordRequest,
+        AuthResponse,
+        TwoFactorRequiredResponse,
+        TwoFactorVerifyRequest,
+        TotpCodeRequest,
+        TotpSetupResponse,
+        RefreshResponse,
+        UserResponse,
+        crate::auth::models::ErrorResponse,
+        LeaderboardResponse,
+        LeaderboardEntry,
+        PlayerStats,
+        ColorStats,
+        PublicProfile,
+        CreateGameRequest,
+        ColorPreference,
+        FenImportRequest,
+        PgnImportRequest,
+        PgnImportResponse,
+        GameResponse,
+        GameListResponse,
+        GameSummary,
+        MoveRequest,
+        ValidateMoveResponse,
+        MovesResponse,
+        SquareMovesResponse,
+        FenResponse,
+        HistoryResponse,
+        MoveRecord,
+        GameTimeStats,
+        ColorTimes,
+        LongestMove,
+        EngineMoveRequest,
+        EngineMoveResponse,
+        AnalyzeRequest,
+        AnalysisResult,
+        CandidateMove,
+        AnnotateRequest,
+        AnnotateResponse,
+        CreateInvitationRequest,
+        InvitationSettings,
+        InvitationResponse,
+        InvitationListResponse,
+        UserListResponse,
+        UserRecord,
+        UpdateUserRequest,
+        UserRole,
+        HealthResponse,
+        handlers::ErrorResponse,
+        GameState,
+        GameStatus,
+        GameMode,
+        Variant,
+        Color,
+        PieceType,
+        Square,
+        Move,
+        MoveAnnotation,
+        CastlingRights,
+        TimeControl,
+        Clock,
+    )),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Accounts and tokens"),
+        (name = "users", description = "Player profiles and ratings"),
+        (name = "games", description = "Playing, reviewing and analyzing games"),
+        (name = "invitations", description = "Challenging other players"),
+        (name = "admin", description = "Moderation"),
+        (name = "health", description = "Service status"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the `bearer_auth` scheme that authenticated paths refer to
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// GET /api-docs/openapi.json - The OpenAPI spec
+pub fn openapi_json() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path("api-docs")
+        .and(warp::path("openapi.json"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .map(|| warp::reply::json(&ApiDoc::openapi()))
+}
+
+/// GET /swagger-ui - Interactive explorer for the spec
+pub fn swagger_ui(
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    let config = Arc::new(Config::from(OPENAPI_JSON_PATH));
+    warp::path("swagger-ui")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::path::tail())
+        .and(warp::any().map(move || config.clone()))
+        .and_then(serve_swagger_ui)
+}
+
+async fn serve_swagger_ui(
+    full_path: FullPath,
+    tail: Tail,
+    config: Arc<Config<'static>>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    // The UI's assets are loaded relative to the directory
+    if full_path.as_str() == "/swagger-ui" {
+        return Ok(warp::redirect::found(Uri::from_static("/swagger-ui/")).into_response());
+    }
+
+    match utoipa_swagger_ui::serve(tail.as_str(), config) {
+        Ok(Some(file)) => Ok(warp::reply::with_header(
+            file.bytes.into_owned(),
+            "Content-Type",
+            file.content_type,
+        )
+        .into_response()),
+        Ok(None) => Err(warp::reject::not_found()),
+        Err(e) => {
+            eprintln!("Failed to serve Swagger UI: {}", e);
+            Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::api::handlers::{ensure_cached, ErrorResponse, GameStore};
+use crate::api::store::GameBackend;
+use crate::chess::GameState;
+use crate::error_codes;
+use futures_util::{future, stream, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Mutex;
+use warp::sse::Event;
+use warp::Reply;
+
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A game state after a change. `id` is the game's `event_id`, which
+/// clients echo back in `Last-Event-ID` when reconnecting.
+#[derive(Clone)]
+pub struct GameEvent {
+    pub id: u64,
+    pub data: String,
+}
+
+/// Broadcast channels for Server-Sent Events subscribers, keyed by game ID
+pub type GameEvents = Arc<Mutex<HashMap<String, broadcast::Sender<GameEvent>>>>;
+
+pub async fn publish_game_event(events: &GameEvents, game_id: &str, game_state: &GameState) {
+    let mut events = events.lock().await;
+
+    if let Some(sender) = events.get(game_id) {
+        if sender.receiver_count() == 0 {
+            events.remove(game_id);
+            return;
+        }
+
+        match serde_json::to_string(game_state) {
+            Ok(data) => {
+                let _ = sender.send(GameEvent {
+                    id: game_state.event_id,
+                    data,
+                });
+            }
+            Err(e) => eprintln!("Failed to serialize game {}: {}", game_id, e),
+        }
+    }
+}
+
+/// States after each move published since `last_event_id`, rebuilt from
+/// the history, ending with the current state
+fn missed_events(game_state: &GameState, last_event_id: u64) -> Vec<GameEvent> {
+    if last_event_id >= game_state.event_id {
+        return Vec::new();
+    }
+
+    let mut missed = Vec::new();
+    if let Ok(mut replay) = game_state.initial_position() {
+        let tagged = game_state.history.iter().zip(&game_state.move_event_ids);
+        for (ply, (chess_move, &id)) in tagged.enumerate() {
+            if replay.make_move(chess_move.clone()).is_err() {
+                break;
+            }
+
+            // Moves published together only need the last of their states,
+            // and the latest event is sent as the live state below
+            let next_id = game_state.move_event_ids.get(ply + 1);
+            if id > last_event_id && id < game_state.event_id && next_id != Some(&id) {
+                if let Ok(data) = serde_json::to_string(&replay) {
+                    missed.push(GameEvent { id, data });
+                }
+            }
+        }
+    }
+
+    // The live state carries clocks, offers and ownership the replay lacks
+    if let Ok(data) = serde_json::to_string(game_state) {
+        missed.push(GameEvent {
+            id: game_state.event_id,
+            data,
+        });
+    }
+
+    missed
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/events",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID"), ("Last-Event-ID" = Option<u64>, Header, description = "Resume after this event")),
+    responses(
+        (status = 200, description = "Stream of game states", content_type = "text/event-stream"),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn game_events(
+    game_id: String,
+    last_event_id: Option<u64>,
+    games: GameStore,
+    store: GameBackend,
+    events: GameEvents,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+
+    if !games.read().await.contains_key(&game_id) {
+        let error = ErrorResponse {
+            error: "Game not found".to_string(),
+            code: error_codes::GAME_NOT_FOUND,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error),
+            warp::http::StatusCode::NOT_FOUND,
+        )
+        .into_response());
+    }
+
+    // Subscribe before reading the state so no move slips in between
+    let receiver = events
+        .lock()
+        .await
+        .entry(game_id.clone())
+        .or_insert_with(|| broadcast::channel(EVENT_CHANNEL_CAPACITY).0)
+        .subscribe();
+
+    let missed = match (last_event_id, games.read().await.get(&game_id)) {
+        (Some(last_event_id), Some(game_state)) => missed_events(game_state, last_event_id),
+        _ => Vec::new(),
+    };
+
+    let last_replayed = missed.last().map(|event| event.id);
+
+    let live = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .filter(move |event| future::ready(last_replayed.is_none_or(|id| event.id > id)));
+
+    let stream = stream::iter(missed).chain(live).map(|event| {
+        Ok::<_, Infallible>(
+            Event::default()
+                .event("move")
+                .id(event.id.to_string())
+                .data(event.data),
+        )
+    });
+
+    Ok(warp::sse::reply(
+        warp::sse::keep_alive()
+            .interval(KEEPALIVE_INTERVAL)
+            .text(" keepalive")
+            .stream(stream),
+    )
+    .into_response())
+}
@@ -0,0 +1,133 @@
+use crate::chess::GameState;
+use crate::db;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Where games live between requests. Handlers keep recently used games in
+/// the `GameStore` cache and read and write through to one of these.
+#[async_trait]
+pub trait GameStoreBackend: Send + Sync {
+    async fn load_game(&self, id: &str) -> anyhow::Result<Option<GameState>>;
+
+    /// Inserts the game, or replaces its state if it already exists
+    async fn save_game(&self, id: &str, state: &GameState) -> anyhow::Result<()>;
+
+    /// Returns whether a game was deleted
+    async fn delete_game(&self, id: &str) -> anyhow::Result<bool>;
+
+    /// Games that are still being played
+    async fn list_active_games(&self) -> anyhow::Result<Vec<(String, GameState)>>;
+
+    /// IDs of unfinished games that have not been saved since `cutoff`,
+    /// each with the `event_id` it was last saved at
+    async fn list_inactive_games(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<(String, u64)>>;
+}
+
+pub type GameBackend = Arc<dyn GameStoreBackend>;
+
+struct StoredGame {
+    state: GameState,
+    updated_at: DateTime<Utc>,
+}
+
+/// Keeps games in process memory only; they are lost on restart
+#[derive(Default)]
+pub struct InMemoryGameStore {
+    games: RwLock<HashMap<String, StoredGame>>,
+}
+
+impl InMemoryGameStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl GameStoreBackend for InMemoryGameStore {
+    async fn load_game(&self, id: &str) -> anyhow::Result<Option<GameState>> {
+        Ok(self
+            .games
+            .read()
+            .await
+            .get(id)
+            .map(|stored| stored.state.clone()))
+    }
+
+    async fn save_game(&self, id: &str, state: &GameState) -> anyhow::Result<()> {
+        let stored = StoredGame {
+            state: state.clone(),
+            updated_at: Utc::now(),
+        };
+        self.games.write().await.insert(id.to_string(), stored);
+        Ok(())
+    }
+
+    async fn delete_game(&self, id: &str) -> anyhow::Result<bool> {
+        Ok(self.games.write().await.remove(id).is_some())
+    }
+
+    async fn list_active_games(&self) -> anyhow::Result<Vec<(String, GameState)>> {
+        let games = self.games.read().await;
+        Ok(games
+            .iter()
+            .filter(|(_, stored)| !stored.state.is_game_over())
+            .map(|(id, stored)| (id.clone(), stored.state.clone()))
+            .collect())
+    }
+
+    async fn list_inactive_games(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<(String, u64)>> {
+        let games = self.games.read().await;
+        Ok(games
+            .iter()
+            .filter(|(_, stored)| !stored.state.is_game_over() && stored.updated_at < cutoff)
+            .map(|(id, stored)| (id.clone(), stored.state.event_id))
+            .collect())
+    }
+}
+
+/// Persists games in the `games` table
+pub struct DbGameStore {
+    pool: Pool,
+}
+
+impl DbGameStore {
+    pub fn new(pool: Pool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl GameStoreBackend for DbGameStore {
+    async fn load_game(&self, id: &str) -> anyhow::Result<Option<GameState>> {
+        db::games::load_game(&self.pool, id).await
+    }
+
+    async fn save_game(&self, id: &str, state: &GameState) -> anyhow::Result<()> {
+        db::games::save_game(&self.pool, id, state).await
+    }
+
+    async fn delete_game(&self, id: &str) -> anyhow::Result<bool> {
+        db::games::delete_game(&self.pool, id).await
+    }
+
+    async fn list_active_games(&self) -> anyhow::Result<Vec<(String, GameState)>> {
+        db::games::list_active_games(&self.pool).await
+    }
+
+    async fn list_inactive_games(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<(String, u64)>> {
+        db::games::list_inactive_games(&self.pool, cutoff).await
+    }
+}
@@ -0,0 +1,171 @@
+use crate::api::handlers::{ensure_cached, ErrorResponse, GameStore};
+use crate::api::store::GameBackend;
+use crate::chess::{notation, Board, GameState, Piece};
+use crate::error_codes;
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex};
+use warp::ws::{Message, WebSocket, Ws};
+use warp::Reply;
+
+/// WebSocket senders for every client watching a game, keyed by game ID
+pub type GameSubscriptions = Arc<Mutex<HashMap<String, Vec<mpsc::UnboundedSender<Message>>>>>;
+
+/// A square whose contents changed with the last move
+#[derive(Serialize)]
+struct SquareChange {
+    square: String,
+    before: Option<Piece>,
+    after: Option<Piece>,
+}
+
+/// Pushes the game state to every subscriber of the game, dropping
+/// senders whose connection has gone away
+pub async fn broadcast_game_state(
+    subscriptions: &GameSubscriptions,
+    game_id: &str,
+    game_state: &GameState,
+) {
+    broadcast(subscriptions, game_id, || serde_json::to_string(game_state)).await;
+}
+
+/// Pushes the game state after a move, with the squares it changed
+/// relative to `previous` under `changes` so clients can patch their
+/// board instead of redrawing it
+pub async fn broadcast_move(
+    subscriptions: &GameSubscriptions,
+    game_id: &str,
+    game_state: &GameState,
+    previous: &Board,
+) {
+    let changes: Vec<SquareChange> = previous
+        .diff(&game_state.board)
+        .into_iter()
+        .map(|(square, before, after)| SquareChange {
+            square: notation::square_to_algebraic(square),
+            before,
+            after,
+        })
+        .collect();
+
+    broadcast(subscriptions, game_id, || {
+        let mut payload = serde_json::to_value(game_state)?;
+        payload["changes"] = serde_json::to_value(&changes)?;
+        serde_json::to_string(&payload)
+    })
+    .await;
+}
+
+/// Sends a payload to every subscriber of the game, only serializing it
+/// if anyone is listening
+async fn broadcast(
+    subscriptions: &GameSubscriptions,
+    game_id: &str,
+    payload: impl FnOnce() -> serde_json::Result<String>,
+) {
+    let mut subscriptions = subscriptions.lock().await;
+
+    if let Some(senders) = subscriptions.get_mut(game_id) {
+        let payload = match payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                eprintln!("Failed to serialize game {}: {}", game_id, e);
+                return;
+            }
+        };
+
+        senders.retain(|sender| sender.send(Message::text(payload.clone())).is_ok());
+        if senders.is_empty() {
+            subscriptions.remove(game_id);
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/ws",
+    tag = "games",
+    params(("id" = String, Path, description = "Game ID")),
+    responses(
+        (status = 101, description = "WebSocket pushing the game state after every move, with the squares it changed under `changes`"),
+        (status = 404, description = "Game not found", body = ErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(game_id = %game_id))]
+pub async fn game_ws(
+    game_id: String,
+    ws: Ws,
+    games: GameStore,
+    store: GameBackend,
+    subscriptions: GameSubscriptions,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    ensure_cached(&game_id, &games, store.as_ref()).await;
+
+    let initial_state = {
+        let games_map = games.read().await;
+        games_map
+            .get(&game_id)
+            .and_then(|game_state| serde_json::to_string(game_state).ok())
+    };
+
+    let initial_state = match initial_state {
+        Some(initial_state) => initial_state,
+        None => {
+            let error = ErrorResponse {
+                error: "Game not found".to_string(),
+                code: error_codes::GAME_NOT_FOUND,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error),
+                warp::http::StatusCode::NOT_FOUND,
+            )
+            .into_response());
+        }
+    };
+
+    Ok(ws
+        .on_upgrade(move |socket| handle_socket(socket, game_id, initial_state, subscriptions))
+        .into_response())
+}
+
+async fn handle_socket(
+    socket: WebSocket,
+    game_id: String,
+    initial_state: String,
+    subscriptions: GameSubscriptions,
+) {
+    let (mut ws_tx, mut ws_rx) = socket.split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+
+    // Send the current state first so the client doesn't have to fetch it
+    let _ = tx.send(Message::text(initial_state));
+    subscriptions
+        .lock()
+        .await
+        .entry(game_id)
+        .or_default()
+        .push(tx);
+
+    let forward = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if ws_tx.send(message).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Updates only flow to the client; incoming messages are ignored
+    // until the connection closes
+    while let Some(result) = ws_rx.next().await {
+        match result {
+            Ok(message) if message.is_close() => break,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    // Dropping the receiver makes the next broadcast prune this sender
+    forward.abort();
+}
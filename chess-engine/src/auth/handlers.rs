@@ -1,12 +1,179 @@
-use crate::auth::{jwt, models::*, validation};
-use bcrypt::{hash, verify, DEFAULT_COST};
-use deadpool_postgres::Pool;
+use crate::auth::{jwt, models::*, password, rate_limit::RateLimiter, totp, validation};
+use crate::db;
+use crate::error_codes;
+use crate::mail::Mailer;
+use crate::metrics;
+use chrono::{Duration, Utc};
+use deadpool_postgres::{Client, Pool};
+use std::sync::Arc;
+use uuid::Uuid;
 use validator::Validate;
 use warp::Reply;
 
+/// How long the link in a verification email works
+const EMAIL_VERIFICATION_TTL_HOURS: i64 = 24;
+
+/// Emails the user a link that verifies their address
+async fn send_verification_email(
+    db_pool: &Pool,
+    mailer: &Mailer,
+    user_id: i32,
+    email: &str,
+) -> anyhow::Result<()> {
+    let token = jwt::generate_link_token();
+    let expires_at = Utc::now() + Duration::hours(EMAIL_VERIFICATION_TTL_HOURS);
+    db::verification::create_email_verification_token(
+        db_pool,
+        user_id,
+        &jwt::hash_token(&token),
+        expires_at,
+    )
+    .await?;
+
+    let link = mailer.url(&format!("/api/v1/auth/verify-email?token={}", token));
+    let body = format!(
+        "Welcome! Confirm your email address within {} hours by opening this link:\n\n{}\n",
+        EMAIL_VERIFICATION_TTL_HOURS, link
+    );
+    mailer.send(email, "Verify your email address", body).await
+}
+
+/// How long the link in a password reset email works
+const PASSWORD_RESET_TTL_HOURS: i64 = 1;
+
+/// Emails the account registered with `email`, if there is one, a link for
+/// choosing a new password
+async fn send_password_reset_email(
+    db_pool: &Pool,
+    mailer: &Mailer,
+    email: &str,
+) -> anyhow::Result<()> {
+    let Some(user_id) = db::users::find_user_id_by_email(db_pool, email).await? else {
+        return Ok(());
+    };
+
+    let token = jwt::generate_link_token();
+    let expires_at = Utc::now() + Duration::hours(PASSWORD_RESET_TTL_HOURS);
+    db::verification::create_password_reset_token(
+        db_pool,
+        user_id,
+        &jwt::hash_reset_token(&token),
+        expires_at,
+    )
+    .await?;
+
+    // A page of the client's, which posts the token to /api/v1/auth/reset-password
+    let link = mailer.url(&format!("/reset-password?token={}", token));
+    let body = format!(
+        "Someone asked to reset the password for your account. Choose a new one within {} hour by opening this link:\n\n{}\n\nIf this wasn't you, ignore this email.\n",
+        PASSWORD_RESET_TTL_HOURS, link
+    );
+    mailer.send(email, "Reset your password", body).await
+}
+
+/// Creates a refresh token for the user and stores its hash
+async fn issue_refresh_token(
+    client: &Client,
+    jwt_config: &jwt::JwtConfig,
+    user_id: i32,
+) -> anyhow::Result<String> {
+    let token_id = Uuid::new_v4();
+    let token = jwt::create_refresh_jwt(jwt_config, user_id, token_id)?;
+    let expires_at = Utc::now() + jwt_config.refresh_token_ttl;
+
+    client
+        .execute(
+            "INSERT INTO refresh_tokens (id, user_id, token_hash, expires_at) VALUES ($1, $2, $3, $4)",
+            &[&token_id, &user_id, &jwt::hash_token(&token), &expires_at],
+        )
+        .await?;
+
+    Ok(token)
+}
+
+/// Records the login and issues the user a new access and refresh token
+async fn start_session(
+    client: &Client,
+    jwt_config: &jwt::JwtConfig,
+    user_id: i32,
+    username: String,
+    email: String,
+    role: UserRole,
+    created_at: chrono::DateTime<Utc>,
+) -> anyhow::Result<AuthResponse> {
+    let _ = client
+        .execute(
+            "UPDATE users SET last_login = NOW() WHERE id = $1",
+            &[&user_id],
+        )
+        .await;
+
+    let token = jwt::create_access_jwt(
+        jwt_config,
+        user_id,
+        username.clone(),
+        email.clone(),
+        role,
+    )?;
+    let refresh_token = issue_refresh_token(client, jwt_config, user_id).await?;
+
+    Ok(AuthResponse {
+        token,
+        refresh_token,
+        user: UserResponse {
+            id: user_id,
+            username,
+            email,
+            created_at,
+            last_login: Some(Utc::now()),
+        },
+    })
+}
+
+/// Asks a user with 2FA enabled for their code, handing them a short-lived
+/// token to send back with it
+fn two_factor_challenge(
+    jwt_config: &jwt::JwtConfig,
+    user_id: i32,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    match jwt::create_two_factor_jwt(jwt_config, user_id) {
+        Ok(partial_token) => {
+            let response = TwoFactorRequiredResponse {
+                requires_2fa: true,
+                partial_token,
+            };
+            warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK)
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to generate token".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/signup",
+    tag = "auth",
+    request_body = SignupRequest,
+    responses(
+        (status = 201, description = "Account created; log in once the email address is verified", body = UserResponse),
+        (status = 400, description = "Validation failed", body = AuthErrorResponse),
+        (status = 409, description = "Username or email taken", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all, fields(username = %signup_req.username))]
 pub async fn signup_handler(
     signup_req: SignupRequest,
     db_pool: Pool,
+    mailer: Arc<Mailer>,
 ) -> Result<impl Reply, warp::Rejection> {
     // Validate input
     if let Err(validation_errors) = signup_req.validate() {
@@ -22,6 +189,7 @@ pub async fn signup_handler(
 
         let error_response = ErrorResponse {
             error: "Validation failed".to_string(),
+            code: error_codes::VALIDATION_FAILED,
             details: Some(errors),
         };
 
@@ -37,6 +205,7 @@ pub async fn signup_handler(
         Err(_) => {
             let error_response = ErrorResponse {
                 error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
                 details: None,
             };
             return Ok(warp::reply::with_status(
@@ -58,6 +227,7 @@ pub async fn signup_handler(
         if !rows.is_empty() {
             let error_response = ErrorResponse {
                 error: "Username already taken".to_string(),
+                code: error_codes::USERNAME_TAKEN,
                 details: None,
             };
             return Ok(warp::reply::with_status(
@@ -76,6 +246,7 @@ pub async fn signup_handler(
         if !rows.is_empty() {
             let error_response = ErrorResponse {
                 error: "Email already registered".to_string(),
+                code: error_codes::EMAIL_TAKEN,
                 details: None,
             };
             return Ok(warp::reply::with_status(
@@ -86,11 +257,12 @@ pub async fn signup_handler(
     }
 
     // Hash password
-    let password_hash = match hash(&signup_req.password, DEFAULT_COST) {
+    let password_hash = match password::hash_password(&signup_req.password) {
         Ok(hash) => hash,
         Err(_) => {
             let error_response = ErrorResponse {
                 error: "Failed to hash password".to_string(),
+                code: error_codes::INTERNAL_ERROR,
                 details: None,
             };
             return Ok(warp::reply::with_status(
@@ -115,31 +287,27 @@ pub async fn signup_handler(
             let email: String = row.get(2);
             let created_at: chrono::NaiveDateTime = row.get(3);
             let created_at = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(created_at, chrono::Utc);
-            // Generate JWT token
-            let token = match jwt::create_jwt(user_id, username.clone(), email.clone()) {
-                Ok(token) => token,
-                Err(_) => {
-                    let error_response = ErrorResponse {
-                        error: "Failed to generate token".to_string(),
-                        details: None,
-                    };
-                    return Ok(warp::reply::with_status(
-                        warp::reply::json(&error_response),
-                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    ));
-                }
+            // No tokens until the email address is verified; the user logs in then
+            let response = UserResponse {
+                id: user_id,
+                username,
+                email,
+                created_at,
+                last_login: None,
             };
 
-            let response = AuthResponse {
-                token,
-                user: UserResponse {
-                    id: user_id,
-                    username,
-                    email,
-                    created_at,
-                },
-            };
+            // Sent in the background so a slow mail server doesn't hold up signup
+            let verification_email = response.email.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    send_verification_email(&db_pool, &mailer, user_id, &verification_email).await
+                {
+                    eprintln!("Failed to send verification email to user {}: {}", user_id, e);
+                }
+            });
 
+            metrics::SIGNUPS.inc();
+            tracing::info!(user_id, "user signed up");
             Ok(warp::reply::with_status(
                 warp::reply::json(&response),
                 warp::http::StatusCode::CREATED,
@@ -148,6 +316,7 @@ pub async fn signup_handler(
         Err(_) => {
             let error_response = ErrorResponse {
                 error: "Failed to create user".to_string(),
+                code: error_codes::INTERNAL_ERROR,
                 details: None,
             };
             Ok(warp::reply::with_status(
@@ -158,14 +327,68 @@ pub async fn signup_handler(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Logged in, or a 2FA code is needed", body = AuthResponse),
+        (status = 401, description = "Invalid credentials", body = AuthErrorResponse),
+        (status = 403, description = "Email address not verified or account deactivated", body = AuthErrorResponse),
+        (status = 429, description = "Too many failed attempts", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
 pub async fn login_handler(
+    login_req: LoginRequest,
+    client_ip: Option<String>,
+    db_pool: Pool,
+    jwt_config: Arc<jwt::JwtConfig>,
+    rate_limiter: Arc<RateLimiter>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let mut keys = vec![format!("user:{}", login_req.username_or_email.to_lowercase())];
+    if let Some(ip) = client_ip {
+        keys.push(format!("ip:{}", ip));
+    }
+
+    if let Some(retry_after) = rate_limiter.retry_after(&keys) {
+        metrics::record_login_attempt(false);
+        let error_response = ErrorResponse {
+            error: "Too many failed login attempts, try again later".to_string(),
+            code: error_codes::RATE_LIMITED,
+            details: None,
+        };
+        let reply = warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::TOO_MANY_REQUESTS,
+        );
+        let retry_after_secs = retry_after.as_secs().max(1).to_string();
+        return Ok(warp::reply::with_header(reply, "Retry-After", retry_after_secs).into_response());
+    }
+
+    let response = login(login_req, db_pool, jwt_config).await?.into_response();
+
+    match response.status() {
+        warp::http::StatusCode::OK => rate_limiter.reset(&keys),
+        warp::http::StatusCode::UNAUTHORIZED => rate_limiter.record_failure(&keys),
+        _ => {}
+    }
+    metrics::record_login_attempt(response.status() == warp::http::StatusCode::OK);
+
+    Ok(response)
+}
+
+async fn login(
     login_req: LoginRequest,
     db_pool: Pool,
+    jwt_config: Arc<jwt::JwtConfig>,
 ) -> Result<impl Reply, warp::Rejection> {
     // Validate input
     if let Err(_) = login_req.validate() {
         let error_response = ErrorResponse {
             error: "Invalid input".to_string(),
+            code: error_codes::INVALID_REQUEST,
             details: None,
         };
         return Ok(warp::reply::with_status(
@@ -180,6 +403,7 @@ pub async fn login_handler(
         Err(_) => {
             let error_response = ErrorResponse {
                 error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
                 details: None,
             };
             return Ok(warp::reply::with_status(
@@ -192,7 +416,7 @@ pub async fn login_handler(
     // Find user by username or email
     let user_result = client
         .query_one(
-            "SELECT id, username, email, password_hash, created_at FROM users WHERE username = $1 OR email = $1",
+            "SELECT id, username, email, password_hash, created_at, email_verified, is_active, totp_enabled, role FROM users WHERE username = $1 OR email = $1",
             &[&login_req.username_or_email],
         )
         .await;
@@ -205,51 +429,77 @@ pub async fn login_handler(
             let password_hash: String = row.get(3);
             let created_at: chrono::NaiveDateTime = row.get(4);
             let created_at = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(created_at, chrono::Utc);
+            let email_verified: bool = row.get(5);
+            let is_active: bool = row.get(6);
+            let totp_enabled: bool = row.get(7);
+            let role = UserRole::from_db(row.get(8));
 
             // Verify password
-            match verify(&login_req.password, &password_hash) {
-                Ok(is_valid) if is_valid => {
-                    // Update last login
-                    let _ = client
-                        .execute(
-                            "UPDATE users SET last_login = NOW() WHERE id = $1",
-                            &[&user_id],
-                        )
-                        .await;
-
-                    // Generate JWT token
-                    let token = match jwt::create_jwt(user_id, username.clone(), email.clone()) {
-                        Ok(token) => token,
+            match password::verify_password(&login_req.password, &password_hash) {
+                true if !is_active => {
+                    let error_response = ErrorResponse {
+                        error: "Account deactivated".to_string(),
+                        code: error_codes::ACCOUNT_DEACTIVATED,
+                        details: None,
+                    };
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&error_response),
+                        warp::http::StatusCode::FORBIDDEN,
+                    ))
+                }
+                true if !email_verified => {
+                    let error_response = ErrorResponse {
+                        error: "Verify your email address before logging in".to_string(),
+                        code: error_codes::EMAIL_NOT_VERIFIED,
+                        details: None,
+                    };
+                    Ok(warp::reply::with_status(
+                        warp::reply::json(&error_response),
+                        warp::http::StatusCode::FORBIDDEN,
+                    ))
+                }
+                true => {
+                    // Transparently upgrade legacy bcrypt hashes to Argon2id
+                    if password::is_legacy_hash(&password_hash) {
+                        if let Ok(new_hash) = password::hash_password(&login_req.password) {
+                            let _ = client
+                                .execute(
+                                    "UPDATE users SET password_hash = $1 WHERE id = $2",
+                                    &[&new_hash, &user_id],
+                                )
+                                .await;
+                        }
+                    }
+
+                    if totp_enabled {
+                        return Ok(two_factor_challenge(&jwt_config, user_id));
+                    }
+
+                    match start_session(&client, &jwt_config, user_id, username, email, role, created_at).await {
+                        Ok(response) => {
+                            tracing::info!(user_id, "user logged in");
+                            Ok(warp::reply::with_status(
+                                warp::reply::json(&response),
+                                warp::http::StatusCode::OK,
+                            ))
+                        }
                         Err(_) => {
                             let error_response = ErrorResponse {
                                 error: "Failed to generate token".to_string(),
+                                code: error_codes::INTERNAL_ERROR,
                                 details: None,
                             };
-                            return Ok(warp::reply::with_status(
+                            Ok(warp::reply::with_status(
                                 warp::reply::json(&error_response),
                                 warp::http::StatusCode::INTERNAL_SERVER_ERROR,
-                            ));
+                            ))
                         }
-                    };
-
-                    let response = AuthResponse {
-                        token,
-                        user: UserResponse {
-                            id: user_id,
-                            username,
-                            email,
-                            created_at,
-                        },
-                    };
-
-                    Ok(warp::reply::with_status(
-                        warp::reply::json(&response),
-                        warp::http::StatusCode::OK,
-                    ))
+                    }
                 }
-                _ => {
+                false => {
                     let error_response = ErrorResponse {
                         error: "Invalid credentials".to_string(),
+                        code: error_codes::INVALID_CREDENTIALS,
                         details: None,
                     };
                     Ok(warp::reply::with_status(
@@ -262,6 +512,7 @@ pub async fn login_handler(
         Err(_) => {
             let error_response = ErrorResponse {
                 error: "Invalid credentials".to_string(),
+                code: error_codes::INVALID_CREDENTIALS,
                 details: None,
             };
             Ok(warp::reply::with_status(
@@ -270,4 +521,1112 @@ pub async fn login_handler(
             ))
         }
     }
-}
\ No newline at end of file
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New token pair", body = RefreshResponse),
+        (status = 401, description = "Invalid refresh token", body = AuthErrorResponse),
+        (status = 403, description = "Email address not verified or account deactivated", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn refresh_handler(
+    refresh_req: RefreshRequest,
+    db_pool: Pool,
+    jwt_config: Arc<jwt::JwtConfig>,
+) -> Result<impl Reply, warp::Rejection> {
+    let invalid_token = || {
+        let error_response = ErrorResponse {
+            error: "Invalid refresh token".to_string(),
+            code: error_codes::INVALID_TOKEN,
+            details: None,
+        };
+        warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::UNAUTHORIZED,
+        )
+    };
+
+    // Verify signature and expiry
+    let claims = match jwt::verify_refresh_jwt(&jwt_config, &refresh_req.refresh_token) {
+        Ok(claims) => claims,
+        Err(_) => return Ok(invalid_token()),
+    };
+    let token_id = match Uuid::parse_str(&claims.jti) {
+        Ok(token_id) => token_id,
+        Err(_) => return Ok(invalid_token()),
+    };
+
+    // Get database connection
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    // Look up the stored token
+    let token_row = client
+        .query_opt(
+            "SELECT r.token_hash, r.revoked, r.expires_at, u.username, u.email, u.role, \
+             u.email_verified, u.is_active \
+             FROM refresh_tokens r JOIN users u ON u.id = r.user_id \
+             WHERE r.id = $1 AND r.user_id = $2",
+            &[&token_id, &claims.sub],
+        )
+        .await;
+
+    let row = match token_row {
+        Ok(Some(row)) => row,
+        _ => return Ok(invalid_token()),
+    };
+
+    let token_hash: String = row.get(0);
+    let revoked: bool = row.get(1);
+    let expires_at: chrono::DateTime<Utc> = row.get(2);
+    let username: String = row.get(3);
+    let email: String = row.get(4);
+    let role = UserRole::from_db(row.get(5));
+    let email_verified: bool = row.get(6);
+    let is_active: bool = row.get(7);
+
+    if token_hash != jwt::hash_token(&refresh_req.refresh_token) {
+        return Ok(invalid_token());
+    }
+
+    // The same gates as login, so a surviving refresh token can't get around them
+    let forbidden = if !is_active {
+        Some(("Account deactivated", error_codes::ACCOUNT_DEACTIVATED))
+    } else if !email_verified {
+        Some(("Verify your email address before logging in", error_codes::EMAIL_NOT_VERIFIED))
+    } else {
+        None
+    };
+    if let Some((error, code)) = forbidden {
+        let error_response = ErrorResponse {
+            error: error.to_string(),
+            code,
+            details: None,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+
+    if revoked {
+        // A revoked token being replayed suggests it was stolen, so end every session
+        let _ = client
+            .execute(
+                "UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = $1",
+                &[&claims.sub],
+            )
+            .await;
+        return Ok(invalid_token());
+    }
+
+    if expires_at <= Utc::now() {
+        return Ok(invalid_token());
+    }
+
+    // Rotate: revoke the old token before issuing a new one
+    let rotated = client
+        .execute(
+            "UPDATE refresh_tokens SET revoked = TRUE WHERE id = $1 AND revoked = FALSE",
+            &[&token_id],
+        )
+        .await;
+
+    if !matches!(rotated, Ok(1)) {
+        return Ok(invalid_token());
+    }
+
+    let token = match jwt::create_access_jwt(&jwt_config, claims.sub, username, email, role) {
+        Ok(token) => token,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to generate token".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let refresh_token = match issue_refresh_token(&client, &jwt_config, claims.sub).await {
+        Ok(refresh_token) => refresh_token,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to generate token".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    tracing::info!(user_id = claims.sub, "tokens refreshed");
+    let response = RefreshResponse {
+        token,
+        refresh_token,
+    };
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&response),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/verify-email",
+    tag = "auth",
+    params(VerifyEmailQuery),
+    responses(
+        (status = 200, description = "Email verified"),
+        (status = 400, description = "Invalid or expired link", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn verify_email_handler(
+    query: VerifyEmailQuery,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    match db::verification::verify_email(&db_pool, &jwt::hash_token(query.token.trim())).await {
+        Ok(Some(user_id)) => {
+            tracing::info!(user_id, "email verified");
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "message": "Email verified" })),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Ok(None) => {
+            let error_response = ErrorResponse {
+                error: "Invalid or expired verification link".to_string(),
+                code: error_codes::INVALID_TOKEN,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::BAD_REQUEST,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to verify email: {}", e);
+            let error_response = ErrorResponse {
+                error: "Failed to verify email".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/forgot-password",
+    tag = "auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "Reset link sent if the account exists"),
+        (status = 400, description = "Validation failed", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn forgot_password_handler(
+    forgot_req: ForgotPasswordRequest,
+    db_pool: Pool,
+    mailer: Arc<Mailer>,
+) -> Result<impl Reply, warp::Rejection> {
+    if let Err(validation_errors) = forgot_req.validate() {
+        let errors: Vec<String> = validation_errors
+            .field_errors()
+            .iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| {
+                    format!("{}: {}", field, error.message.clone().unwrap_or_default())
+                })
+            })
+            .collect();
+
+        let error_response = ErrorResponse {
+            error: "Validation failed".to_string(),
+            code: error_codes::VALIDATION_FAILED,
+            details: Some(errors),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    // Looked up and sent in the background so neither the answer nor the
+    // response time reveals whether the email is registered
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset_email(&db_pool, &mailer, &forgot_req.email).await {
+            eprintln!("Failed to send password reset email: {}", e);
+        }
+    });
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&serde_json::json!({
+            "message": "If an account uses that email, a reset link has been sent"
+        })),
+        warp::http::StatusCode::OK,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/reset-password",
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset"),
+        (status = 400, description = "Validation failed or invalid link", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn reset_password_handler(
+    reset_req: ResetPasswordRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    if let Err(validation_errors) = reset_req.validate() {
+        let errors: Vec<String> = validation_errors
+            .field_errors()
+            .iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| {
+                    format!("{}: {}", field, error.message.clone().unwrap_or_default())
+                })
+            })
+            .collect();
+
+        let error_response = ErrorResponse {
+            error: "Validation failed".to_string(),
+            code: error_codes::VALIDATION_FAILED,
+            details: Some(errors),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let new_hash = match password::hash_password(&reset_req.new_password) {
+        Ok(hash) => hash,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to hash password".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let token_hash = jwt::hash_reset_token(reset_req.token.trim());
+    match db::verification::reset_password(&db_pool, &token_hash, &new_hash).await {
+        Ok(Some(user_id)) => {
+            tracing::info!(user_id, "password reset");
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "message": "Password reset" })),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Ok(None) => {
+            let error_response = ErrorResponse {
+                error: "Invalid or expired reset link".to_string(),
+                code: error_codes::INVALID_TOKEN,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::BAD_REQUEST,
+            ))
+        }
+        Err(e) => {
+            eprintln!("Failed to reset password: {}", e);
+            let error_response = ErrorResponse {
+                error: "Failed to reset password".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/logout",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Token revoked"),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn logout_handler(
+    claims: jwt::Claims,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let expires_at = chrono::DateTime::<Utc>::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+
+    match db::tokens::revoke_token(&db_pool, claims.jti, claims.sub, expires_at).await {
+        Ok(()) => Ok(warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "message": "Logged out" })),
+            warp::http::StatusCode::OK,
+        )),
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to revoke token".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/auth/me",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Current user's profile", body = UserResponse),
+        (status = 401, description = "User no longer exists", body = AuthErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn me_handler(
+    claims: jwt::Claims,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let user_result = client
+        .query_opt(
+            "SELECT id, username, email, created_at, last_login FROM users WHERE id = $1",
+            &[&claims.sub],
+        )
+        .await;
+
+    match user_result {
+        Ok(Some(row)) => {
+            let created_at: chrono::NaiveDateTime = row.get(3);
+            let last_login: Option<chrono::NaiveDateTime> = row.get(4);
+
+            let response = UserResponse {
+                id: row.get(0),
+                username: row.get(1),
+                email: row.get(2),
+                created_at: chrono::DateTime::<Utc>::from_naive_utc_and_offset(created_at, Utc),
+                last_login: last_login
+                    .map(|ts| chrono::DateTime::<Utc>::from_naive_utc_and_offset(ts, Utc)),
+            };
+
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Ok(None) => {
+            // Token is valid but the account has since been deleted
+            let error_response = ErrorResponse {
+                error: "User no longer exists".to_string(),
+                code: error_codes::USER_NOT_FOUND,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::UNAUTHORIZED,
+            ))
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database error".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/users/me/password",
+    tag = "users",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Validation failed", body = AuthErrorResponse),
+        (status = 401, description = "Current password is wrong", body = AuthErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn change_password_handler(
+    claims: jwt::Claims,
+    change_req: ChangePasswordRequest,
+    db_pool: Pool,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    if let Err(validation_errors) = change_req.validate() {
+        let errors: Vec<String> = validation_errors
+            .field_errors()
+            .iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| {
+                    format!("{}: {}", field, error.message.clone().unwrap_or_default())
+                })
+            })
+            .collect();
+
+        let error_response = ErrorResponse {
+            error: "Validation failed".to_string(),
+            code: error_codes::VALIDATION_FAILED,
+            details: Some(errors),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::BAD_REQUEST,
+        )
+        .into_response());
+    }
+
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+
+    let stored_hash: String = match client
+        .query_opt("SELECT password_hash FROM users WHERE id = $1", &[&claims.sub])
+        .await
+    {
+        Ok(Some(row)) => row.get(0),
+        Ok(None) => {
+            let error_response = ErrorResponse {
+                error: "User no longer exists".to_string(),
+                code: error_codes::USER_NOT_FOUND,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::UNAUTHORIZED,
+            )
+            .into_response());
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database error".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+
+    if !password::verify_password(&change_req.current_password, &stored_hash) {
+        let error_response = ErrorResponse {
+            error: "Current password is incorrect".to_string(),
+            code: error_codes::INVALID_CREDENTIALS,
+            details: None,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::UNAUTHORIZED,
+        )
+        .into_response());
+    }
+
+    let new_hash = match password::hash_password(&change_req.new_password) {
+        Ok(hash) => hash,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to hash password".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+
+    let update_result = client
+        .execute(
+            "UPDATE users SET password_hash = $1 WHERE id = $2",
+            &[&new_hash, &claims.sub],
+        )
+        .await;
+
+    if update_result.is_err() {
+        let error_response = ErrorResponse {
+            error: "Failed to update password".to_string(),
+            code: error_codes::INTERNAL_ERROR,
+            details: None,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response());
+    }
+
+    // Force other devices to log in again with the new password
+    let _ = client
+        .execute(
+            "UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = $1",
+            &[&claims.sub],
+        )
+        .await;
+
+    Ok(warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/auth/me",
+    tag = "auth",
+    request_body = DeactivateAccountRequest,
+    responses(
+        (status = 204, description = "Account deactivated"),
+        (status = 400, description = "Validation failed", body = AuthErrorResponse),
+        (status = 401, description = "Password is wrong", body = AuthErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn deactivate_account_handler(
+    claims: jwt::Claims,
+    deactivate_req: DeactivateAccountRequest,
+    db_pool: Pool,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    if let Err(validation_errors) = deactivate_req.validate() {
+        let errors: Vec<String> = validation_errors
+            .field_errors()
+            .iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| {
+                    format!("{}: {}", field, error.message.clone().unwrap_or_default())
+                })
+            })
+            .collect();
+
+        let error_response = ErrorResponse {
+            error: "Validation failed".to_string(),
+            code: error_codes::VALIDATION_FAILED,
+            details: Some(errors),
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::BAD_REQUEST,
+        )
+        .into_response());
+    }
+
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+
+    let stored_hash: String = match client
+        .query_opt(
+            "SELECT password_hash FROM users WHERE id = $1 AND is_active",
+            &[&claims.sub],
+        )
+        .await
+    {
+        Ok(Some(row)) => row.get(0),
+        Ok(None) => {
+            let error_response = ErrorResponse {
+                error: "User no longer exists".to_string(),
+                code: error_codes::USER_NOT_FOUND,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::UNAUTHORIZED,
+            )
+            .into_response());
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database error".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+    drop(client);
+
+    if !password::verify_password(&deactivate_req.password, &stored_hash) {
+        let error_response = ErrorResponse {
+            error: "Password is incorrect".to_string(),
+            code: error_codes::INVALID_CREDENTIALS,
+            details: None,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::UNAUTHORIZED,
+        )
+        .into_response());
+    }
+
+    if let Err(e) = db::users::deactivate_user(&db_pool, claims.sub).await {
+        eprintln!("Failed to deactivate user {}: {}", claims.sub, e);
+        let error_response = ErrorResponse {
+            error: "Failed to deactivate account".to_string(),
+            code: error_codes::INTERNAL_ERROR,
+            details: None,
+        };
+        return Ok(warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response());
+    }
+
+    // End the session this request came from too, rather than letting its
+    // access token run out
+    let expires_at = chrono::DateTime::<Utc>::from_timestamp(claims.exp, 0).unwrap_or_else(Utc::now);
+    let _ = db::tokens::revoke_token(&db_pool, claims.jti, claims.sub, expires_at).await;
+
+    tracing::info!(user_id = claims.sub, "account deactivated");
+    Ok(warp::reply::with_status(warp::reply(), warp::http::StatusCode::NO_CONTENT).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/setup",
+    tag = "auth",
+    responses(
+        (status = 200, description = "New TOTP secret, to be confirmed", body = TotpSetupResponse),
+        (status = 409, description = "2FA is already enabled", body = AuthErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn totp_setup_handler(
+    claims: jwt::Claims,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let secret = totp::generate_secret();
+
+    // Replaces any earlier unconfirmed secret; an enabled one is left alone
+    let updated = client
+        .query_opt(
+            "UPDATE users SET totp_secret = $1 WHERE id = $2 AND NOT totp_enabled RETURNING username",
+            &[&secret, &claims.sub],
+        )
+        .await;
+
+    match updated {
+        Ok(Some(row)) => {
+            let username: String = row.get(0);
+            let response = TotpSetupResponse {
+                otpauth_uri: totp::provisioning_uri(&secret, &username),
+                secret,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&response),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Ok(None) => {
+            let error_response = ErrorResponse {
+                error: "Two-factor authentication is already enabled".to_string(),
+                code: error_codes::TOTP_ALREADY_ENABLED,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::CONFLICT,
+            ))
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database error".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/confirm",
+    tag = "auth",
+    request_body = TotpCodeRequest,
+    responses(
+        (status = 200, description = "2FA enabled"),
+        (status = 400, description = "Wrong code, or 2FA was never set up", body = AuthErrorResponse),
+        (status = 409, description = "2FA is already enabled", body = AuthErrorResponse),
+    ),
+    security(("bearer_auth" = []))
+)]
+#[tracing::instrument(skip_all, fields(user_id = claims.sub))]
+pub async fn totp_confirm_handler(
+    claims: jwt::Claims,
+    code_req: TotpCodeRequest,
+    db_pool: Pool,
+) -> Result<impl Reply, warp::Rejection> {
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let (secret, totp_enabled): (Option<String>, bool) = match client
+        .query_opt(
+            "SELECT totp_secret, totp_enabled FROM users WHERE id = $1",
+            &[&claims.sub],
+        )
+        .await
+    {
+        Ok(Some(row)) => (row.get(0), row.get(1)),
+        Ok(None) => {
+            let error_response = ErrorResponse {
+                error: "User no longer exists".to_string(),
+                code: error_codes::USER_NOT_FOUND,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::UNAUTHORIZED,
+            ));
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database error".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let error_response = match secret {
+        _ if totp_enabled => Some((
+            "Two-factor authentication is already enabled",
+            error_codes::TOTP_ALREADY_ENABLED,
+            warp::http::StatusCode::CONFLICT,
+        )),
+        None => Some((
+            "Set up two-factor authentication first",
+            error_codes::TOTP_NOT_SET_UP,
+            warp::http::StatusCode::BAD_REQUEST,
+        )),
+        Some(secret) if !totp::verify_code(&secret, &code_req.code) => Some((
+            "Invalid code",
+            error_codes::INVALID_TOTP_CODE,
+            warp::http::StatusCode::BAD_REQUEST,
+        )),
+        Some(_) => None,
+    };
+    if let Some((error, code, status)) = error_response {
+        let error_response = ErrorResponse {
+            error: error.to_string(),
+            code,
+            details: None,
+        };
+        return Ok(warp::reply::with_status(warp::reply::json(&error_response), status));
+    }
+
+    match client
+        .execute(
+            "UPDATE users SET totp_enabled = TRUE WHERE id = $1",
+            &[&claims.sub],
+        )
+        .await
+    {
+        Ok(_) => {
+            tracing::info!(user_id = claims.sub, "2fa enabled");
+            Ok(warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "message": "Two-factor authentication enabled" })),
+                warp::http::StatusCode::OK,
+            ))
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to enable two-factor authentication".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/2fa/verify",
+    tag = "auth",
+    request_body = TwoFactorVerifyRequest,
+    responses(
+        (status = 200, description = "Logged in", body = AuthResponse),
+        (status = 401, description = "Invalid partial token or code", body = AuthErrorResponse),
+        (status = 429, description = "Too many failed attempts", body = AuthErrorResponse),
+    )
+)]
+#[tracing::instrument(skip_all)]
+pub async fn totp_verify_handler(
+    verify_req: TwoFactorVerifyRequest,
+    db_pool: Pool,
+    jwt_config: Arc<jwt::JwtConfig>,
+    rate_limiter: Arc<RateLimiter>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let claims = match jwt::verify_two_factor_jwt(&jwt_config, &verify_req.partial_token) {
+        Ok(claims) if claims.pending_2fa => claims,
+        _ => {
+            let error_response = ErrorResponse {
+                error: "Invalid or expired token".to_string(),
+                code: error_codes::INVALID_TOKEN,
+                details: None,
+            };
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::UNAUTHORIZED,
+            )
+            .into_response());
+        }
+    };
+
+    // Six digits are quick to guess, so failures count like failed logins
+    let keys = vec![format!("2fa:{}", claims.sub)];
+    if let Some(retry_after) = rate_limiter.retry_after(&keys) {
+        let error_response = ErrorResponse {
+            error: "Too many failed attempts, try again later".to_string(),
+            code: error_codes::RATE_LIMITED,
+            details: None,
+        };
+        let reply = warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::TOO_MANY_REQUESTS,
+        );
+        let retry_after_secs = retry_after.as_secs().max(1).to_string();
+        return Ok(warp::reply::with_header(reply, "Retry-After", retry_after_secs).into_response());
+    }
+
+    let response = verify_two_factor(claims.sub, &verify_req.code, db_pool, jwt_config)
+        .await
+        .into_response();
+
+    match response.status() {
+        warp::http::StatusCode::OK => rate_limiter.reset(&keys),
+        warp::http::StatusCode::UNAUTHORIZED => rate_limiter.record_failure(&keys),
+        _ => {}
+    }
+
+    Ok(response)
+}
+
+async fn verify_two_factor(
+    user_id: i32,
+    code: &str,
+    db_pool: Pool,
+    jwt_config: Arc<jwt::JwtConfig>,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    let client = match db_pool.get().await {
+        Ok(client) => client,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database connection failed".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            );
+        }
+    };
+
+    let row = match client
+        .query_opt(
+            "SELECT username, email, created_at, totp_secret, role FROM users WHERE id = $1 AND is_active AND totp_enabled",
+            &[&user_id],
+        )
+        .await
+    {
+        Ok(row) => row,
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Database error".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            return warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            );
+        }
+    };
+
+    let user = row.and_then(|row| {
+        let secret: Option<String> = row.get(3);
+        secret
+            .filter(|secret| totp::verify_code(secret, code))
+            .map(|_| row)
+    });
+    let Some(row) = user else {
+        let error_response = ErrorResponse {
+            error: "Invalid code".to_string(),
+            code: error_codes::INVALID_TOTP_CODE,
+            details: None,
+        };
+        return warp::reply::with_status(
+            warp::reply::json(&error_response),
+            warp::http::StatusCode::UNAUTHORIZED,
+        );
+    };
+
+    let created_at: chrono::NaiveDateTime = row.get(2);
+    let created_at = chrono::DateTime::<Utc>::from_naive_utc_and_offset(created_at, Utc);
+    let role = UserRole::from_db(row.get(4));
+    match start_session(
+        &client,
+        &jwt_config,
+        user_id,
+        row.get(0),
+        row.get(1),
+        role,
+        created_at,
+    )
+    .await
+    {
+        Ok(response) => {
+            tracing::info!(user_id, "user logged in with 2fa");
+            warp::reply::with_status(warp::reply::json(&response), warp::http::StatusCode::OK)
+        }
+        Err(_) => {
+            let error_response = ErrorResponse {
+                error: "Failed to generate token".to_string(),
+                code: error_codes::INTERNAL_ERROR,
+                details: None,
+            };
+            warp::reply::with_status(
+                warp::reply::json(&error_response),
+                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
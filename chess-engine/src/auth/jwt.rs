@@ -1,27 +1,98 @@
+use crate::auth::models::UserRole;
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
 
-const JWT_SECRET: &str = "your-secret-key-change-this-in-production"; // TODO: Move to env variable
-const JWT_EXPIRATION_HOURS: i64 = 24;
+const MIN_SECRET_LENGTH: usize = 32;
+const DEFAULT_ACCESS_TOKEN_EXPIRATION_MINUTES: i64 = 15;
+pub const REFRESH_TOKEN_EXPIRATION_DAYS: i64 = 30;
+/// How long a user has to enter their 2FA code after giving their password
+const TWO_FACTOR_TOKEN_EXPIRATION_MINUTES: i64 = 5;
+
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    secret: String,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+}
+
+impl JwtConfig {
+    /// Loads JWT settings from the environment. Panics if `JWT_SECRET` is
+    /// missing or too short, since running with a weak secret is never safe.
+    pub fn from_env() -> Self {
+        let secret = std::env::var("JWT_SECRET")
+            .expect("JWT_SECRET must be set in environment variables");
+        let secret = secret.trim().to_string();
+        if secret.len() < MIN_SECRET_LENGTH {
+            panic!(
+                "JWT_SECRET must be at least {} bytes long (got {})",
+                MIN_SECRET_LENGTH,
+                secret.len()
+            );
+        }
+
+        let access_token_ttl = match std::env::var("JWT_EXPIRATION_HOURS") {
+            Ok(hours) => {
+                let hours = hours
+                    .trim()
+                    .parse::<i64>()
+                    .expect("JWT_EXPIRATION_HOURS must be a whole number of hours");
+                Duration::hours(hours)
+            }
+            Err(_) => Duration::minutes(DEFAULT_ACCESS_TOKEN_EXPIRATION_MINUTES),
+        };
+
+        Self {
+            secret,
+            access_token_ttl,
+            refresh_token_ttl: Duration::days(REFRESH_TOKEN_EXPIRATION_DAYS),
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i32,        // User ID
     pub username: String,
     pub email: String,
+    #[serde(default)]
+    pub role: UserRole,  // Absent from tokens issued before roles existed
+    pub jti: Uuid,       // Token ID, used for revocation
     pub exp: i64,        // Expiration time
     pub iat: i64,        // Issued at
 }
 
-pub fn create_jwt(user_id: i32, username: String, email: String) -> Result<String, jsonwebtoken::errors::Error> {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshClaims {
+    pub sub: i32,        // User ID
+    pub jti: String,     // refresh_tokens row ID
+    pub exp: i64,
+    pub iat: i64,
+}
+
+/// Proves the password was right for a user who still has to give a 2FA
+/// code. Accepted only by `POST /api/v1/auth/2fa/verify`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorClaims {
+    pub sub: i32,        // User ID
+    pub pending_2fa: bool,
+    pub exp: i64,
+    pub iat: i64,
+}
+
+pub fn create_access_jwt(config: &JwtConfig, user_id: i32, username: String, email: String, role: UserRole) -> Result<String, jsonwebtoken::errors::Error> {
     let now = Utc::now();
-    let exp = (now + Duration::hours(JWT_EXPIRATION_HOURS)).timestamp();
-    
+    let exp = (now + config.access_token_ttl).timestamp();
+
     let claims = Claims {
         sub: user_id,
         username,
         email,
+        role,
+        jti: Uuid::new_v4(),
         exp,
         iat: now.timestamp(),
     };
@@ -29,23 +100,93 @@ pub fn create_jwt(user_id: i32, username: String, email: String) -> Result<Strin
     encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &EncodingKey::from_secret(config.secret.as_bytes()),
     )
 }
 
-pub fn verify_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+pub fn create_refresh_jwt(config: &JwtConfig, user_id: i32, token_id: Uuid) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let exp = (now + config.refresh_token_ttl).timestamp();
+
+    let claims = RefreshClaims {
+        sub: user_id,
+        jti: token_id.to_string(),
+        exp,
+        iat: now.timestamp(),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.secret.as_bytes()),
+    )
+}
+
+pub fn create_two_factor_jwt(config: &JwtConfig, user_id: i32) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = Utc::now();
+    let exp = (now + Duration::minutes(TWO_FACTOR_TOKEN_EXPIRATION_MINUTES)).timestamp();
+
+    let claims = TwoFactorClaims {
+        sub: user_id,
+        pending_2fa: true,
+        exp,
+        iat: now.timestamp(),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(config.secret.as_bytes()),
+    )
+}
+
+pub fn verify_jwt(config: &JwtConfig, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
     decode::<Claims>(
         token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &DecodingKey::from_secret(config.secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+}
+
+pub fn verify_refresh_jwt(config: &JwtConfig, token: &str) -> Result<RefreshClaims, jsonwebtoken::errors::Error> {
+    decode::<RefreshClaims>(
+        token,
+        &DecodingKey::from_secret(config.secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map(|data| data.claims)
+}
+
+pub fn verify_two_factor_jwt(config: &JwtConfig, token: &str) -> Result<TwoFactorClaims, jsonwebtoken::errors::Error> {
+    decode::<TwoFactorClaims>(
+        token,
+        &DecodingKey::from_secret(config.secret.as_bytes()),
         &Validation::default(),
     )
     .map(|data| data.claims)
 }
 
+/// Refresh and email link tokens are stored as SHA-256 hex digests, never in plaintext
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Password reset tokens are stored as BLAKE3 hex digests
+pub fn hash_reset_token(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
+}
+
+/// A random single-use token for links sent by email, as 64 hex characters
+pub fn generate_link_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
 pub fn extract_token_from_header(auth_header: &str) -> Option<&str> {
     if auth_header.starts_with("Bearer ") {
         Some(&auth_header[7..])
     } else {
         None
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,69 @@
+use crate::auth::jwt::{self, Claims, JwtConfig};
+use crate::auth::models::UserRole;
+use crate::db;
+use deadpool_postgres::Pool;
+use std::sync::Arc;
+use warp::{Filter, Rejection};
+
+#[derive(Debug)]
+pub struct AuthRejection(pub String);
+
+impl warp::reject::Reject for AuthRejection {}
+
+/// A valid token without the role the route needs
+#[derive(Debug)]
+pub struct ForbiddenRejection(pub String);
+
+impl warp::reject::Reject for ForbiddenRejection {}
+
+/// Extracts and verifies the `Authorization: Bearer <token>` header,
+/// passing the token's claims to the handler or rejecting with 401.
+/// Tokens revoked through logout are rejected as well.
+pub fn require_auth(
+    jwt_config: Arc<JwtConfig>,
+    db_pool: Pool,
+) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::any().map(move || jwt_config.clone()))
+        .and(warp::any().map(move || db_pool.clone()))
+        .and_then(|auth_header: Option<String>, jwt_config: Arc<JwtConfig>, db_pool: Pool| async move {
+            let auth_header = auth_header.ok_or_else(|| {
+                warp::reject::custom(AuthRejection("Missing authorization header".to_string()))
+            })?;
+            let token = jwt::extract_token_from_header(&auth_header).ok_or_else(|| {
+                warp::reject::custom(AuthRejection("Invalid authorization header".to_string()))
+            })?;
+
+            let claims = jwt::verify_jwt(&jwt_config, token).map_err(|_| {
+                warp::reject::custom(AuthRejection("Invalid or expired token".to_string()))
+            })?;
+
+            match db::tokens::is_token_revoked(&db_pool, claims.jti).await {
+                Ok(false) => Ok(claims),
+                Ok(true) => Err(warp::reject::custom(AuthRejection(
+                    "Token has been revoked".to_string(),
+                ))),
+                Err(_) => Err(warp::reject::custom(AuthRejection(
+                    "Unable to verify token".to_string(),
+                ))),
+            }
+        })
+}
+
+/// Like `require_auth`, but also rejects with 403 unless the token belongs
+/// to an admin. The role is read from the token, so a demotion takes effect
+/// when the user's current access token expires.
+pub fn require_admin(
+    jwt_config: Arc<JwtConfig>,
+    db_pool: Pool,
+) -> impl Filter<Extract = (Claims,), Error = Rejection> + Clone {
+    require_auth(jwt_config, db_pool).and_then(|claims: Claims| async move {
+        if claims.role == UserRole::Admin {
+            Ok(claims)
+        } else {
+            Err(warp::reject::custom(ForbiddenRejection(
+                "Admin access required".to_string(),
+            )))
+        }
+    })
+}
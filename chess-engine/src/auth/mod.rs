@@ -1,6 +1,10 @@
 pub mod models;
 pub mod handlers;
 pub mod jwt;
+pub mod middleware;
+pub mod password;
+pub mod rate_limit;
+pub mod totp;
 pub mod validation;
 
 pub use models::*;
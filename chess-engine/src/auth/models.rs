@@ -1,7 +1,34 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
+/// What a user may do, stored in the `role` column and in access tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    #[default]
+    User,
+    Admin,
+}
+
+impl UserRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserRole::User => "user",
+            UserRole::Admin => "admin",
+        }
+    }
+
+    /// Parses the `role` column, treating anything unknown as a plain user
+    pub fn from_db(role: &str) -> Self {
+        match role {
+            "admin" => UserRole::Admin,
+            _ => UserRole::User,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: i32,
@@ -12,16 +39,18 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub last_login: Option<DateTime<Utc>>,
     pub is_active: bool,
+    pub rating: i32,
+    pub rating_deviation: i32,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct SignupRequest {
     #[validate(length(min = 3, max = 50, message = "Username must be between 3 and 50 characters"))]
     #[validate(regex(path = "crate::auth::validation::USERNAME_REGEX", message = "Username can only contain letters, numbers, and underscores"))]
     pub username: String,
     
     #[validate(email(message = "Invalid email format"))]
-    #[validate(custom = "crate::auth::validation::validate_mcu_email")]
+    #[validate(custom = "crate::auth::validation::validate_email_domain")]
     pub email: String,
     
     #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
@@ -29,7 +58,7 @@ pub struct SignupRequest {
     pub password: String,
 }
 
-#[derive(Debug, Deserialize, Validate)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
 pub struct LoginRequest {
     #[validate(length(min = 1))]
     pub username_or_email: String,
@@ -38,18 +67,94 @@ pub struct LoginRequest {
     pub password: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    #[validate(custom = "crate::auth::validation::validate_password_strength")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct DeactivateAccountRequest {
+    #[validate(length(min = 1))]
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ForgotPasswordRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate, ToSchema)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+
+    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
+    #[validate(custom = "crate::auth::validation::validate_password_strength")]
+    pub new_password: String,
+}
+
+/// A code from the user's authenticator app
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TwoFactorVerifyRequest {
+    pub partial_token: String,
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct VerifyEmailQuery {
+    pub token: String,
+}
+
+/// Returned by login instead of tokens when the user has 2FA enabled
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TwoFactorRequiredResponse {
+    pub requires_2fa: bool,
+    pub partial_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TotpSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AuthResponse {
     pub token: String,
+    pub refresh_token: String,
     pub user: UserResponse,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RefreshResponse {
+    pub token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct UserResponse {
     pub id: i32,
     pub username: String,
     pub email: String,
     pub created_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
 }
 
 impl From<User> for UserResponse {
@@ -59,12 +164,15 @@ impl From<User> for UserResponse {
             username: user.username,
             email: user.email,
             created_at: user.created_at,
+            last_login: user.last_login,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+#[schema(as = AuthErrorResponse)]
 pub struct ErrorResponse {
     pub error: String,
+    pub code: &'static str,
     pub details: Option<Vec<String>>,
 }
\ No newline at end of file
@@ -0,0 +1,33 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+
+const BCRYPT_PREFIXES: [&str; 4] = ["$2a$", "$2b$", "$2x$", "$2y$"];
+
+/// Hashes a password with Argon2id, returning the full PHC string
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("failed to hash password: {}", e))?;
+
+    Ok(hash.to_string())
+}
+
+/// Older accounts were created with bcrypt before the switch to Argon2id
+pub fn is_legacy_hash(stored_hash: &str) -> bool {
+    BCRYPT_PREFIXES.iter().any(|prefix| stored_hash.starts_with(prefix))
+}
+
+/// Checks a password against either an Argon2 PHC string or a legacy bcrypt hash
+pub fn verify_password(password: &str, stored_hash: &str) -> bool {
+    if is_legacy_hash(stored_hash) {
+        return bcrypt::verify(password, stored_hash).unwrap_or(false);
+    }
+
+    match PasswordHash::new(stored_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,92 @@
+use dashmap::DashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_WINDOW_SECS: u64 = 15 * 60;
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Tracks failed login attempts per key (client IP or username) within a fixed window
+pub struct RateLimiter {
+    attempts: DashMap<String, (u32, Instant)>,
+    max_attempts: u32,
+    window: Duration,
+}
+
+impl RateLimiter {
+    pub fn new(max_attempts: u32, window: Duration) -> Self {
+        Self {
+            attempts: DashMap::new(),
+            max_attempts,
+            window,
+        }
+    }
+
+    /// Reads `RATE_LIMIT_ATTEMPTS` and `RATE_LIMIT_WINDOW_SECS`, falling back to 5 attempts per 15 minutes
+    pub fn from_env() -> Self {
+        let max_attempts = std::env::var("RATE_LIMIT_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let window_secs = std::env::var("RATE_LIMIT_WINDOW_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_WINDOW_SECS);
+
+        Self::new(max_attempts, Duration::from_secs(window_secs))
+    }
+
+    /// Returns how long the caller must wait if any of the keys is currently blocked
+    pub fn retry_after(&self, keys: &[String]) -> Option<Duration> {
+        let now = Instant::now();
+        keys.iter()
+            .filter_map(|key| {
+                let entry = self.attempts.get(key)?;
+                let (count, window_start) = *entry;
+                let elapsed = now.duration_since(window_start);
+                if count >= self.max_attempts && elapsed < self.window {
+                    Some(self.window - elapsed)
+                } else {
+                    None
+                }
+            })
+            .max()
+    }
+
+    pub fn record_failure(&self, keys: &[String]) {
+        let now = Instant::now();
+        for key in keys {
+            let mut entry = self.attempts.entry(key.clone()).or_insert((0, now));
+            if now.duration_since(entry.1) >= self.window {
+                *entry = (0, now);
+            }
+            entry.0 += 1;
+        }
+
+        if self.attempts.len() > PRUNE_THRESHOLD {
+            let window = self.window;
+            self.attempts
+                .retain(|_, (_, window_start)| now.duration_since(*window_start) < window);
+        }
+    }
+
+    pub fn reset(&self, keys: &[String]) {
+        for key in keys {
+            self.attempts.remove(key);
+        }
+    }
+}
+
+/// Best-effort client IP: the first `X-Forwarded-For` entry when behind a proxy,
+/// otherwise the socket's remote address
+pub fn client_ip() -> impl Filter<Extract = (Option<String>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("x-forwarded-for")
+        .and(warp::addr::remote())
+        .map(|forwarded_for: Option<String>, remote: Option<SocketAddr>| {
+            forwarded_for
+                .and_then(|value| value.split(',').next().map(|ip| ip.trim().to_string()))
+                .filter(|ip| !ip.is_empty())
+                .or_else(|| remote.map(|addr| addr.ip().to_string()))
+        })
+}
@@ -0,0 +1,49 @@
+//! Time-based one-time passwords (RFC 6238) as used by authenticator apps:
+//! six digits from HMAC-SHA1 over 30 second steps.
+
+use chrono::Utc;
+use rand::Rng;
+use totp_lite::{totp_custom, Sha1, DEFAULT_STEP};
+
+const DIGITS: u32 = 6;
+const SECRET_BYTES: usize = 20;
+const ISSUER: &str = "ChessEngine";
+
+/// Codes from this many steps either side of now are accepted, so a phone
+/// clock that is slightly off still works
+const ALLOWED_DRIFT_STEPS: u64 = 1;
+
+const ALPHABET: base32::Alphabet = base32::Alphabet::RFC4648 { padding: false };
+
+/// A new random secret, base32-encoded as authenticator apps expect
+pub fn generate_secret() -> String {
+    let bytes: [u8; SECRET_BYTES] = rand::thread_rng().gen();
+    base32::encode(ALPHABET, &bytes)
+}
+
+/// The `otpauth://` URI that authenticator apps scan as a QR code
+pub fn provisioning_uri(secret: &str, username: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={digits}&period={period}",
+        issuer = ISSUER,
+        username = username,
+        secret = secret,
+        digits = DIGITS,
+        period = DEFAULT_STEP,
+    )
+}
+
+/// Whether `code` is the current code for `secret`, give or take
+/// `ALLOWED_DRIFT_STEPS`
+pub fn verify_code(secret: &str, code: &str) -> bool {
+    let Some(key) = base32::decode(ALPHABET, secret) else {
+        return false;
+    };
+    let code = code.trim();
+    let now = Utc::now().timestamp().max(0) as u64;
+
+    (0..=2 * ALLOWED_DRIFT_STEPS).any(|step| {
+        let time = (now + step * DEFAULT_STEP).saturating_sub(ALLOWED_DRIFT_STEPS * DEFAULT_STEP);
+        totp_custom::<Sha1>(DEFAULT_STEP, DIGITS, &key, time) == code
+    })
+}
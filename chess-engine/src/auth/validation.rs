@@ -8,15 +8,35 @@ lazy_static! {
     pub static ref PASSWORD_LOWERCASE: Regex = Regex::new(r"[a-z]").unwrap();
     pub static ref PASSWORD_NUMBER: Regex = Regex::new(r"[0-9]").unwrap();
     pub static ref PASSWORD_SPECIAL: Regex = Regex::new(r"[!@#$%^&*(),.?:{}|<>]").unwrap();
+
+    /// Email domains accepted at signup, from the comma-separated
+    /// `ALLOWED_EMAIL_DOMAINS` variable. Empty means any domain is allowed.
+    pub static ref ALLOWED_EMAIL_DOMAINS: Vec<String> = std::env::var("ALLOWED_EMAIL_DOMAINS")
+        .map(|domains| parse_email_domains(&domains))
+        .unwrap_or_default();
+}
+
+fn parse_email_domains(domains: &str) -> Vec<String> {
+    domains
+        .split(',')
+        .map(|domain| domain.trim().trim_start_matches('@').to_lowercase())
+        .filter(|domain| !domain.is_empty())
+        .map(|domain| format!("@{}", domain))
+        .collect()
 }
 
-const ALLOWED_EMAIL_DOMAIN: &str = "@undergraduate.mcu.edu.ng";
+/// Validates that email ends with one of the configured allowed domains
+pub fn validate_email_domain(email: &str) -> Result<(), ValidationError> {
+    if ALLOWED_EMAIL_DOMAINS.is_empty() {
+        return Ok(());
+    }
 
-/// Validates that email ends with @undergraduate.mcu.edu.ng
-pub fn validate_mcu_email(email: &str) -> Result<(), ValidationError> {
-    if !email.to_lowercase().ends_with(ALLOWED_EMAIL_DOMAIN) {
+    let email = email.to_lowercase();
+    if !ALLOWED_EMAIL_DOMAINS.iter().any(|domain| email.ends_with(domain.as_str())) {
         let mut error = ValidationError::new("invalid_domain");
-        error.message = Some(format!("Email must end with {}", ALLOWED_EMAIL_DOMAIN).into());
+        error.message = Some(
+            format!("Email must end with one of: {}", ALLOWED_EMAIL_DOMAINS.join(", ")).into(),
+        );
         return Err(error);
     }
     Ok(())
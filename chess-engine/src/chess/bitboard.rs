@@ -0,0 +1,342 @@
+use super::types::{Color, Piece, PieceType, Square};
+use lazy_static::lazy_static;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// Fixed seed so magic search is deterministic and startup time predictable
+const MAGIC_SEED: u64 = 0xB17B_0A2D_5EED_0001;
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+/// Bit index of a square: a1 = 0, h1 = 7, a8 = 56
+pub fn square_bit(square: Square) -> u64 {
+    1u64 << square.to_index()
+}
+
+/// The square of the lowest set bit, if any
+pub fn first_square(bitboard: u64) -> Option<Square> {
+    if bitboard == 0 {
+        return None;
+    }
+    Square::from_index(bitboard.trailing_zeros() as u8)
+}
+
+/// Iterates over the squares of every set bit, lowest first
+pub fn squares(mut bitboard: u64) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        let square = first_square(bitboard)?;
+        bitboard &= bitboard - 1;
+        Some(square)
+    })
+}
+
+fn offset_square(index: usize, file_step: i8, rank_step: i8) -> Option<usize> {
+    let file = (index % 8) as i8 + file_step;
+    let rank = (index / 8) as i8 + rank_step;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Some((rank * 8 + file) as usize)
+    } else {
+        None
+    }
+}
+
+fn step_attacks(index: usize, offsets: &[(i8, i8)]) -> u64 {
+    offsets
+        .iter()
+        .filter_map(|&(file_step, rank_step)| offset_square(index, file_step, rank_step))
+        .fold(0, |attacks, target| attacks | (1u64 << target))
+}
+
+/// Slow ray walk, used only to build the magic tables
+fn sliding_attacks(index: usize, occupied: u64, directions: &[(i8, i8); 4]) -> u64 {
+    let mut attacks = 0;
+    for &(file_step, rank_step) in directions {
+        let mut current = index;
+        while let Some(next) = offset_square(current, file_step, rank_step) {
+            attacks |= 1u64 << next;
+            if occupied & (1u64 << next) != 0 {
+                break;
+            }
+            current = next;
+        }
+    }
+    attacks
+}
+
+/// Squares whose occupancy affects a slider's attacks; the last square
+/// of each ray is left out since it is attacked whether occupied or not
+fn relevant_mask(index: usize, directions: &[(i8, i8); 4]) -> u64 {
+    let mut mask = 0;
+    for &(file_step, rank_step) in directions {
+        let mut current = index;
+        while let Some(next) = offset_square(current, file_step, rank_step) {
+            if offset_square(next, file_step, rank_step).is_none() {
+                break;
+            }
+            mask |= 1u64 << next;
+            current = next;
+        }
+    }
+    mask
+}
+
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: u64,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn index(&self, occupied: u64) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+struct AttackTables {
+    pawn: [[u64; 64]; 2], // [color][square]
+    rook_magics: [Magic; 64],
+    bishop_magics: [Magic; 64],
+    sliders: Vec<u64>,
+}
+
+impl AttackTables {
+    fn generate() -> Self {
+        let pawn = [
+            std::array::from_fn(|index| step_attacks(index, &[(-1, 1), (1, 1)])),
+            std::array::from_fn(|index| step_attacks(index, &[(-1, -1), (1, -1)])),
+        ];
+
+        let mut rng = StdRng::seed_from_u64(MAGIC_SEED);
+        let mut sliders = Vec::new();
+        let mut rook_magics = [Magic::default(); 64];
+        let mut bishop_magics = [Magic::default(); 64];
+        for index in 0..64 {
+            rook_magics[index] = find_magic(index, &ROOK_DIRECTIONS, &mut rng, &mut sliders);
+            bishop_magics[index] = find_magic(index, &BISHOP_DIRECTIONS, &mut rng, &mut sliders);
+        }
+
+        Self {
+            pawn,
+            rook_magics,
+            bishop_magics,
+            sliders,
+        }
+    }
+}
+
+/// Searches for a multiplier that maps every occupancy subset of the
+/// square's mask to a table slot without conflicting attack sets, and
+/// appends the resulting table to `sliders`
+fn find_magic(
+    index: usize,
+    directions: &[(i8, i8); 4],
+    rng: &mut StdRng,
+    sliders: &mut Vec<u64>,
+) -> Magic {
+    let mask = relevant_mask(index, directions);
+    let bits = mask.count_ones();
+    let size = 1usize << bits;
+
+    // Enumerate every subset of the mask (carry-rippler)
+    let mut occupancies = Vec::with_capacity(size);
+    let mut attacks = Vec::with_capacity(size);
+    let mut subset = 0u64;
+    loop {
+        occupancies.push(subset);
+        attacks.push(sliding_attacks(index, subset, directions));
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+
+    let mut table = vec![0u64; size];
+    let mut epoch = vec![0u32; size];
+    let mut attempt = 0u32;
+    loop {
+        // Sparse candidates succeed far more often
+        let magic = rng.gen::<u64>() & rng.gen::<u64>() & rng.gen::<u64>();
+        if (mask.wrapping_mul(magic) >> 56).count_ones() < 6 {
+            continue;
+        }
+
+        attempt += 1;
+        let shift = 64 - bits;
+        let mut collision = false;
+        for (occupied, &attack) in occupancies.iter().zip(attacks.iter()) {
+            let slot = (occupied.wrapping_mul(magic) >> shift) as usize;
+            if epoch[slot] != attempt {
+                epoch[slot] = attempt;
+                table[slot] = attack;
+            } else if table[slot] != attack {
+                collision = true;
+                break;
+            }
+        }
+
+        if !collision {
+            let offset = sliders.len();
+            sliders.extend_from_slice(&table);
+            return Magic {
+                mask,
+                magic,
+                shift,
+                offset,
+            };
+        }
+    }
+}
+
+fn step_table(offsets: &[(i8, i8)]) -> [u64; 64] {
+    let mut table = [0u64; 64];
+    for (index, attacks) in table.iter_mut().enumerate() {
+        *attacks = step_attacks(index, offsets);
+    }
+    table
+}
+
+lazy_static! {
+    static ref TABLES: AttackTables = AttackTables::generate();
+    // Kept apart from the magic tables so the square-array board can use
+    // them without paying for the magic search
+    static ref KNIGHT_ATTACKS: [u64; 64] = step_table(&KNIGHT_OFFSETS);
+    static ref KING_ATTACKS: [u64; 64] = step_table(&KING_OFFSETS);
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn piece_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Rook => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+pub fn rook_attacks(square: Square, occupied: u64) -> u64 {
+    let magic = &TABLES.rook_magics[square.to_index() as usize];
+    TABLES.sliders[magic.index(occupied)]
+}
+
+pub fn bishop_attacks(square: Square, occupied: u64) -> u64 {
+    let magic = &TABLES.bishop_magics[square.to_index() as usize];
+    TABLES.sliders[magic.index(occupied)]
+}
+
+pub fn knight_attacks(square: Square) -> u64 {
+    KNIGHT_ATTACKS[square.to_index() as usize]
+}
+
+pub fn king_attacks(square: Square) -> u64 {
+    KING_ATTACKS[square.to_index() as usize]
+}
+
+/// Squares a pawn of `color` on `square` attacks diagonally
+pub fn pawn_attacks(square: Square, color: Color) -> u64 {
+    TABLES.pawn[color_index(color)][square.to_index() as usize]
+}
+
+/// One bitboard per piece type per color, kept in sync with the square array
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bitboards {
+    pieces: [[u64; 6]; 2], // [color][piece type]
+}
+
+impl Bitboards {
+    pub fn set(&mut self, square: Square, piece: Piece) {
+        self.pieces[color_index(piece.color)][piece_index(piece.piece_type)] |= square_bit(square);
+    }
+
+    pub fn clear(&mut self, square: Square, piece: Piece) {
+        self.pieces[color_index(piece.color)][piece_index(piece.piece_type)] &= !square_bit(square);
+    }
+
+    pub fn pieces_of(&self, color: Color, piece_type: PieceType) -> u64 {
+        self.pieces[color_index(color)][piece_index(piece_type)]
+    }
+
+    pub fn occupied_by(&self, color: Color) -> u64 {
+        self.pieces[color_index(color)].iter().fold(0, |all, bitboard| all | bitboard)
+    }
+
+    pub fn occupied(&self) -> u64 {
+        self.occupied_by(Color::White) | self.occupied_by(Color::Black)
+    }
+
+    /// Squares attacked by a piece of the given type and color on `square`,
+    /// given the current occupancy. Own pieces are not masked out.
+    pub fn attacks_from(&self, square: Square, piece_type: PieceType, color: Color) -> u64 {
+        let occupied = self.occupied();
+        match piece_type {
+            PieceType::Pawn => pawn_attacks(square, color),
+            PieceType::Knight => knight_attacks(square),
+            PieceType::Bishop => bishop_attacks(square, occupied),
+            PieceType::Rook => rook_attacks(square, occupied),
+            PieceType::Queen => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+            PieceType::King => king_attacks(square),
+        }
+    }
+
+    /// Every square attacked by at least one piece of `color`
+    pub fn attacks_by(&self, color: Color) -> u64 {
+        const PIECE_TYPES: [PieceType; 6] = [
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+            PieceType::King,
+        ];
+
+        PIECE_TYPES.iter().fold(0, |attacks, &piece_type| {
+            squares(self.pieces_of(color, piece_type)).fold(attacks, |attacks, square| {
+                attacks | self.attacks_from(square, piece_type, color)
+            })
+        })
+    }
+
+    /// Whether any piece of `by_color` attacks `square`
+    pub fn is_attacked(&self, square: Square, by_color: Color) -> bool {
+        let occupied = self.occupied();
+        let queens = self.pieces_of(by_color, PieceType::Queen);
+
+        // A pawn of the defending color on `square` would attack exactly
+        // the squares an attacking pawn could strike it from
+        pawn_attacks(square, by_color.opposite()) & self.pieces_of(by_color, PieceType::Pawn) != 0
+            || knight_attacks(square) & self.pieces_of(by_color, PieceType::Knight) != 0
+            || king_attacks(square) & self.pieces_of(by_color, PieceType::King) != 0
+            || bishop_attacks(square, occupied) & (self.pieces_of(by_color, PieceType::Bishop) | queens) != 0
+            || rook_attacks(square, occupied) & (self.pieces_of(by_color, PieceType::Rook) | queens) != 0
+    }
+}
@@ -1,16 +1,47 @@
+use super::bitboard::Bitboards;
 use super::types::{Color, Piece, PieceType, Square};
+use super::zobrist;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "BoardData")]
 pub struct Board {
     squares: [[Option<Piece>; 8]; 8],
+    hash: u64,
+    #[serde(skip)]
+    bitboards: Bitboards,
+}
+
+/// Serialized form of `Board`; bitboards are rebuilt from the squares on load
+#[derive(Deserialize)]
+struct BoardData {
+    squares: [[Option<Piece>; 8]; 8],
+    hash: u64,
+}
+
+impl From<BoardData> for Board {
+    fn from(data: BoardData) -> Self {
+        let mut bitboards = Bitboards::default();
+        for (rank, row) in data.squares.iter().enumerate() {
+            for (file, piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    bitboards.set(Square::new(file as u8, rank as u8).unwrap(), *piece);
+                }
+            }
+        }
+
+        Self {
+            squares: data.squares,
+            hash: data.hash,
+            bitboards,
+        }
+    }
 }
 
 impl Board {
     pub fn new() -> Self {
-        let mut board = Self {
-            squares: [[None; 8]; 8],
-        };
+        let mut board = Self::empty();
         board.setup_starting_position();
         board
     }
@@ -18,7 +49,23 @@ impl Board {
     pub fn empty() -> Self {
         Self {
             squares: [[None; 8]; 8],
+            hash: 0,
+            bitboards: Bitboards::default(),
+        }
+    }
+
+    /// A starting position with the given back rank for both sides,
+    /// mirrored for Black, and pawns on their usual ranks
+    pub fn with_back_rank(back_rank: [PieceType; 8]) -> Self {
+        let mut board = Self::empty();
+        for (file, piece_type) in back_rank.into_iter().enumerate() {
+            let file = file as u8;
+            board.set_piece(Square::new(file, 0).unwrap(), Piece::new(piece_type, Color::White));
+            board.set_piece(Square::new(file, 1).unwrap(), Piece::new(PieceType::Pawn, Color::White));
+            board.set_piece(Square::new(file, 6).unwrap(), Piece::new(PieceType::Pawn, Color::Black));
+            board.set_piece(Square::new(file, 7).unwrap(), Piece::new(piece_type, Color::Black));
         }
+        board
     }
 
     fn setup_starting_position(&mut self) {
@@ -26,8 +73,8 @@ impl Board {
         self.set_piece(Square::new(0, 0).unwrap(), Piece::new(PieceType::Rook, Color::White));
         self.set_piece(Square::new(1, 0).unwrap(), Piece::new(PieceType::Knight, Color::White));
         self.set_piece(Square::new(2, 0).unwrap(), Piece::new(PieceType::Bishop, Color::White));
-        self.set_piece(Square::new(3, 0).unwrap(), Piece::new(PieceType::King, Color::White));
-        self.set_piece(Square::new(4, 0).unwrap(), Piece::new(PieceType::Queen, Color::White));
+        self.set_piece(Square::new(3, 0).unwrap(), Piece::new(PieceType::Queen, Color::White));
+        self.set_piece(Square::new(4, 0).unwrap(), Piece::new(PieceType::King, Color::White));
         self.set_piece(Square::new(5, 0).unwrap(), Piece::new(PieceType::Bishop, Color::White));
         self.set_piece(Square::new(6, 0).unwrap(), Piece::new(PieceType::Knight, Color::White));
         self.set_piece(Square::new(7, 0).unwrap(), Piece::new(PieceType::Rook, Color::White));
@@ -41,8 +88,8 @@ impl Board {
         self.set_piece(Square::new(0, 7).unwrap(), Piece::new(PieceType::Rook, Color::Black));
         self.set_piece(Square::new(1, 7).unwrap(), Piece::new(PieceType::Knight, Color::Black));
         self.set_piece(Square::new(2, 7).unwrap(), Piece::new(PieceType::Bishop, Color::Black));
-        self.set_piece(Square::new(3, 7).unwrap(), Piece::new(PieceType::King, Color::Black));
-        self.set_piece(Square::new(4, 7).unwrap(), Piece::new(PieceType::Queen, Color::Black));
+        self.set_piece(Square::new(3, 7).unwrap(), Piece::new(PieceType::Queen, Color::Black));
+        self.set_piece(Square::new(4, 7).unwrap(), Piece::new(PieceType::King, Color::Black));
         self.set_piece(Square::new(5, 7).unwrap(), Piece::new(PieceType::Bishop, Color::Black));
         self.set_piece(Square::new(6, 7).unwrap(), Piece::new(PieceType::Knight, Color::Black));
         self.set_piece(Square::new(7, 7).unwrap(), Piece::new(PieceType::Rook, Color::Black));
@@ -63,7 +110,13 @@ impl Board {
 
     pub fn set_piece(&mut self, square: Square, piece: Piece) {
         if square.is_valid() {
+            if let Some(old) = self.squares[square.rank as usize][square.file as usize] {
+                self.hash ^= zobrist::piece_key(old, square);
+                self.bitboards.clear(square, old);
+            }
             self.squares[square.rank as usize][square.file as usize] = Some(piece);
+            self.hash ^= zobrist::piece_key(piece, square);
+            self.bitboards.set(square, piece);
         }
     }
 
@@ -71,6 +124,10 @@ impl Board {
         if square.is_valid() {
             let piece = self.squares[square.rank as usize][square.file as usize];
             self.squares[square.rank as usize][square.file as usize] = None;
+            if let Some(piece) = piece {
+                self.hash ^= zobrist::piece_key(piece, square);
+                self.bitboards.clear(square, piece);
+            }
             piece
         } else {
             None
@@ -84,50 +141,131 @@ impl Board {
         captured
     }
 
+    #[cfg(feature = "bitboards")]
     pub fn find_king(&self, color: Color) -> Option<Square> {
-        for rank in 0..8 {
-            for file in 0..8 {
-                let square = Square::new(file, rank).unwrap();
-                if let Some(piece) = self.get_piece(square) {
-                    if piece.piece_type == PieceType::King && piece.color == color {
-                        return Some(square);
-                    }
-                }
-            }
-        }
-        None
+        super::bitboard::first_square(self.bitboards.pieces_of(color, PieceType::King))
+    }
+
+    #[cfg(not(feature = "bitboards"))]
+    pub fn find_king(&self, color: Color) -> Option<Square> {
+        self.get_all_pieces()
+            .find(|(_, piece)| *piece == Piece::new(PieceType::King, color))
+            .map(|(square, _)| square)
+    }
+
+    /// Every occupied square and its piece, a1 through h8
+    pub fn get_all_pieces(&self) -> impl Iterator<Item = (Square, Piece)> + '_ {
+        Square::iter().filter_map(move |square| self.get_piece(square).map(|piece| (square, piece)))
     }
 
     pub fn get_pieces(&self, color: Color) -> Vec<(Square, Piece)> {
-        let mut pieces = Vec::new();
-        for rank in 0..8 {
-            for file in 0..8 {
-                let square = Square::new(file, rank).unwrap();
-                if let Some(piece) = self.get_piece(square) {
-                    if piece.color == color {
-                        pieces.push((square, piece));
-                    }
-                }
-            }
-        }
-        pieces
+        self.get_all_pieces()
+            .filter(|(_, piece)| piece.color == color)
+            .collect()
+    }
+
+    /// Number of pieces of both colors on the board, kings included
+    pub fn piece_count(&self) -> u8 {
+        self.get_all_pieces().count() as u8
+    }
+
+    pub fn is_empty_square(&self, square: Square) -> bool {
+        self.get_piece(square).is_none()
+    }
+
+    /// Number of pieces of the given color and type
+    pub fn count_piece_type(&self, color: Color, piece_type: PieceType) -> u8 {
+        self.bitboards.pieces_of(color, piece_type).count_ones() as u8
+    }
+
+    /// Total centipawn value of a side's pieces, kings excluded
+    pub fn material_value(&self, color: Color) -> i32 {
+        [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen]
+            .into_iter()
+            .map(|piece_type| {
+                let count = self.count_piece_type(color, piece_type) as i32;
+                count * piece_type.centipawn_value()
+            })
+            .sum()
+    }
+
+    /// Total centipawn value of a side's pieces, kings excluded; the same
+    /// as `material_value`
+    pub fn material_count(&self, color: Color) -> i32 {
+        self.material_value(color)
+    }
+
+    /// `material_value` of both sides together
+    pub fn total_material(&self) -> i32 {
+        self.material_value(Color::White) + self.material_value(Color::Black)
+    }
+
+    /// Number of pieces of every color and type, with zero for the
+    /// types a side has none of
+    pub fn count_pieces_by_type(&self) -> HashMap<(Color, PieceType), u8> {
+        let piece_types = [
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+            PieceType::King,
+        ];
+        [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(|color| piece_types.map(|piece_type| (color, piece_type)))
+            .map(|key| (key, self.count_piece_type(key.0, key.1)))
+            .collect()
     }
 
+    /// Every square whose contents differ from `other`, a1 through h8,
+    /// with the piece on it here and the piece on it in `other`
+    pub fn diff(&self, other: &Board) -> Vec<(Square, Option<Piece>, Option<Piece>)> {
+        Square::iter()
+            .filter_map(|square| {
+                let before = self.get_piece(square);
+                let after = other.get_piece(square);
+                (before != after).then_some((square, before, after))
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "bitboards")]
+    pub fn is_square_attacked(&self, square: Square, by_color: Color) -> bool {
+        self.bitboards.is_attacked(square, by_color)
+    }
+
+    #[cfg(not(feature = "bitboards"))]
     pub fn is_square_attacked(&self, square: Square, by_color: Color) -> bool {
         // Check if any piece of the given color can attack the square
-        for rank in 0..8 {
-            for file in 0..8 {
-                let from = Square::new(file, rank).unwrap();
-                if let Some(piece) = self.get_piece(from) {
-                    if piece.color == by_color && self.can_piece_attack(from, square, piece) {
-                        return true;
-                    }
-                }
-            }
+        self.get_all_pieces()
+            .any(|(from, piece)| piece.color == by_color && self.can_piece_attack(from, square, piece))
+    }
+
+    /// Every square at least one piece of `color` attacks, a1 through h8
+    #[cfg(feature = "bitboards")]
+    pub fn attacked_squares_by(&self, color: Color) -> Vec<Square> {
+        super::bitboard::squares(self.bitboards.attacks_by(color)).collect()
+    }
+
+    /// Every square at least one piece of `color` attacks, a1 through h8
+    #[cfg(not(feature = "bitboards"))]
+    pub fn attacked_squares_by(&self, color: Color) -> Vec<Square> {
+        Square::iter()
+            .filter(|&square| self.is_square_attacked(square, color))
+            .collect()
+    }
+
+    /// Whether the king of `color` is out of reach of every enemy piece.
+    /// A board without that king counts as safe.
+    pub fn is_king_safe(&self, color: Color) -> bool {
+        match self.find_king(color) {
+            Some(king) => !self.attacked_squares_by(color.opposite()).contains(&king),
+            None => true,
         }
-        false
     }
 
+    #[cfg(not(feature = "bitboards"))]
     fn can_piece_attack(&self, from: Square, to: Square, piece: Piece) -> bool {
         if from == to {
             return false;
@@ -149,14 +287,15 @@ impl Board {
                 && self.is_path_clear(from, to)
             }
             PieceType::Knight => {
-                (file_diff == 2 && rank_diff == 1) || (file_diff == 1 && rank_diff == 2)
+                super::bitboard::knight_attacks(from) & super::bitboard::square_bit(to) != 0
             }
             PieceType::King => {
-                file_diff <= 1 && rank_diff <= 1
+                super::bitboard::king_attacks(from) & super::bitboard::square_bit(to) != 0
             }
         }
     }
 
+    #[cfg(not(feature = "bitboards"))]
     fn can_pawn_attack(&self, from: Square, to: Square, color: Color) -> bool {
         let direction = match color {
             Color::White => 1,
@@ -189,13 +328,140 @@ impl Board {
         true
     }
 
+    /// Zobrist hash of the position, including side to move, castling
+    /// rights and en passant file once maintained by `GameState`
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn xor_hash(&mut self, key: u64) {
+        self.hash ^= key;
+    }
+
+    pub fn bitboards(&self) -> &Bitboards {
+        &self.bitboards
+    }
+
     pub fn to_2d_array(&self) -> [[Option<Piece>; 8]; 8] {
         self.squares
     }
+
+    /// Text diagram with White at the bottom, uppercase for White pieces
+    /// and lowercase for Black
+    pub fn to_ascii(&self) -> String {
+        self.diagram(false)
+    }
+
+    /// Same layout as `to_ascii`, drawn with Unicode chess symbols
+    pub fn to_unicode(&self) -> String {
+        self.diagram(true)
+    }
+
+    fn diagram(&self, unicode: bool) -> String {
+        let mut diagram = String::new();
+        for rank in (0..8).rev() {
+            diagram.push((b'1' + rank) as char);
+            for file in 0..8 {
+                let symbol = match self.get_piece(Square::new(file, rank).unwrap()) {
+                    Some(piece) if unicode => unicode_symbol(piece),
+                    Some(piece) => piece.to_fen_char(),
+                    None if unicode => '·',
+                    None => '.',
+                };
+                diagram.push(' ');
+                diagram.push(symbol);
+            }
+            diagram.push('\n');
+        }
+        diagram.push_str("  a b c d e f g h\n");
+        diagram
+    }
+}
+
+fn unicode_symbol(piece: Piece) -> char {
+    match (piece.color, piece.piece_type) {
+        (Color::White, PieceType::King) => '♔',
+        (Color::White, PieceType::Queen) => '♕',
+        (Color::White, PieceType::Rook) => '♖',
+        (Color::White, PieceType::Bishop) => '♗',
+        (Color::White, PieceType::Knight) => '♘',
+        (Color::White, PieceType::Pawn) => '♙',
+        (Color::Black, PieceType::King) => '♚',
+        (Color::Black, PieceType::Queen) => '♛',
+        (Color::Black, PieceType::Rook) => '♜',
+        (Color::Black, PieceType::Bishop) => '♝',
+        (Color::Black, PieceType::Knight) => '♞',
+        (Color::Black, PieceType::Pawn) => '♟',
+    }
 }
 
 impl Default for Board {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::notation::{algebraic_to_square, uci_to_move};
+    use crate::chess::GameState;
+
+    fn piece_on(board: &Board, square: &str) -> Option<Piece> {
+        board.get_piece(algebraic_to_square(square).unwrap())
+    }
+
+    #[test]
+    fn kings_and_queens_start_on_their_own_files() {
+        let board = Board::new();
+        assert_eq!(piece_on(&board, "e1"), Some(Piece::new(PieceType::King, Color::White)));
+        assert_eq!(piece_on(&board, "d1"), Some(Piece::new(PieceType::Queen, Color::White)));
+        assert_eq!(piece_on(&board, "e8"), Some(Piece::new(PieceType::King, Color::Black)));
+        assert_eq!(piece_on(&board, "d8"), Some(Piece::new(PieceType::Queen, Color::Black)));
+    }
+
+    #[test]
+    fn counts_pieces_and_material_of_the_starting_position() {
+        let board = Board::new();
+        let counts = board.count_pieces_by_type();
+        assert_eq!(counts.len(), 12);
+        assert_eq!(counts[&(Color::White, PieceType::Pawn)], 8);
+        assert_eq!(counts[&(Color::Black, PieceType::King)], 1);
+        assert_eq!(board.material_count(Color::White), 4000);
+        assert_eq!(board.total_material(), 2 * board.material_count(Color::Black));
+    }
+
+    #[test]
+    fn king_is_unsafe_on_a_square_the_enemy_attacks() {
+        let state = GameState::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 0 1").unwrap();
+        let attacked = state.board.attacked_squares_by(Color::White);
+        assert!(attacked.contains(&algebraic_to_square("a8").unwrap()));
+        assert!(!attacked.contains(&algebraic_to_square("e8").unwrap()));
+        assert!(state.board.is_king_safe(Color::Black));
+
+        let state = GameState::from_fen("R3k3/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        assert!(!state.board.is_king_safe(Color::Black));
+        assert!(state.is_in_check(Color::Black));
+    }
+
+    #[test]
+    fn diff_lists_the_squares_castling_changes() {
+        let mut state = GameState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let before = state.board.clone();
+        let castling = state.infer_castling(uci_to_move("e1g1").unwrap());
+        state.make_move(castling).unwrap();
+
+        let king = Piece::new(PieceType::King, Color::White);
+        let rook = Piece::new(PieceType::Rook, Color::White);
+        let square = |name| algebraic_to_square(name).unwrap();
+        assert_eq!(
+            before.diff(&state.board),
+            vec![
+                (square("e1"), Some(king), None),
+                (square("f1"), None, Some(rook)),
+                (square("g1"), None, Some(king)),
+                (square("h1"), Some(rook), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use super::types::Color;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TimeControl {
+    #[default]
+    Unlimited,
+    Classic { initial_ms: u64, increment_ms: u64 },
+    Correspondence { days_per_move: u8 },
+}
+
+impl TimeControl {
+    /// Time each side starts with (and, for correspondence, gets back after every move)
+    fn initial_ms(&self) -> u64 {
+        match *self {
+            TimeControl::Unlimited => 0,
+            TimeControl::Classic { initial_ms, .. } => initial_ms,
+            TimeControl::Correspondence { days_per_move } => days_per_move as u64 * MS_PER_DAY,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct Clock {
+    pub white_ms_remaining: u64,
+    pub black_ms_remaining: u64,
+    // Not persisted: after a reload the running player's time starts
+    // counting again from their next move
+    #[serde(skip)]
+    pub last_move_instant: Option<Instant>,
+}
+
+impl Clock {
+    pub fn new(time_control: &TimeControl) -> Self {
+        let initial_ms = time_control.initial_ms();
+        Self {
+            white_ms_remaining: initial_ms,
+            black_ms_remaining: initial_ms,
+            last_move_instant: None,
+        }
+    }
+
+    pub fn remaining_ms(&self, color: Color) -> u64 {
+        match color {
+            Color::White => self.white_ms_remaining,
+            Color::Black => self.black_ms_remaining,
+        }
+    }
+
+    fn remaining_mut(&mut self, color: Color) -> &mut u64 {
+        match color {
+            Color::White => &mut self.white_ms_remaining,
+            Color::Black => &mut self.black_ms_remaining,
+        }
+    }
+
+    /// Deducts the time `color` has spent since the last move. Returns
+    /// false once their clock has run out. The clock only starts running
+    /// after the first move.
+    pub fn deduct_elapsed(&mut self, color: Color) -> bool {
+        if let Some(last_move) = self.last_move_instant {
+            let elapsed_ms = last_move.elapsed().as_millis() as u64;
+            let remaining = self.remaining_mut(color);
+            *remaining = remaining.saturating_sub(elapsed_ms);
+        }
+        self.last_move_instant = Some(Instant::now());
+
+        self.remaining_ms(color) > 0
+    }
+
+    /// Credits `color` after completing a move
+    pub fn complete_move(&mut self, color: Color, time_control: &TimeControl) {
+        match *time_control {
+            TimeControl::Unlimited => {}
+            TimeControl::Classic { increment_ms, .. } => {
+                *self.remaining_mut(color) += increment_ms;
+            }
+            TimeControl::Correspondence { .. } => {
+                *self.remaining_mut(color) = time_control.initial_ms();
+            }
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use super::{
+    bitboard::{self, Bitboards},
+    board::Board,
+    clock::TimeControl,
+    evaluation::{evaluate, MATE_SCORE},
+    game::GameState,
+    transposition::{BoundType, TranspositionEntry, TranspositionTable},
+    types::*,
+};
+use std::cmp::Reverse;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+const INFINITY: i32 = 1_000_000;
+
+// Scores beyond this are mates, stored relative to the node rather than the root
+const MATE_THRESHOLD: i32 = MATE_SCORE - 1_000;
+
+// Move ordering tiers; history scores are capped below the killer tier
+const HASH_MOVE_SCORE: i32 = 3_000_000;
+const CAPTURE_SCORE: i32 = 2_000_000;
+const KILLER_SCORE: i32 = 1_000_000;
+
+pub struct Engine {
+    pub max_depth: u8,
+    pub table: Arc<Mutex<TranspositionTable>>,
+}
+
+/// A root move with its score in centipawns for the side to move
+#[derive(Debug, Clone)]
+pub struct ScoredMove {
+    pub chess_move: Move,
+    pub score: i32,
+}
+
+struct SearchContext {
+    deadline: Instant,
+    timed_out: bool,
+    /// Two quiet moves per ply that recently caused a beta cutoff
+    killers: Vec<[Option<Move>; 2]>,
+    /// Cutoff counts for quiet moves, weighted by depth, indexed [from][to]
+    history: Vec<[i32; 64]>,
+}
+
+impl SearchContext {
+    fn new(deadline: Instant) -> Self {
+        Self {
+            deadline,
+            timed_out: false,
+            killers: Vec::new(),
+            history: vec![[0; 64]; 64],
+        }
+    }
+
+    fn killers_at(&self, ply: usize) -> &[Option<Move>] {
+        self.killers.get(ply).map_or(&[], |killers| &killers[..])
+    }
+
+    fn history_score(&self, chess_move: &Move) -> i32 {
+        self.history[chess_move.from.to_index() as usize][chess_move.to.to_index() as usize]
+    }
+
+    /// Remembers a quiet move that refuted the position at `ply`
+    fn record_cutoff(&mut self, chess_move: &Move, ply: usize, depth: u8) {
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None, None]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0].as_ref() != Some(chess_move) {
+            killers[1] = killers[0].take();
+            killers[0] = Some(chess_move.clone());
+        }
+
+        let entry = &mut self.history[chess_move.from.to_index() as usize]
+            [chess_move.to.to_index() as usize];
+        *entry = (*entry + depth as i32 * depth as i32).min(KILLER_SCORE - 1);
+    }
+
+    fn check_time(&mut self) -> bool {
+        if !self.timed_out && Instant::now() >= self.deadline {
+            self.timed_out = true;
+        }
+        self.timed_out
+    }
+}
+
+impl Engine {
+    pub fn new(max_depth: u8) -> Self {
+        Self {
+            max_depth: max_depth.max(1),
+            table: Arc::new(Mutex::new(TranspositionTable::default())),
+        }
+    }
+
+    /// Shares an existing table so results carry over between engines
+    pub fn with_table(mut self, table: Arc<Mutex<TranspositionTable>>) -> Self {
+        self.table = table;
+        self
+    }
+
+    /// Searches the position with iterative deepening: depth 1, then 2, and
+    /// so on up to `max_depth` or until `deadline` passes. An iteration cut
+    /// short by the deadline is discarded in favor of the last completed one.
+    pub fn best_move_timed(&self, state: &GameState, deadline: Instant) -> Option<Move> {
+        let mut moves = state.get_legal_moves();
+        if moves.is_empty() {
+            return None;
+        }
+
+        // Search positions are hypothetical, so they shouldn't tick the real clock
+        let mut root = state.clone();
+        root.time_control = TimeControl::Unlimited;
+
+        let mut ctx = SearchContext::new(deadline);
+
+        // A poisoned table only means a previous search panicked mid-store
+        let mut table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut best_move = moves[0].clone();
+        for depth in 1..=self.max_depth {
+            let (chess_move, score) =
+                match self.search_root(&root, &mut moves, depth, &mut table, &mut ctx) {
+                    Some(result) => result,
+                    None => break,
+                };
+            best_move = chess_move;
+
+            // No deeper search will find a faster mate
+            if score.abs() > MATE_THRESHOLD {
+                break;
+            }
+        }
+
+        Some(best_move)
+    }
+
+    /// Scores every legal move with an exact (full-window) search, deepening
+    /// like `best_move_timed`. Moves are returned best first. If not even
+    /// the first iteration finishes in time, the scores are static evaluations.
+    pub fn score_moves_timed(&self, state: &GameState, deadline: Instant) -> Vec<ScoredMove> {
+        let mut root = state.clone();
+        root.time_control = TimeControl::Unlimited;
+
+        let mut scored: Vec<ScoredMove> = root
+            .get_legal_moves()
+            .into_iter()
+            .filter_map(|chess_move| {
+                let mut child = root.clone();
+                child.make_move(chess_move.clone()).ok()?;
+                let score = if let Some(winner) = child.winner() {
+                    if winner == root.current_player {
+                        MATE_SCORE - 1
+                    } else {
+                        -(MATE_SCORE - 1)
+                    }
+                } else if child.is_draw() || child.can_claim_draw().is_some() {
+                    0
+                } else {
+                    -relative_score(&child)
+                };
+                Some(ScoredMove { chess_move, score })
+            })
+            .collect();
+        if scored.is_empty() {
+            return scored;
+        }
+        scored.sort_by_key(|m| Reverse(m.score));
+
+        let mut ctx = SearchContext::new(deadline);
+        let mut table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+
+        for depth in 1..=self.max_depth {
+            let mut iteration = Vec::with_capacity(scored.len());
+            for ScoredMove { chess_move, .. } in &scored {
+                let mut child = root.clone();
+                if child.make_move(chess_move.clone()).is_err() {
+                    continue;
+                }
+
+                let score = -self.negamax(
+                    &child,
+                    depth - 1,
+                    1,
+                    -INFINITY,
+                    INFINITY,
+                    &mut table,
+                    &mut ctx,
+                );
+                if ctx.timed_out {
+                    break;
+                }
+                iteration.push(ScoredMove {
+                    chess_move: chess_move.clone(),
+                    score,
+                });
+            }
+            if ctx.timed_out {
+                break;
+            }
+
+            // Sorting by the last iteration also orders the next one
+            iteration.sort_by_key(|m| Reverse(m.score));
+            scored = iteration;
+        }
+
+        scored
+    }
+
+    /// The line the search expects after `first`, read back from the
+    /// transposition table and cut short at the first move that isn't legal
+    pub fn principal_variation(
+        &self,
+        state: &GameState,
+        first: &Move,
+        max_len: usize,
+    ) -> Vec<Move> {
+        let table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+        let mut position = state.clone();
+        position.time_control = TimeControl::Unlimited;
+
+        let mut line = Vec::new();
+        let mut next = Some(first.clone());
+        while let Some(chess_move) = next {
+            if line.len() >= max_len || position.make_move(chess_move.clone()).is_err() {
+                break;
+            }
+            line.push(chess_move);
+            next = table
+                .probe(position.board.hash())
+                .and_then(|entry| entry.best_move.clone());
+        }
+
+        line
+    }
+
+    /// One full-width search of the root moves. Returns `None` if the
+    /// deadline passed before every move was searched.
+    fn search_root(
+        &self,
+        root: &GameState,
+        moves: &mut Vec<Move>,
+        depth: u8,
+        table: &mut TranspositionTable,
+        ctx: &mut SearchContext,
+    ) -> Option<(Move, i32)> {
+        let hash = root.board.hash();
+        let hash_move = table.probe(hash).and_then(|e| e.best_move.clone());
+        *moves = order_moves(std::mem::take(moves), root, hash_move, ctx, 0);
+
+        let mut best_move = moves[0].clone();
+        let mut alpha = -INFINITY;
+        let beta = INFINITY;
+
+        for chess_move in moves.iter() {
+            let mut child = root.clone();
+            if child.make_move(chess_move.clone()).is_err() {
+                continue;
+            }
+
+            let score = -self.negamax(&child, depth - 1, 1, -beta, -alpha, table, ctx);
+            if ctx.timed_out {
+                return None;
+            }
+
+            if score > alpha {
+                alpha = score;
+                best_move = chess_move.clone();
+            }
+        }
+
+        table.store(TranspositionEntry {
+            hash,
+            depth,
+            score: alpha,
+            flag: BoundType::Exact,
+            best_move: Some(best_move.clone()),
+        });
+
+        Some((best_move, alpha))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(
+        &self,
+        state: &GameState,
+        depth: u8,
+        ply: i32,
+        mut alpha: i32,
+        beta: i32,
+        table: &mut TranspositionTable,
+        ctx: &mut SearchContext,
+    ) -> i32 {
+        if ctx.check_time() {
+            return 0;
+        }
+
+        // In Antichess the side left without a move is the one that wins
+        if let Some(winner) = state.winner() {
+            return if winner == state.current_player {
+                MATE_SCORE - ply
+            } else {
+                -MATE_SCORE + ply
+            };
+        }
+        // Whichever side is worse off would claim a claimable draw
+        if state.is_draw() || state.can_claim_draw().is_some() {
+            return 0;
+        }
+
+        if depth == 0 {
+            return relative_score(state);
+        }
+
+        let hash = state.board.hash();
+        let mut hash_move = None;
+        if let Some(entry) = table.probe(hash) {
+            if entry.depth >= depth {
+                let score = score_from_table(entry.score, ply);
+                match entry.flag {
+                    BoundType::Exact => return score,
+                    BoundType::LowerBound if score >= beta => return beta,
+                    BoundType::UpperBound if score <= alpha => return alpha,
+                    _ => {}
+                }
+            }
+            hash_move = entry.best_move.clone();
+        }
+
+        let moves = order_moves(state.get_legal_moves(), state, hash_move, ctx, ply as usize);
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+
+        for chess_move in moves {
+            let mut child = state.clone();
+            if child.make_move(chess_move.clone()).is_err() {
+                continue;
+            }
+
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha, table, ctx);
+            if ctx.timed_out {
+                return 0;
+            }
+
+            if score >= beta {
+                if !chess_move.is_capture(&state.board) {
+                    ctx.record_cutoff(&chess_move, ply as usize, depth);
+                }
+                table.store(TranspositionEntry {
+                    hash,
+                    depth,
+                    score: score_to_table(beta, ply),
+                    flag: BoundType::LowerBound,
+                    best_move: Some(chess_move),
+                });
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(chess_move);
+            }
+        }
+
+        let flag = if alpha > original_alpha {
+            BoundType::Exact
+        } else {
+            BoundType::UpperBound
+        };
+        table.store(TranspositionEntry {
+            hash,
+            depth,
+            score: score_to_table(alpha, ply),
+            flag,
+            best_move,
+        });
+
+        alpha
+    }
+}
+
+/// Sorts moves so the likeliest refutations are searched first: the
+/// table's best move, then captures that don't lose material by how much
+/// they win, then killer moves, then quiet moves by history score, and
+/// captures that lose material last
+fn order_moves(
+    moves: Vec<Move>,
+    state: &GameState,
+    tt_best_move: Option<Move>,
+    ctx: &SearchContext,
+    ply: usize,
+) -> Vec<Move> {
+    let killers = ctx.killers_at(ply);
+    let mut scored: Vec<(i32, Move)> = moves
+        .into_iter()
+        .map(|chess_move| {
+            let score = if tt_best_move.as_ref() == Some(&chess_move) {
+                HASH_MOVE_SCORE
+            } else if let Some(gain) = exchange_score(&chess_move, &state.board) {
+                if gain >= 0 {
+                    CAPTURE_SCORE + gain
+                } else {
+                    gain
+                }
+            } else if let Some(slot) = killers
+                .iter()
+                .position(|killer| killer.as_ref() == Some(&chess_move))
+            {
+                KILLER_SCORE - slot as i32
+            } else {
+                ctx.history_score(&chess_move)
+            };
+            (score, chess_move)
+        })
+        .collect();
+
+    // Stable, so equal scores keep generation order
+    scored.sort_by_key(|(score, _)| Reverse(*score));
+    scored
+        .into_iter()
+        .map(|(_, chess_move)| chess_move)
+        .collect()
+}
+
+/// `see` for a capture, or `None` if the move takes nothing
+fn exchange_score(chess_move: &Move, board: &Board) -> Option<i32> {
+    let target = chess_move.captured_piece(board)?;
+    let attacker = board.get_piece(chess_move.from)?;
+    Some(see(board, chess_move.to, target, chess_move.from, attacker))
+}
+
+/// Static Exchange Evaluation: the centipawns won, or lost if negative, by
+/// `attacker_piece` on `from` taking `target_piece` on `square` when both
+/// sides then keep recapturing there with their least valuable piece for
+/// as long as it pays. Sliders lined up behind a capturer join in once it
+/// has gone.
+fn see(
+    board: &Board,
+    square: Square,
+    target_piece: Piece,
+    from: Square,
+    attacker_piece: Piece,
+) -> i32 {
+    let bitboards = board.bitboards();
+    let mut occupied = bitboards.occupied() & !bitboard::square_bit(from);
+
+    // What each capture in the sequence wins for its side, if the
+    // exchange were to stop right after it
+    let mut gains = vec![target_piece.centipawn_value()];
+    let mut on_square = attacker_piece;
+    let mut side = attacker_piece.color.opposite();
+    while let Some((capturer_square, capturer)) =
+        least_valuable_attacker(bitboards, square, occupied, side)
+    {
+        gains.push(on_square.centipawn_value() - gains[gains.len() - 1]);
+        occupied &= !bitboard::square_bit(capturer_square);
+        on_square = capturer;
+        side = side.opposite();
+    }
+
+    // Working back from the end, each side only recaptures when that beats
+    // stopping
+    while gains.len() > 1 {
+        let recapture = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -((-*previous).max(recapture));
+    }
+    gains[0]
+}
+
+/// The cheapest piece of `color` attacking `square`, among the pieces
+/// still on `occupied`
+fn least_valuable_attacker(
+    bitboards: &Bitboards,
+    square: Square,
+    occupied: u64,
+    color: Color,
+) -> Option<(Square, Piece)> {
+    const PIECE_TYPES: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
+    PIECE_TYPES.into_iter().find_map(|piece_type| {
+        let reach = match piece_type {
+            // Squares a pawn of `color` could take on `square` from
+            PieceType::Pawn => bitboard::pawn_attacks(square, color.opposite()),
+            PieceType::Knight => bitboard::knight_attacks(square),
+            PieceType::Bishop => bitboard::bishop_attacks(square, occupied),
+            PieceType::Rook => bitboard::rook_attacks(square, occupied),
+            PieceType::Queen => {
+                bitboard::bishop_attacks(square, occupied)
+                    | bitboard::rook_attacks(square, occupied)
+            }
+            PieceType::King => bitboard::king_attacks(square),
+        };
+        let attackers = reach & bitboards.pieces_of(color, piece_type) & occupied;
+        bitboard::first_square(attackers).map(|from| (from, Piece::new(piece_type, color)))
+    })
+}
+
+/// Mate scores count plies from the root; the table stores them as
+/// distance from the node so they stay valid at any depth
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    if score > MATE_THRESHOLD {
+        score + ply
+    } else if score < -MATE_THRESHOLD {
+        score - ply
+    } else {
+        score
+    }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    if score > MATE_THRESHOLD {
+        score - ply
+    } else if score < -MATE_THRESHOLD {
+        score + ply
+    } else {
+        score
+    }
+}
+
+/// Evaluation from the perspective of the side to move
+fn relative_score(state: &GameState) -> i32 {
+    let score = evaluate(state);
+    match state.current_player {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
@@ -0,0 +1,362 @@
+use super::{board::Board, game::GameState, types::*};
+
+/// Score for a won position, far above any material balance
+pub const MATE_SCORE: i32 = 100_000;
+
+const MOBILITY_WEIGHT: i32 = 5;
+const ROOK_OPEN_FILE_BONUS: i32 = 20;
+const ROOK_HALF_OPEN_FILE_BONUS: i32 = 10;
+const BISHOP_PAIR_BONUS: i32 = 50;
+// Per rook a side is up when the opponent has the extra minor pieces instead
+const ROOK_VS_MINOR_ENDGAME_BONUS: i32 = 40;
+// A passed pawn is worth this on its starting rank, and 10 more per rank advanced
+const PASSED_PAWN_BONUS: i32 = 30;
+const PASSED_PAWN_RANK_BONUS: i32 = 10;
+
+// Knights do better in closed positions and bishops in open ones: each pawn
+// on the board above this count makes a knight worth a little more than a
+// bishop, and each pawn below it the other way round
+const BALANCED_PAWN_COUNT: i32 = 12;
+const MINOR_PIECE_PAWN_WEIGHT: i32 = 4;
+
+// King safety penalties, only counted before the endgame
+const KING_OPEN_FILE_PENALTY: i32 = 80;
+const MISSING_SHIELD_PAWN_PENALTY: i32 = 30;
+const KING_ATTACKER_PENALTY: i32 = 15;
+const UNCASTLED_KING_PENALTY: i32 = 50;
+// The king should have castled by the time this move is reached
+const CASTLING_DEADLINE_MOVE: u32 = 10;
+
+// Game phase by the total material of both sides, kings not counted: the
+// opening until a few pieces have come off, the endgame once roughly a rook
+// or two and the pawns are left per side
+const OPENING_MATERIAL: i32 = 7000;
+const ENDGAME_MATERIAL: i32 = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GamePhase {
+    Opening,
+    Middlegame,
+    Endgame,
+}
+
+// Piece-square tables from White's point of view, rank 8 first.
+// Black pieces use the same tables mirrored vertically.
+const PAWN_TABLE: [[i8; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [50, 50, 50, 50, 50, 50, 50, 50],
+    [10, 10, 20, 30, 30, 20, 10, 10],
+    [5, 5, 10, 25, 25, 10, 5, 5],
+    [0, 0, 0, 20, 20, 0, 0, 0],
+    [5, -5, -10, 0, 0, -10, -5, 5],
+    [5, 10, 10, -20, -20, 10, 10, 5],
+    [0, 0, 0, 0, 0, 0, 0, 0],
+];
+
+const KNIGHT_TABLE: [[i8; 8]; 8] = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20, 0, 0, 0, 0, -20, -40],
+    [-30, 0, 10, 15, 15, 10, 0, -30],
+    [-30, 5, 15, 20, 20, 15, 5, -30],
+    [-30, 0, 15, 20, 20, 15, 0, -30],
+    [-30, 5, 10, 15, 15, 10, 5, -30],
+    [-40, -20, 0, 5, 5, 0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+const BISHOP_TABLE: [[i8; 8]; 8] = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 10, 10, 5, 0, -10],
+    [-10, 5, 5, 10, 10, 5, 5, -10],
+    [-10, 0, 10, 10, 10, 10, 0, -10],
+    [-10, 10, 10, 10, 10, 10, 10, -10],
+    [-10, 5, 0, 0, 0, 0, 5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+const ROOK_TABLE: [[i8; 8]; 8] = [
+    [0, 0, 0, 0, 0, 0, 0, 0],
+    [5, 10, 10, 10, 10, 10, 10, 5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [-5, 0, 0, 0, 0, 0, 0, -5],
+    [0, 0, 0, 5, 5, 0, 0, 0],
+];
+
+const QUEEN_TABLE: [[i8; 8]; 8] = [
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+    [-10, 0, 0, 0, 0, 0, 0, -10],
+    [-10, 0, 5, 5, 5, 5, 0, -10],
+    [-5, 0, 5, 5, 5, 5, 0, -5],
+    [0, 0, 5, 5, 5, 5, 0, -5],
+    [-10, 5, 5, 5, 5, 5, 0, -10],
+    [-10, 0, 5, 0, 0, 0, 0, -10],
+    [-20, -10, -10, -5, -5, -10, -10, -20],
+];
+
+// Opening and middlegame: stay tucked away behind the pawns
+const KING_TABLE: [[i8; 8]; 8] = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [20, 20, 0, 0, 0, 0, 20, 20],
+    [20, 30, 10, 0, 0, 10, 30, 20],
+];
+
+// Endgame: head for the center
+const KING_ENDGAME_TABLE: [[i8; 8]; 8] = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10, 0, 0, -10, -20, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 30, 40, 40, 30, -10, -30],
+    [-30, -10, 20, 30, 30, 20, -10, -30],
+    [-30, -30, 0, 0, 0, 0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+/// Static evaluation in centipawns from White's point of view
+pub fn evaluate(state: &GameState) -> i32 {
+    if let Some(winner) = state.winner() {
+        return match winner {
+            Color::White => MATE_SCORE,
+            Color::Black => -MATE_SCORE,
+        };
+    }
+    if state.is_draw() {
+        return 0;
+    }
+
+    let board = &state.board;
+    let phase = game_phase(board);
+    let endgame = phase == GamePhase::Endgame;
+    let mut score = 0;
+
+    for color in [Color::White, Color::Black] {
+        let sign = match color {
+            Color::White => 1,
+            Color::Black => -1,
+        };
+
+        let mut positional =
+            board.material_count(color) + material_imbalance(board, color, endgame);
+        for (square, piece) in board.get_pieces(color) {
+            positional += square_bonus(piece, square, endgame);
+            match piece.piece_type {
+                PieceType::Rook => positional += rook_file_bonus(board, square, color),
+                PieceType::Pawn => positional += passed_pawn_bonus(board, square, color),
+                _ => {}
+            }
+        }
+        if !endgame {
+            positional += king_safety_score(state, color);
+        }
+
+        score += sign * positional;
+    }
+
+    score + mobility(state)
+}
+
+/// Phase of the game by how much material is left on the board
+fn game_phase(board: &Board) -> GamePhase {
+    let material = board.total_material();
+    if material > OPENING_MATERIAL {
+        GamePhase::Opening
+    } else if material > ENDGAME_MATERIAL {
+        GamePhase::Middlegame
+    } else {
+        GamePhase::Endgame
+    }
+}
+
+fn square_bonus(piece: Piece, square: Square, endgame: bool) -> i32 {
+    let row = match piece.color {
+        Color::White => 7 - square.rank as usize,
+        Color::Black => square.rank as usize,
+    };
+    let col = square.file as usize;
+
+    let table = match piece.piece_type {
+        PieceType::Pawn => &PAWN_TABLE,
+        PieceType::Knight => &KNIGHT_TABLE,
+        PieceType::Bishop => &BISHOP_TABLE,
+        PieceType::Rook => &ROOK_TABLE,
+        PieceType::Queen => &QUEEN_TABLE,
+        PieceType::King if endgame => &KING_ENDGAME_TABLE,
+        PieceType::King => &KING_TABLE,
+    };
+    table[row][col] as i32
+}
+
+/// Adjustments for how the pieces combine rather than what each is worth
+/// alone: the bishop pair, knights against bishops depending on how many
+/// pawns are left, and rooks against minor pieces in the endgame
+fn material_imbalance(board: &Board, color: Color, endgame: bool) -> i32 {
+    let count =
+        |color: Color, piece_type: PieceType| board.count_piece_type(color, piece_type) as i32;
+    let minors = |color: Color| count(color, PieceType::Knight) + count(color, PieceType::Bishop);
+    let enemy = color.opposite();
+
+    let mut score = 0;
+    if count(color, PieceType::Bishop) >= 2 {
+        score += BISHOP_PAIR_BONUS;
+    }
+
+    let pawns = count(Color::White, PieceType::Pawn) + count(Color::Black, PieceType::Pawn);
+    let knights_over_bishops = count(color, PieceType::Knight) - count(color, PieceType::Bishop);
+    score += knights_over_bishops * (pawns - BALANCED_PAWN_COUNT) * MINOR_PIECE_PAWN_WEIGHT;
+
+    if endgame {
+        let extra_rooks = count(color, PieceType::Rook) - count(enemy, PieceType::Rook);
+        let missing_minors = minors(enemy) - minors(color);
+        if extra_rooks > 0 && missing_minors > 0 {
+            score += extra_rooks.min(missing_minors) * ROOK_VS_MINOR_ENDGAME_BONUS;
+        }
+    }
+
+    score
+}
+
+/// Bonus for a pawn with no enemy pawn in front of it on its own or an
+/// adjacent file, growing as it nears promotion
+fn passed_pawn_bonus(board: &Board, square: Square, color: Color) -> i32 {
+    let ahead = |rank: u8| match color {
+        Color::White => rank > square.rank,
+        Color::Black => rank < square.rank,
+    };
+
+    let blocked = board
+        .get_pieces(color.opposite())
+        .iter()
+        .any(|&(enemy, piece)| {
+            piece.piece_type == PieceType::Pawn
+                && (enemy.file as i8 - square.file as i8).abs() <= 1
+                && ahead(enemy.rank)
+        });
+    if blocked {
+        return 0;
+    }
+
+    let ranks_advanced = match color {
+        Color::White => square.rank as i32 - 1,
+        Color::Black => 6 - square.rank as i32,
+    };
+    PASSED_PAWN_BONUS + ranks_advanced * PASSED_PAWN_RANK_BONUS
+}
+
+/// Bonus for a rook on a file with no pawns, or with only enemy pawns
+fn rook_file_bonus(board: &Board, square: Square, color: Color) -> i32 {
+    let mut own_pawns = false;
+    let mut enemy_pawns = false;
+
+    for rank in 0..8 {
+        if let Some(piece) = Square::new(square.file, rank).and_then(|sq| board.get_piece(sq)) {
+            if piece.piece_type == PieceType::Pawn {
+                if piece.color == color {
+                    own_pawns = true;
+                } else {
+                    enemy_pawns = true;
+                }
+            }
+        }
+    }
+
+    match (own_pawns, enemy_pawns) {
+        (false, false) => ROOK_OPEN_FILE_BONUS,
+        (false, true) => ROOK_HALF_OPEN_FILE_BONUS,
+        _ => 0,
+    }
+}
+
+/// Penalties for an exposed king: standing on a file without pawns,
+/// missing pawns in front of it once castled, enemy pieces close by and,
+/// past the opening moves, still sitting in the center without having
+/// castled. Zero or negative, from `color`'s point of view.
+fn king_safety_score(state: &GameState, color: Color) -> i32 {
+    let board = &state.board;
+    let king = match board.find_king(color) {
+        Some(king) => king,
+        None => return 0,
+    };
+    let home_rank = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    let forward: i8 = match color {
+        Color::White => 1,
+        Color::Black => -1,
+    };
+    let pawn_on = |file: u8, pawn_color: Option<Color>| {
+        (0..8).any(|rank| {
+            Square::new(file, rank)
+                .and_then(|square| board.get_piece(square))
+                .is_some_and(|piece| {
+                    piece.piece_type == PieceType::Pawn
+                        && pawn_color.is_none_or(|pawn_color| piece.color == pawn_color)
+                })
+        })
+    };
+
+    let mut score = 0;
+    if !pawn_on(king.file, None) {
+        score -= KING_OPEN_FILE_PENALTY;
+    }
+
+    // Castled: on the back rank, on either wing
+    let castled = king.rank == home_rank && !(3..=5).contains(&king.file);
+    if castled {
+        for file in king.file.saturating_sub(1)..=(king.file + 1).min(7) {
+            let shielded = (1..=2i8).any(|distance| {
+                Square::new(file, (king.rank as i8 + forward * distance) as u8)
+                    .and_then(|square| board.get_piece(square))
+                    == Some(Piece::new(PieceType::Pawn, color))
+            });
+            if !shielded {
+                score -= MISSING_SHIELD_PAWN_PENALTY;
+            }
+        }
+    }
+
+    // Enemy pieces within two squares of the king
+    for (square, piece) in board.get_pieces(color.opposite()) {
+        let distance = (square.file as i8 - king.file as i8)
+            .abs()
+            .max((square.rank as i8 - king.rank as i8).abs());
+        if distance <= 2 {
+            score -= KING_ATTACKER_PENALTY * attacker_weight(piece.piece_type);
+        }
+    }
+
+    let on_home_square = king == Square::new(4, home_rank).unwrap();
+    let can_still_castle = state.castling_rights.can_castle(color, true)
+        || state.castling_rights.can_castle(color, false);
+    if on_home_square && can_still_castle && state.fullmove_number > CASTLING_DEADLINE_MOVE {
+        score -= UNCASTLED_KING_PENALTY;
+    }
+
+    score
+}
+
+/// How dangerous a piece is near the enemy king, roughly in proportion to
+/// its value
+fn attacker_weight(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight | PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 5,
+        PieceType::King => 0,
+    }
+}
+
+/// Difference in legal move counts between White and Black
+fn mobility(state: &GameState) -> i32 {
+    (state.mobility_score(Color::White) - state.mobility_score(Color::Black)) * MOBILITY_WEIGHT
+}
@@ -1,6 +1,10 @@
-use super::{board::Board, types::*};
+use super::{board::Board, clock::{Clock, TimeControl}, notation, types::*, zobrist};
+#[cfg(feature = "bitboards")]
+use super::bitboard;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use thiserror::Error;
+use utoipa::ToSchema;
 
 #[derive(Debug, Error)]
 pub enum ChessError {
@@ -12,10 +16,68 @@ pub enum ChessError {
     NotYourTurn,
     #[error("King would be in check")]
     KingInCheck,
+    #[error("Castling rights have been revoked")]
+    CastlingRightsRevoked,
+    #[error("Castling path blocked")]
+    CastlingPathBlocked,
+    #[error("Cannot castle through check")]
+    CastlingThroughCheck,
+    #[error("No draw offer to accept")]
+    NoDrawOffer,
+    #[error("Time has run out")]
+    TimeExpired,
+    #[error("No moves to undo")]
+    NothingToUndo,
+    #[error("Invalid FEN: {0}")]
+    InvalidFen(String),
+    #[error("A capture is available and must be made")]
+    CaptureRequired,
+    #[error("No draw claim available")]
+    NoDrawClaim,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ChessError {
+    /// Stable identifier for API clients, independent of the message wording
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ChessError::InvalidMove(_) => "INVALID_MOVE",
+            ChessError::GameOver => "GAME_OVER",
+            ChessError::NotYourTurn => "NOT_YOUR_TURN",
+            ChessError::KingInCheck => "KING_IN_CHECK",
+            ChessError::CastlingRightsRevoked => "CASTLING_RIGHTS_REVOKED",
+            ChessError::CastlingPathBlocked => "CASTLING_PATH_BLOCKED",
+            ChessError::CastlingThroughCheck => "CASTLING_THROUGH_CHECK",
+            ChessError::NoDrawOffer => "NO_DRAW_OFFER",
+            ChessError::TimeExpired => "TIME_EXPIRED",
+            ChessError::NothingToUndo => "NOTHING_TO_UNDO",
+            ChessError::InvalidFen(_) => "INVALID_FEN",
+            ChessError::CaptureRequired => "CAPTURE_REQUIRED",
+            ChessError::NoDrawClaim => "NO_DRAW_CLAIM",
+        }
+    }
+}
+
+// Halfmoves without a pawn move or capture after which a draw may be
+// claimed, and after which the game is drawn without a claim
+const FIFTY_MOVE_RULE_PLIES: u32 = 100;
+const SEVENTY_FIVE_MOVE_RULE_PLIES: u32 = 150;
+
+// Occurrences of a position after which a draw may be claimed, and after
+// which the game is drawn without a claim
+const CLAIMABLE_REPETITIONS: u8 = 3;
+const AUTOMATIC_REPETITIONS: u8 = 5;
+
+/// Each color's kingside and queenside castling, in FEN order
+const CASTLING_SIDES: [(Color, bool); 4] = [
+    (Color::White, true),
+    (Color::White, false),
+    (Color::Black, true),
+    (Color::Black, false),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct GameState {
+    #[schema(value_type = Object)]
     pub board: Board,
     pub current_player: Color,
     pub castling_rights: CastlingRights,
@@ -23,11 +85,112 @@ pub struct GameState {
     pub halfmove_clock: u32,
     pub fullmove_number: u32,
     pub status: GameStatus,
+    pub history: Vec<Move>,
+    pub starting_fen: Option<String>,
+    pub created_by: Option<i32>,
+    pub white_player: Option<i32>,
+    pub black_player: Option<i32>,
+    pub pending_draw_offer: Option<Color>,
+    #[serde(default)]
+    pub time_control: TimeControl,
+    #[serde(default)]
+    pub clock: Clock,
+    #[serde(default)]
+    pub game_mode: GameMode,
+    #[serde(default)]
+    pub variant: Variant,
+    /// Times each position has occurred, keyed by `to_position_key`
+    #[serde(default)]
+    pub position_counts: HashMap<String, u8>,
+    /// Pieces taken off the board, with the fullmove number of the capture
+    #[serde(default, with = "captured_pieces_format")]
+    #[schema(value_type = Object)]
+    pub captured_pieces: Vec<(Piece, u32)>,
+    /// Sequence number of the last published change, which only ever grows
+    #[serde(default)]
+    pub event_id: u64,
+    /// The event each move in `history` was published with
+    #[serde(default)]
+    pub move_event_ids: Vec<u64>,
+}
+
+/// Captured pieces grouped by the side that took them, as
+/// `{"captured_by_white": [...], "captured_by_black": [...]}`
+mod captured_pieces_format {
+    use super::{Color, Piece};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct CapturedPiece {
+        #[serde(flatten)]
+        piece: Piece,
+        move_number: u32,
+    }
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct ByCapturer {
+        captured_by_white: Vec<CapturedPiece>,
+        captured_by_black: Vec<CapturedPiece>,
+    }
+
+    pub fn serialize<S: Serializer>(
+        captured: &[(Piece, u32)],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut grouped = ByCapturer::default();
+        for &(piece, move_number) in captured {
+            let entry = CapturedPiece { piece, move_number };
+            match piece.color {
+                Color::Black => grouped.captured_by_white.push(entry),
+                Color::White => grouped.captured_by_black.push(entry),
+            }
+        }
+        grouped.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<(Piece, u32)>, D::Error> {
+        let grouped = ByCapturer::deserialize(deserializer)?;
+        let mut captured: Vec<(Piece, u32)> = grouped
+            .captured_by_white
+            .into_iter()
+            .chain(grouped.captured_by_black)
+            .map(|entry| (entry.piece, entry.move_number))
+            .collect();
+        captured.sort_by_key(|&(_, move_number)| move_number);
+        Ok(captured)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PgnMetadata {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+    pub result: String,
+}
+
+impl Default for PgnMetadata {
+    fn default() -> Self {
+        Self {
+            event: "?".to_string(),
+            site: "?".to_string(),
+            date: "????.??.??".to_string(),
+            round: "?".to_string(),
+            white: "?".to_string(),
+            black: "?".to_string(),
+            result: "*".to_string(),
+        }
+    }
 }
 
 impl GameState {
     pub fn new() -> Self {
-        Self {
+        let mut state = Self {
             board: Board::new(),
             current_player: Color::White,
             castling_rights: CastlingRights::new(),
@@ -35,35 +198,629 @@ impl GameState {
             halfmove_clock: 0,
             fullmove_number: 1,
             status: GameStatus::InProgress,
+            history: Vec::new(),
+            starting_fen: None,
+            created_by: None,
+            white_player: None,
+            black_player: None,
+            pending_draw_offer: None,
+            time_control: TimeControl::Unlimited,
+            clock: Clock::default(),
+            game_mode: GameMode::default(),
+            variant: Variant::default(),
+            position_counts: HashMap::new(),
+            captured_pieces: Vec::new(),
+            event_id: 0,
+            move_event_ids: Vec::new(),
+        };
+        state.board.xor_hash(zobrist::castling_key(&state.castling_rights));
+        state
+    }
+
+    /// The Chess960 starting position with the given Scharnagl number.
+    /// Ids wrap around past 959; 518 is the standard setup.
+    pub fn new_chess960(position_id: u16) -> Self {
+        let mut n = (position_id % 960) as usize;
+        let mut back_rank: [Option<PieceType>; 8] = [None; 8];
+
+        // Bishops on opposite colors: light squares are b, d, f, h
+        back_rank[2 * (n % 4) + 1] = Some(PieceType::Bishop);
+        n /= 4;
+        back_rank[2 * (n % 4)] = Some(PieceType::Bishop);
+        n /= 4;
+
+        fn place_on_empty(back_rank: &mut [Option<PieceType>; 8], index: usize, piece_type: PieceType) {
+            let file = (0..8).filter(|&file| back_rank[file].is_none()).nth(index).unwrap();
+            back_rank[file] = Some(piece_type);
+        }
+
+        place_on_empty(&mut back_rank, n % 6, PieceType::Queen);
+        n /= 6;
+
+        // Knights take two of the five remaining squares, indexed 0..10
+        const KNIGHT_PLACEMENTS: [(usize, usize); 10] =
+            [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+        let (first, second) = KNIGHT_PLACEMENTS[n];
+        // Placing the second knight first keeps the first one's index valid
+        place_on_empty(&mut back_rank, second, PieceType::Knight);
+        place_on_empty(&mut back_rank, first, PieceType::Knight);
+
+        // The king goes between the two rooks
+        let remaining: Vec<usize> = (0..8).filter(|&file| back_rank[file].is_none()).collect();
+        back_rank[remaining[0]] = Some(PieceType::Rook);
+        back_rank[remaining[1]] = Some(PieceType::King);
+        back_rank[remaining[2]] = Some(PieceType::Rook);
+
+        let mut state = Self::new();
+        state.board = Board::with_back_rank(back_rank.map(Option::unwrap));
+        state.castling_rights = CastlingRights::chess960(remaining[2] as u8, remaining[0] as u8);
+        state.board.xor_hash(zobrist::castling_key(&state.castling_rights));
+        state.variant = Variant::Chess960;
+        state.starting_fen = Some(state.to_fen());
+        state
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, ChessError> {
+        Self::from_fen_with_variant(fen, Variant::default())
+    }
+
+    /// Parses `fen` as a position of `variant`, so the initial status is
+    /// judged by that variant's rules
+    pub fn from_fen_with_variant(fen: &str, variant: Variant) -> Result<Self, ChessError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 6 {
+            return Err(ChessError::InvalidFen(format!(
+                "expected 6 fields, found {}",
+                fields.len()
+            )));
+        }
+
+        // Piece placement
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(ChessError::InvalidFen(format!(
+                "expected 8 ranks, found {}",
+                ranks.len()
+            )));
+        }
+
+        let mut board = Board::empty();
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let rank = 7 - i as u8;
+            let mut file: u8 = 0;
+            for c in rank_str.chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    if !(1..=8).contains(&digit) {
+                        return Err(ChessError::InvalidFen(format!(
+                            "invalid empty square count '{}' on rank {}",
+                            c,
+                            rank + 1
+                        )));
+                    }
+                    file += digit as u8;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or_else(|| {
+                        ChessError::InvalidFen(format!("unknown piece character '{}'", c))
+                    })?;
+                    let square = Square::new(file, rank).ok_or_else(|| {
+                        ChessError::InvalidFen(format!("too many squares on rank {}", rank + 1))
+                    })?;
+                    board.set_piece(square, piece);
+                    file += 1;
+                }
+                if file > 8 {
+                    return Err(ChessError::InvalidFen(format!(
+                        "too many squares on rank {}",
+                        rank + 1
+                    )));
+                }
+            }
+            if file != 8 {
+                return Err(ChessError::InvalidFen(format!(
+                    "rank {} describes {} squares instead of 8",
+                    rank + 1,
+                    file
+                )));
+            }
+        }
+
+        // Antichess kings can be captured, so a side may have none left
+        for color in [Color::White, Color::Black] {
+            let kings = board.count_piece_type(color, PieceType::King);
+            if kings != 1 && variant != Variant::Antichess {
+                return Err(ChessError::InvalidFen(format!(
+                    "expected exactly one {:?} king, found {}",
+                    color, kings
+                )));
+            }
+        }
+
+        // Active color
+        let mut active = fields[1].chars();
+        let current_player = match (active.next().map(Color::try_from), active.next()) {
+            (Some(Ok(color)), None) => color,
+            _ => {
+                return Err(ChessError::InvalidFen(format!(
+                    "invalid active color '{}'",
+                    fields[1]
+                )));
+            }
+        };
+
+        // Castling rights, as KQkq or with Chess960 rook files (Shredder-FEN, e.g. HAha)
+        let mut castling_rights = match fields[2].parse::<CastlingRights>() {
+            Ok(mut castling_rights) => {
+                // In Chess960, K and Q mean the outermost rook on that side
+                for (color, kingside) in CASTLING_SIDES {
+                    if castling_rights.can_castle(color, kingside) {
+                        let rook_file = Self::outermost_rook_file(&board, color, kingside);
+                        castling_rights.set_rook_file(color, kingside, rook_file);
+                    }
+                }
+                castling_rights
+            }
+            Err(_) => Self::parse_rook_file_castling(fields[2], &board).ok_or_else(|| {
+                ChessError::InvalidFen(format!("invalid castling rights '{}'", fields[2]))
+            })?,
+        };
+        // Antichess has no castling, whatever the FEN says
+        if variant == Variant::Antichess {
+            castling_rights = CastlingRights::none();
+        }
+
+        // En passant target
+        let en_passant_target = if fields[3] == "-" {
+            None
+        } else {
+            let square = notation::algebraic_to_square(fields[3]).ok_or_else(|| {
+                ChessError::InvalidFen(format!("invalid en passant square '{}'", fields[3]))
+            })?;
+            if square.rank != 2 && square.rank != 5 {
+                return Err(ChessError::InvalidFen(format!(
+                    "en passant square '{}' must be on rank 3 or 6",
+                    fields[3]
+                )));
+            }
+            Some(square)
+        };
+
+        // Halfmove clock and fullmove number
+        let halfmove_clock = fields[4].parse::<u32>().map_err(|_| {
+            ChessError::InvalidFen(format!("invalid halfmove clock '{}'", fields[4]))
+        })?;
+        let fullmove_number = fields[5].parse::<u32>().map_err(|_| {
+            ChessError::InvalidFen(format!("invalid fullmove number '{}'", fields[5]))
+        })?;
+        if fullmove_number == 0 {
+            return Err(ChessError::InvalidFen(
+                "fullmove number must be at least 1".to_string(),
+            ));
+        }
+
+        let mut state = Self {
+            board,
+            current_player,
+            castling_rights,
+            en_passant_target,
+            halfmove_clock,
+            fullmove_number,
+            status: GameStatus::InProgress,
+            history: Vec::new(),
+            starting_fen: Some(fen.to_string()),
+            created_by: None,
+            white_player: None,
+            black_player: None,
+            pending_draw_offer: None,
+            time_control: TimeControl::Unlimited,
+            clock: Clock::default(),
+            game_mode: GameMode::default(),
+            variant,
+            position_counts: HashMap::new(),
+            captured_pieces: Vec::new(),
+            event_id: 0,
+            move_event_ids: Vec::new(),
+        };
+        let mut state_key = zobrist::castling_key(&state.castling_rights)
+            ^ zobrist::en_passant_key(state.en_passant_target);
+        if state.current_player == Color::Black {
+            state_key ^= zobrist::side_key();
+        }
+        state.board.xor_hash(state_key);
+        state.validate_position()?;
+        state.update_status();
+
+        Ok(state)
+    }
+
+    /// Castling rights given as rook files, e.g. `HAha`, which is how
+    /// Shredder-FEN names Chess960 rooks
+    fn parse_rook_file_castling(field: &str, board: &Board) -> Option<CastlingRights> {
+        let mut castling_rights = CastlingRights::none();
+        for c in field.chars() {
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king_file = Self::back_rank_king_file(board, color)?;
+            let file = match c.to_ascii_lowercase() {
+                file @ 'a'..='h' => file as u8 - b'a',
+                _ => return None,
+            };
+            let kingside = file > king_file;
+            castling_rights.grant(color, kingside);
+            castling_rights.set_rook_file(color, kingside, file);
+        }
+        Some(castling_rights)
+    }
+
+    fn back_rank_king_file(board: &Board, color: Color) -> Option<u8> {
+        let back_rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        board
+            .find_king(color)
+            .filter(|square| square.rank == back_rank)
+            .map(|square| square.file)
+    }
+
+    /// File of the rook furthest from the king on the given side of the back
+    /// rank, which is what K and Q refer to in a Chess960 FEN. Falls back to
+    /// the standard h- or a-file when there is no such rook.
+    fn outermost_rook_file(board: &Board, color: Color, kingside: bool) -> u8 {
+        let rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        let king_file = Self::back_rank_king_file(board, color);
+        let files: Vec<u8> = if kingside { (0..8).rev().collect() } else { (0..8).collect() };
+        files
+            .into_iter()
+            .take_while(|&file| Some(file) != king_file)
+            .find(|&file| {
+                board.get_piece(Square::new(file, rank).unwrap()) == Some(Piece::new(PieceType::Rook, color))
+            })
+            .unwrap_or(if kingside { 7 } else { 0 })
+    }
+
+    /// Rejects positions that could never arise in a legal game
+    fn validate_position(&self) -> Result<(), ChessError> {
+        for color in [Color::White, Color::Black] {
+            let pieces = self.board.get_pieces(color);
+            let pawns = self.board.count_piece_type(color, PieceType::Pawn);
+            if pieces.len() > 16 || pawns > 8 {
+                return Err(ChessError::InvalidFen(format!("too many {:?} pieces", color)));
+            }
+            let back_rank_pawn = pieces.iter().any(|(square, piece)| {
+                piece.piece_type == PieceType::Pawn && (square.rank == 0 || square.rank == 7)
+            });
+            if back_rank_pawn {
+                return Err(ChessError::InvalidFen(
+                    "pawns cannot stand on the first or last rank".to_string(),
+                ));
+            }
+        }
+
+        if self.is_in_check(self.current_player.opposite()) {
+            return Err(ChessError::InvalidFen(
+                "the side not to move is in check".to_string(),
+            ));
+        }
+
+        let has_piece = |file: u8, rank: u8, piece_type: PieceType, color: Color| {
+            Square::new(file, rank).and_then(|square| self.board.get_piece(square))
+                == Some(Piece::new(piece_type, color))
+        };
+        for color in [Color::White, Color::Black] {
+            for kingside in [true, false] {
+                if !self.castling_rights.can_castle(color, kingside) {
+                    continue;
+                }
+                let rank = match color {
+                    Color::White => 0,
+                    Color::Black => 7,
+                };
+                let rook_file = self.castling_rights.rook_file(color, kingside);
+                let king_home = self.board.find_king(color).is_some_and(|king| {
+                    king.rank == rank && (rook_file > king.file) == kingside
+                });
+                if !king_home || !has_piece(rook_file, rank, PieceType::Rook, color) {
+                    return Err(ChessError::InvalidFen(
+                        "castling rights require the king and rook on their starting squares".to_string(),
+                    ));
+                }
+            }
+        }
+
+        if let Some(target) = self.en_passant_target {
+            // The pawn that just double-stepped belongs to the side not to move
+            let (target_rank, pawn_rank) = match self.current_player {
+                Color::White => (5, 4),
+                Color::Black => (2, 3),
+            };
+            if target.rank != target_rank
+                || !has_piece(target.file, pawn_rank, PieceType::Pawn, self.current_player.opposite())
+            {
+                return Err(ChessError::InvalidFen(format!(
+                    "en passant square {} does not follow a double pawn push",
+                    notation::square_to_algebraic(target)
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the given user may play moves in this game
+    pub fn is_participant(&self, user_id: i32) -> bool {
+        match self.created_by {
+            Some(creator) => {
+                creator == user_id
+                    || self.white_player == Some(user_id)
+                    || self.black_player == Some(user_id)
+            }
+            None => true,
+        }
+    }
+
+    pub fn player_for(&self, color: Color) -> Option<i32> {
+        match color {
+            Color::White => self.white_player,
+            Color::Black => self.black_player,
+        }
+    }
+
+    /// The color assigned to a user, if they have one
+    pub fn color_of(&self, user_id: i32) -> Option<Color> {
+        if self.white_player == Some(user_id) {
+            Some(Color::White)
+        } else if self.black_player == Some(user_id) {
+            Some(Color::Black)
+        } else {
+            None
         }
     }
 
+    /// Whether both colors have an assigned player, so only they may move
+    pub fn has_both_players(&self) -> bool {
+        self.white_player.is_some() && self.black_player.is_some()
+    }
+
+    /// Whether the given user may move for the side to play. Sides without
+    /// an assigned player can be moved by any participant.
+    pub fn is_players_turn(&self, user_id: i32) -> bool {
+        match self.player_for(self.current_player) {
+            Some(player) => player == user_id,
+            None => self.is_participant(user_id),
+        }
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.status.is_game_over()
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.status.is_draw()
+    }
+
+    pub fn winner(&self) -> Option<Color> {
+        self.status.winner()
+    }
+
+    pub fn set_time_control(&mut self, time_control: TimeControl) {
+        self.time_control = time_control;
+        self.clock = Clock::new(&time_control);
+    }
+
     pub fn make_move(&mut self, chess_move: Move) -> Result<(), ChessError> {
         // Check if game is over
-        match self.status {
-            GameStatus::Checkmate(_) | GameStatus::Stalemate | GameStatus::Draw => {
-                return Err(ChessError::GameOver);
-            }
-            _ => {}
+        if self.is_game_over() {
+            return Err(ChessError::GameOver);
+        }
+
+        // Charge the side to move for their thinking time
+        let mover = self.current_player;
+        if self.time_control != TimeControl::Unlimited && !self.clock.deduct_elapsed(mover) {
+            self.status = GameStatus::Timeout(mover);
+            self.pending_draw_offer = None;
+            return Err(ChessError::TimeExpired);
         }
 
         // Validate the move
         self.validate_move(&chess_move)?;
 
-        // Make the move
+        self.apply_move(chess_move);
+        self.clock.complete_move(mover, &self.time_control);
+
+        Ok(())
+    }
+
+    /// Plays a move that is already known to be legal
+    fn apply_move(&mut self, chess_move: Move) {
+        let previous_castling = zobrist::castling_key(&self.castling_rights);
+        let previous_en_passant = zobrist::en_passant_key(self.en_passant_target);
+
+        // Playing on instead of accepting declines the opponent's draw offer
+        if self.pending_draw_offer == Some(self.current_player.opposite()) {
+            self.pending_draw_offer = None;
+        }
+
+        // The starting position counts towards repetitions too
+        if self.position_counts.is_empty() {
+            self.record_position();
+        }
+
+        // Make the move, noting first whether it takes a piece
+        let was_capture = chess_move.is_capture(&self.board);
         self.execute_move(chess_move.clone());
 
         // Update game state
         self.update_castling_rights(&chess_move);
         self.update_en_passant(&chess_move);
-        self.update_clocks(&chess_move);
+        self.update_clocks(&chess_move, was_capture);
         self.switch_player();
+        self.board.xor_hash(
+            zobrist::side_key()
+                ^ previous_castling
+                ^ zobrist::castling_key(&self.castling_rights)
+                ^ previous_en_passant
+                ^ zobrist::en_passant_key(self.en_passant_target),
+        );
+        self.record_position();
         self.update_status();
+    }
+
+    fn record_position(&mut self) {
+        *self.position_counts.entry(self.to_position_key()).or_insert(0) += 1;
+    }
+
+    /// Ends the game with `color` resigning
+    pub fn resign(&mut self, color: Color) -> Result<(), ChessError> {
+        if self.is_game_over() {
+            return Err(ChessError::GameOver);
+        }
+
+        self.status = GameStatus::Resigned(color.opposite());
+        self.pending_draw_offer = None;
+        Ok(())
+    }
+
+    pub fn offer_draw(&mut self, color: Color) -> Result<(), ChessError> {
+        if self.is_game_over() {
+            return Err(ChessError::GameOver);
+        }
+
+        self.pending_draw_offer = Some(color);
+        Ok(())
+    }
+
+    /// Accepts a draw offered by the other side
+    pub fn accept_draw(&mut self, color: Color) -> Result<(), ChessError> {
+        if self.is_game_over() {
+            return Err(ChessError::GameOver);
+        }
+        if self.pending_draw_offer != Some(color.opposite()) {
+            return Err(ChessError::NoDrawOffer);
+        }
+
+        self.status = GameStatus::Draw;
+        self.pending_draw_offer = None;
+        Ok(())
+    }
+
+    /// Why a draw could be claimed in the current position, if it could.
+    /// Nothing is claimable once the game is over, including when the draw
+    /// has already been applied automatically.
+    pub fn can_claim_draw(&self) -> Option<DrawClaimReason> {
+        if self.is_game_over() {
+            return None;
+        }
+
+        let repetitions = self.position_counts.get(&self.to_position_key()).copied();
+        if self.halfmove_clock >= FIFTY_MOVE_RULE_PLIES {
+            Some(DrawClaimReason::FiftyMoveRule)
+        } else if repetitions.unwrap_or(0) >= CLAIMABLE_REPETITIONS {
+            Some(DrawClaimReason::ThreefoldRepetition)
+        } else {
+            None
+        }
+    }
+
+    /// Ends the game as a draw under the fifty-move rule or threefold
+    /// repetition. Only the side to move may claim.
+    pub fn claim_draw(&mut self, color: Color) -> Result<(), ChessError> {
+        if self.is_game_over() {
+            return Err(ChessError::GameOver);
+        }
+        if color != self.current_player {
+            return Err(ChessError::NotYourTurn);
+        }
+        if self.can_claim_draw().is_none() {
+            return Err(ChessError::NoDrawClaim);
+        }
 
+        self.status = GameStatus::Draw;
+        self.pending_draw_offer = None;
         Ok(())
     }
 
-    fn validate_move(&self, chess_move: &Move) -> Result<(), ChessError> {
+    /// Ends a game nobody has played in for too long as a draw
+    pub fn abandon(&mut self) {
+        if self.is_game_over() {
+            return;
+        }
+
+        self.status = GameStatus::Draw;
+        self.pending_draw_offer = None;
+    }
+
+    /// Counts the leaf nodes of the legal move tree to the given depth
+    pub fn perft(&mut self, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        // Leaves only need counting
+        if depth == 1 {
+            return self.count_legal_moves() as u64;
+        }
+
+        let mut nodes = 0;
+        for chess_move in self.get_legal_moves() {
+            let snapshot = self.clone();
+            self.apply_move(chess_move);
+            nodes += self.perft(depth - 1);
+            *self = snapshot;
+        }
+
+        nodes
+    }
+
+    /// Flags a king move entered by its squares as castling, either the usual
+    /// two-square king move or, as Chess960 clients send it, the king moving
+    /// onto its own castling rook. Any other move is returned unchanged.
+    pub fn infer_castling(&self, chess_move: Move) -> Move {
+        let from = chess_move.from;
+        let to = chess_move.to;
+        let is_own_king = self.board.get_piece(from) == Some(Piece::new(PieceType::King, self.current_player));
+        if !is_own_king || chess_move.is_castling || to.rank != from.rank {
+            return chess_move;
+        }
+
+        for kingside in [true, false] {
+            let (rook_from, _) = self.castling_rook_squares(self.current_player, kingside);
+            if self.castling_rights.can_castle(self.current_player, kingside) && to == rook_from {
+                let king_to = if kingside { 6 } else { 2 };
+                return Move::castling(from, Square::new(king_to, from.rank).unwrap());
+            }
+        }
+
+        if (to.file as i8 - from.file as i8).abs() == 2 && (to.file == 6 || to.file == 2) {
+            return Move::castling(from, to);
+        }
+        chess_move
+    }
+
+    /// Flags a pawn move entered by its squares as en passant when it steps
+    /// diagonally onto the en passant target. Any other move is returned
+    /// unchanged.
+    pub fn infer_en_passant(&self, chess_move: Move) -> Move {
+        let from = chess_move.from;
+        let to = chess_move.to;
+        let is_own_pawn = self.board.get_piece(from) == Some(Piece::new(PieceType::Pawn, self.current_player));
+        if !is_own_pawn || chess_move.is_en_passant || self.en_passant_target != Some(to) {
+            return chess_move;
+        }
+
+        if (to.file as i8 - from.file as i8).abs() == 1 {
+            return Move::en_passant(from, to);
+        }
+        chess_move
+    }
+
+    /// Checks that a move is legal in the current position without playing it
+    pub fn validate_move(&self, chess_move: &Move) -> Result<(), ChessError> {
         // Check if piece exists at source
         let piece = self.board.get_piece(chess_move.from)
             .ok_or_else(|| ChessError::InvalidMove("No piece at source square".to_string()))?;
@@ -73,6 +830,17 @@ impl GameState {
             return Err(ChessError::NotYourTurn);
         }
 
+        // Castling reports why it isn't allowed, and already covers check
+        if chess_move.is_castling {
+            if !self.has_royal_king() {
+                return Err(ChessError::InvalidMove("Castling is not allowed in this variant".to_string()));
+            }
+            if piece.piece_type != PieceType::King {
+                return Err(ChessError::InvalidMove("Only the king can castle".to_string()));
+            }
+            return self.check_castling(chess_move, piece.color);
+        }
+
         // Check if the move is legal for this piece type
         if !self.is_legal_move(chess_move, piece) {
             return Err(ChessError::InvalidMove("Illegal move for this piece".to_string()));
@@ -83,6 +851,10 @@ impl GameState {
             return Err(ChessError::KingInCheck);
         }
 
+        if !chess_move.is_capture(&self.board) && self.capture_required() {
+            return Err(ChessError::CaptureRequired);
+        }
+
         Ok(())
     }
 
@@ -90,6 +862,12 @@ impl GameState {
         let from = chess_move.from;
         let to = chess_move.to;
 
+        // In Chess960 the king may land on its own rook's square, or stay put
+        if chess_move.is_castling {
+            return piece.piece_type == PieceType::King
+                && self.check_castling(chess_move, piece.color).is_ok();
+        }
+
         // Check if destination has same color piece
         if let Some(dest_piece) = self.board.get_piece(to) {
             if dest_piece.color == piece.color {
@@ -103,13 +881,7 @@ impl GameState {
             PieceType::Knight => self.is_legal_knight_move(from, to),
             PieceType::Bishop => self.is_legal_bishop_move(from, to),
             PieceType::Queen => self.is_legal_queen_move(from, to),
-            PieceType::King => {
-                if chess_move.is_castling {
-                    self.is_legal_castling(chess_move, piece.color)
-                } else {
-                    self.is_legal_king_move(from, to)
-                }
-            }
+            PieceType::King => self.is_legal_king_move(from, to),
         }
     }
 
@@ -129,7 +901,7 @@ impl GameState {
         if file_diff == 0 {
             if rank_diff == direction {
                 // One square forward
-                self.board.get_piece(to).is_none()
+                self.board.is_empty_square(to)
             } else if rank_diff == 2 * direction {
                 // Two squares forward from starting position
                 let starting_rank = match color {
@@ -137,7 +909,7 @@ impl GameState {
                     Color::Black => 6,
                 };
                 from.rank == starting_rank 
-                    && self.board.get_piece(to).is_none()
+                    && self.board.is_empty_square(to)
                     && self.board.is_path_clear(from, to)
             } else {
                 false
@@ -188,65 +960,80 @@ impl GameState {
         file_diff <= 1 && rank_diff <= 1 && (file_diff > 0 || rank_diff > 0)
     }
 
-    fn is_legal_castling(&self, chess_move: &Move, color: Color) -> bool {
+    /// Whether the king on `chess_move.from` may castle to `chess_move.to`,
+    /// and if not, why not. The king ends on the g- or c-file and the rook
+    /// next to it on the f- or d-file, wherever they started. Every square
+    /// either piece crosses must be empty apart from the two of them, and the
+    /// king may not start on, pass through or land on an attacked square.
+    fn check_castling(&self, chess_move: &Move, color: Color) -> Result<(), ChessError> {
         let from = chess_move.from;
         let to = chess_move.to;
 
-        // Check if king is on starting square
-        let king_start_square = match color {
-            Color::White => Square::new(4, 0).unwrap(),
-            Color::Black => Square::new(4, 7).unwrap(),
+        let back_rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
         };
-
-        if from != king_start_square {
-            return false;
+        if from.rank != back_rank || to.rank != back_rank || (to.file != 6 && to.file != 2) {
+            return Err(ChessError::InvalidMove("Illegal castling move".to_string()));
         }
 
-        // Determine if kingside or queenside
-        let kingside = to.file > from.file;
-
-        // Check castling rights
+        let kingside = to.file == 6;
         if !self.castling_rights.can_castle(color, kingside) {
-            return false;
+            return Err(ChessError::CastlingRightsRevoked);
         }
 
-        // Check if king is in check
-        if self.is_in_check(color) {
-            return false;
+        // The rook must still be on its starting file, on the correct side of the king
+        let (rook_from, rook_to) = self.castling_rook_squares(color, kingside);
+        if self.board.get_piece(rook_from) != Some(Piece::new(PieceType::Rook, color))
+            || (rook_from.file > from.file) != kingside
+        {
+            return Err(ChessError::CastlingRightsRevoked);
         }
 
-        // Check if path is clear and king doesn't move through check
-        let squares_to_check = if kingside {
-            vec![
-                Square::new(5, from.rank).unwrap(),
-                Square::new(6, from.rank).unwrap(),
-            ]
-        } else {
-            vec![
-                Square::new(3, from.rank).unwrap(),
-                Square::new(2, from.rank).unwrap(),
-                Square::new(1, from.rank).unwrap(),
-            ]
-        };
+        let span = |a: u8, b: u8| a.min(b)..=a.max(b);
+        let mut board = self.board.clone();
+        board.remove_piece(from);
+        board.remove_piece(rook_from);
 
-        for square in squares_to_check {
-            if square != to && self.board.get_piece(square).is_some() {
-                return false; // Path not clear
-            }
-            if square.file <= 6 && square.file >= 2 {
-                // Check if king would be in check on this square
-                let mut temp_board = self.board.clone();
-                temp_board.move_piece(from, square);
-                if temp_board.is_square_attacked(square, color.opposite()) {
-                    return false;
-                }
+        let crossed = span(from.file, to.file).chain(span(rook_from.file, rook_to.file));
+        for file in crossed {
+            if board.get_piece(Square::new(file, back_rank).unwrap()).is_some() {
+                return Err(ChessError::CastlingPathBlocked);
             }
         }
 
-        true
+        let attacked = |file: u8| {
+            board.is_square_attacked(Square::new(file, back_rank).unwrap(), color.opposite())
+        };
+        if attacked(from.file) {
+            return Err(ChessError::KingInCheck);
+        }
+        if span(from.file, to.file).any(attacked) {
+            return Err(ChessError::CastlingThroughCheck);
+        }
+
+        Ok(())
+    }
+
+    /// Starting and destination squares of the rook in a castling move
+    fn castling_rook_squares(&self, color: Color, kingside: bool) -> (Square, Square) {
+        let rank = match color {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        let rook_to = if kingside { 5 } else { 3 };
+        (
+            Square::new(self.castling_rights.rook_file(color, kingside), rank).unwrap(),
+            Square::new(rook_to, rank).unwrap(),
+        )
     }
 
     fn would_leave_king_in_check(&self, chess_move: &Move) -> bool {
+        // Castling already checks every square the king crosses
+        if chess_move.is_castling || !self.has_royal_king() {
+            return false;
+        }
+
         // Make a temporary copy of the board
         let mut temp_board = self.board.clone();
         
@@ -278,23 +1065,15 @@ impl GameState {
         let piece = self.board.get_piece(chess_move.from).unwrap();
 
         if chess_move.is_castling {
-            // Move king
-            self.board.move_piece(chess_move.from, chess_move.to);
-            
-            // Move rook
-            let (rook_from, rook_to) = if chess_move.to.file > chess_move.from.file {
-                // Kingside castling
-                (Square::new(7, chess_move.from.rank).unwrap(), 
-                 Square::new(5, chess_move.from.rank).unwrap())
-            } else {
-                // Queenside castling
-                (Square::new(0, chess_move.from.rank).unwrap(), 
-                 Square::new(3, chess_move.from.rank).unwrap())
-            };
-            self.board.move_piece(rook_from, rook_to);
+            // Lift both pieces first since in Chess960 either may land where the other stood
+            let (rook_from, rook_to) = self.castling_rook_squares(piece.color, chess_move.to.file == 6);
+            let rook = self.board.remove_piece(rook_from).unwrap();
+            self.board.remove_piece(chess_move.from);
+            self.board.set_piece(chess_move.to, piece);
+            self.board.set_piece(rook_to, rook);
         } else {
             // Regular move
-            self.board.move_piece(chess_move.from, chess_move.to);
+            let mut captured = self.board.move_piece(chess_move.from, chess_move.to);
             
             // Handle en passant capture
             if chess_move.is_en_passant {
@@ -302,7 +1081,11 @@ impl GameState {
                     chess_move.to.file,
                     chess_move.from.rank,
                 ).unwrap();
-                self.board.remove_piece(capture_square);
+                captured = self.board.remove_piece(capture_square);
+            }
+
+            if let Some(captured) = captured {
+                self.captured_pieces.push((captured, self.fullmove_number));
             }
             
             // Handle pawn promotion
@@ -310,6 +1093,8 @@ impl GameState {
                 self.board.set_piece(chess_move.to, Piece::new(promotion, piece.color));
             }
         }
+
+        self.history.push(chess_move);
     }
 
     fn update_castling_rights(&mut self, chess_move: &Move) {
@@ -321,19 +1106,33 @@ impl GameState {
             }
             PieceType::Rook => {
                 // Check if rook moved from starting position
-                let (queenside_file, kingside_file, rank) = match piece.color {
-                    Color::White => (0, 7, 0),
-                    Color::Black => (0, 7, 7),
+                let rank = match piece.color {
+                    Color::White => 0,
+                    Color::Black => 7,
                 };
                 
-                if chess_move.from == Square::new(queenside_file, rank).unwrap() {
-                    self.castling_rights.remove_rights(piece.color, Some(false));
-                } else if chess_move.from == Square::new(kingside_file, rank).unwrap() {
-                    self.castling_rights.remove_rights(piece.color, Some(true));
+                for kingside in [true, false] {
+                    let rook_file = self.castling_rights.rook_file(piece.color, kingside);
+                    if chess_move.from == Square::new(rook_file, rank).unwrap() {
+                        self.castling_rights.remove_rights(piece.color, Some(kingside));
+                    }
                 }
             }
             _ => {}
         }
+
+        // Landing on an enemy rook's starting square captures that rook
+        let enemy = piece.color.opposite();
+        let enemy_rank = match enemy {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+        for kingside in [true, false] {
+            let rook_file = self.castling_rights.rook_file(enemy, kingside);
+            if chess_move.to == Square::new(rook_file, enemy_rank).unwrap() {
+                self.castling_rights.remove_rights(enemy, Some(kingside));
+            }
+        }
     }
 
     fn update_en_passant(&mut self, chess_move: &Move) {
@@ -353,11 +1152,11 @@ impl GameState {
         }
     }
 
-    fn update_clocks(&mut self, chess_move: &Move) {
+    fn update_clocks(&mut self, chess_move: &Move, was_capture: bool) {
         let piece = self.board.get_piece(chess_move.to).unwrap();
         
         // Reset halfmove clock on pawn move or capture
-        if piece.piece_type == PieceType::Pawn || chess_move.is_en_passant {
+        if piece.piece_type == PieceType::Pawn || was_capture {
             self.halfmove_clock = 0;
         } else {
             self.halfmove_clock += 1;
@@ -375,7 +1174,10 @@ impl GameState {
         let in_check = self.is_in_check(self.current_player);
         let has_legal_moves = self.has_legal_moves();
 
-        self.status = if !has_legal_moves {
+        self.status = if !has_legal_moves && self.variant == Variant::Antichess {
+            // Having nothing left to move wins Antichess
+            GameStatus::VariantWin(self.current_player)
+        } else if !has_legal_moves {
             if in_check {
                 GameStatus::Checkmate(self.current_player.opposite())
             } else {
@@ -387,89 +1189,314 @@ impl GameState {
             GameStatus::InProgress
         };
 
-        // Check for draw conditions
-        if self.halfmove_clock >= 50 {
+        // Check for draw conditions. The fifty-move rule and threefold
+        // repetition only draw when claimed, see `claim_draw`.
+        if self.halfmove_clock >= SEVENTY_FIVE_MOVE_RULE_PLIES && self.status.winner().is_none() {
+            self.status = GameStatus::Draw;
+        }
+
+        // Kings can be captured in Antichess, so any material can still win
+        if self.has_royal_king() && !self.has_sufficient_material() && !self.status.is_checkmate() {
+            self.status = GameStatus::Draw;
+        }
+
+        // Fivefold repetition
+        let repetitions = self.position_counts.get(&self.to_position_key()).copied();
+        if repetitions.unwrap_or(0) >= AUTOMATIC_REPETITIONS && self.status.winner().is_none() {
             self.status = GameStatus::Draw;
         }
     }
 
-    pub fn is_in_check(&self, color: Color) -> bool {
-        if let Some(king_square) = self.board.find_king(color) {
-            self.board.is_square_attacked(king_square, color.opposite())
-        } else {
-            false
+    /// Returns false when neither side can possibly deliver checkmate:
+    /// K vs K, K+B vs K, K+N vs K, or K+B vs K+B with same-colored bishops
+    pub fn has_sufficient_material(&self) -> bool {
+        // Every dead position below is two kings and at most two minor pieces
+        if self.board.piece_count() > 4 {
+            return true;
+        }
+
+        let counts = self.board.count_pieces_by_type();
+        let count = |color: Color, piece_type: PieceType| counts[&(color, piece_type)];
+
+        for color in [Color::White, Color::Black] {
+            if count(color, PieceType::Pawn) > 0
+                || count(color, PieceType::Rook) > 0
+                || count(color, PieceType::Queen) > 0
+            {
+                return true;
+            }
+        }
+
+        let white_minors = count(Color::White, PieceType::Knight) + count(Color::White, PieceType::Bishop);
+        let black_minors = count(Color::Black, PieceType::Knight) + count(Color::Black, PieceType::Bishop);
+
+        match (white_minors, black_minors) {
+            (0, 0) | (1, 0) | (0, 1) => false,
+            (1, 1) => {
+                // Only same-colored bishops are a dead draw
+                let bishop_square_colors: Vec<u8> = [Color::White, Color::Black]
+                    .iter()
+                    .flat_map(|&color| self.board.get_pieces(color))
+                    .filter(|(_, piece)| piece.piece_type == PieceType::Bishop)
+                    .map(|(square, _)| (square.file + square.rank) % 2)
+                    .collect();
+                !(bishop_square_colors.len() == 2 && bishop_square_colors[0] == bishop_square_colors[1])
+            }
+            _ => true,
         }
     }
 
+    /// Pieces of `color` that have been captured, in the order they were taken
+    pub fn get_captured_pieces(&self, color: Color) -> Vec<Piece> {
+        self.captured_pieces
+            .iter()
+            .filter(|(piece, _)| piece.color == color)
+            .map(|&(piece, _)| piece)
+            .collect()
+    }
+
+    pub fn is_in_check(&self, color: Color) -> bool {
+        self.has_royal_king() && !self.board.is_king_safe(color)
+    }
+
+    /// Stops at the first piece that has a legal move
     fn has_legal_moves(&self) -> bool {
-        let pieces = self.board.get_pieces(self.current_player);
-        
-        for (from, piece) in pieces {
-            for rank in 0..8 {
-                for file in 0..8 {
-                    let to = Square::new(file, rank).unwrap();
-                    let chess_move = Move::new(from, to);
-                    
-                    if self.is_legal_move(&chess_move, piece) && !self.would_leave_king_in_check(&chess_move) {
-                        return true;
-                    }
-                }
+        let mut moves = Vec::new();
+        for (from, piece) in self.board.get_pieces(self.current_player) {
+            self.add_legal_moves_from(from, piece, &mut moves);
+            if !moves.is_empty() {
+                return true;
             }
         }
-        
         false
     }
 
+    /// Number of legal moves for the side to move
+    pub fn count_legal_moves(&self) -> usize {
+        self.get_legal_moves().len()
+    }
+
+    /// Sum of the legal move counts of every piece of `color`, as if it
+    /// were that side's turn
+    pub fn mobility_score(&self, color: Color) -> i32 {
+        let flipped;
+        let position = if color == self.current_player {
+            self
+        } else {
+            let mut state = self.clone();
+            state.current_player = color;
+            state.en_passant_target = None;
+            flipped = state;
+            &flipped
+        };
+
+        position
+            .board
+            .get_pieces(color)
+            .iter()
+            .map(|&(square, _)| position.get_legal_moves_from(square).len() as i32)
+            .sum()
+    }
+
     pub fn get_legal_moves(&self) -> Vec<Move> {
         let mut moves = Vec::new();
-        let pieces = self.board.get_pieces(self.current_player);
-        
-        for (from, piece) in pieces {
-            for rank in 0..8 {
-                for file in 0..8 {
-                    let to = Square::new(file, rank).unwrap();
-                    let mut chess_move = Move::new(from, to);
-                    
-                    // Check for castling
-                    if piece.piece_type == PieceType::King {
-                        let file_diff = to.file as i8 - from.file as i8;
-                        if file_diff.abs() == 2 {
-                            chess_move.is_castling = true;
-                        }
-                    }
-                    
-                    // Check for en passant
-                    if piece.piece_type == PieceType::Pawn && Some(to) == self.en_passant_target {
-                        chess_move.is_en_passant = true;
+        for (from, piece) in self.board.get_pieces(self.current_player) {
+            self.add_legal_moves_from(from, piece, &mut moves);
+        }
+        self.retain_required_captures(&mut moves);
+        moves
+    }
+
+    /// Legal moves for the side to move's pieces of the given type
+    pub fn get_legal_moves_for(&self, piece_type: PieceType) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for (from, piece) in self.board.get_pieces(self.current_player) {
+            if piece.piece_type == piece_type {
+                self.add_legal_moves_from(from, piece, &mut moves);
+            }
+        }
+        self.retain_required_captures(&mut moves);
+        moves
+    }
+
+    /// Legal moves for the piece on `square`, empty unless it belongs to the side to move
+    pub fn get_legal_moves_from(&self, square: Square) -> Vec<Move> {
+        let mut moves = Vec::new();
+        if let Some(piece) = self.board.get_piece(square) {
+            if piece.color == self.current_player {
+                self.add_legal_moves_from(square, piece, &mut moves);
+            }
+        }
+        self.retain_required_captures(&mut moves);
+        moves
+    }
+
+    /// Generates candidate destinations from the bitboards in bulk, then
+    /// keeps the ones that don't leave the king in check
+    #[cfg(feature = "bitboards")]
+    pub(super) fn add_legal_moves_from(&self, from: Square, piece: Piece, moves: &mut Vec<Move>) {
+        let bitboards = self.board.bitboards();
+        let own = bitboards.occupied_by(piece.color);
+        let enemy = bitboards.occupied_by(piece.color.opposite());
+
+        let targets = match piece.piece_type {
+            PieceType::Pawn => self.pawn_targets(from, piece.color, enemy),
+            PieceType::King => {
+                self.add_castling_moves(from, piece.color, moves);
+                bitboards.attacks_from(from, PieceType::King, piece.color) & !own
+            }
+            piece_type => bitboards.attacks_from(from, piece_type, piece.color) & !own,
+        };
+
+        for to in bitboard::squares(targets) {
+            let mut chess_move = Move::new(from, to);
+            if piece.piece_type == PieceType::Pawn && Some(to) == self.en_passant_target {
+                chess_move.is_en_passant = true;
+            }
+
+            if self.would_leave_king_in_check(&chess_move) {
+                continue;
+            }
+
+            let promotion_rank = match piece.color {
+                Color::White => 7,
+                Color::Black => 0,
+            };
+            if piece.piece_type == PieceType::Pawn && to.rank == promotion_rank {
+                for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                    moves.push(chess_move.clone().with_promotion(promotion));
+                }
+            } else {
+                moves.push(chess_move);
+            }
+        }
+    }
+
+    /// Castling to either side, if legal for the king on `from`
+    fn add_castling_moves(&self, from: Square, color: Color, moves: &mut Vec<Move>) {
+        for file in [6, 2] {
+            let chess_move = Move::castling(from, Square::new(file, from.rank).unwrap());
+            if self.check_castling(&chess_move, color).is_ok() {
+                moves.push(chess_move);
+            }
+        }
+    }
+
+    /// Pushes, double pushes, captures and en passant captures for a pawn
+    #[cfg(feature = "bitboards")]
+    fn pawn_targets(&self, from: Square, color: Color, enemy: u64) -> u64 {
+        let empty = !self.board.bitboards().occupied();
+        let (direction, starting_rank): (i8, u8) = match color {
+            Color::White => (1, 1),
+            Color::Black => (-1, 6),
+        };
+
+        let mut targets = 0;
+        if let Some(one) = Square::new(from.file, (from.rank as i8 + direction) as u8) {
+            if empty & bitboard::square_bit(one) != 0 {
+                targets |= bitboard::square_bit(one);
+                if from.rank == starting_rank {
+                    let two = Square::new(from.file, (from.rank as i8 + 2 * direction) as u8).unwrap();
+                    if empty & bitboard::square_bit(two) != 0 {
+                        targets |= bitboard::square_bit(two);
                     }
+                }
+            }
+        }
+
+        let mut capturable = enemy;
+        if let Some(target) = self.en_passant_target {
+            capturable |= bitboard::square_bit(target);
+        }
+        targets | (bitboard::pawn_attacks(from, color) & capturable)
+    }
+
+    #[cfg(not(feature = "bitboards"))]
+    pub(super) fn add_legal_moves_from(&self, from: Square, piece: Piece, moves: &mut Vec<Move>) {
+        if piece.piece_type == PieceType::King {
+            self.add_castling_moves(from, piece.color, moves);
+        }
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                let to = Square::new(file, rank).unwrap();
+                let mut chess_move = Move::new(from, to);
+            
+                // Check for en passant
+                if piece.piece_type == PieceType::Pawn && Some(to) == self.en_passant_target {
+                    chess_move.is_en_passant = true;
+                }
+            
+                if self.is_legal_move(&chess_move, piece) && !self.would_leave_king_in_check(&chess_move) {
+                    // Check for pawn promotion
+                    if piece.piece_type == PieceType::Pawn {
+                        let promotion_rank = match piece.color {
+                            Color::White => 7,
+                            Color::Black => 0,
+                        };
                     
-                    if self.is_legal_move(&chess_move, piece) && !self.would_leave_king_in_check(&chess_move) {
-                        // Check for pawn promotion
-                        if piece.piece_type == PieceType::Pawn {
-                            let promotion_rank = match piece.color {
-                                Color::White => 7,
-                                Color::Black => 0,
-                            };
-                            
-                            if to.rank == promotion_rank {
-                                // Add all possible promotions
-                                for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
-                                    let mut promo_move = chess_move.clone();
-                                    promo_move.promotion = Some(promotion);
-                                    moves.push(promo_move);
-                                }
-                            } else {
-                                moves.push(chess_move);
+                        if to.rank == promotion_rank {
+                            // Add all possible promotions
+                            for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+                                let mut promo_move = chess_move.clone();
+                                promo_move.promotion = Some(promotion);
+                                moves.push(promo_move);
                             }
                         } else {
                             moves.push(chess_move);
                         }
+                    } else {
+                        moves.push(chess_move);
                     }
                 }
             }
         }
-        
-        moves
+    }
+
+    /// `Board::to_ascii` with the en passant square and side to move marked
+    pub fn to_ascii(&self) -> String {
+        self.annotate_diagram(&self.board.to_ascii())
+    }
+
+    /// `Board::to_unicode` with the en passant square and side to move marked
+    pub fn to_unicode(&self) -> String {
+        self.annotate_diagram(&self.board.to_unicode())
+    }
+
+    /// Marks the en passant square of a board diagram with `*` and the
+    /// back rank of the side to move with `>`
+    fn annotate_diagram(&self, diagram: &str) -> String {
+        let marked_rank = match self.current_player {
+            Color::White => 0,
+            Color::Black => 7,
+        };
+
+        let mut annotated = String::new();
+        for (index, line) in diagram.lines().enumerate() {
+            // Ranks run from 8 down to 1, then the file letters
+            let rank = 7u8.checked_sub(index as u8);
+            let mut symbols: Vec<char> = line.chars().collect();
+            if let (Some(rank), Some(target)) = (rank, self.en_passant_target) {
+                if target.rank == rank {
+                    symbols[2 + 2 * target.file as usize] = '*';
+                }
+            }
+            annotated.push_str(if rank == Some(marked_rank) { "> " } else { "  " });
+            annotated.extend(symbols);
+            annotated.push('\n');
+        }
+        annotated
+    }
+
+    /// The first four FEN fields: placement, side to move, castling
+    /// rights and en passant target. Positions with equal keys are
+    /// repetitions of each other.
+    pub fn to_position_key(&self) -> String {
+        self.to_fen()
+            .split(' ')
+            .take(4)
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 
     pub fn to_fen(&self) -> String {
@@ -485,19 +1512,7 @@ impl GameState {
                         fen.push_str(&empty_count.to_string());
                         empty_count = 0;
                     }
-                    let piece_char = match piece.piece_type {
-                        PieceType::Pawn => 'p',
-                        PieceType::Rook => 'r',
-                        PieceType::Knight => 'n',
-                        PieceType::Bishop => 'b',
-                        PieceType::Queen => 'q',
-                        PieceType::King => 'k',
-                    };
-                    let piece_char = match piece.color {
-                        Color::White => piece_char.to_ascii_uppercase(),
-                        Color::Black => piece_char,
-                    };
-                    fen.push(piece_char);
+                    fen.push(piece.to_fen_char());
                 } else {
                     empty_count += 1;
                 }
@@ -520,17 +1535,28 @@ impl GameState {
         // Castling rights
         fen.push(' ');
         let mut castling = String::new();
-        if self.castling_rights.white_kingside { castling.push('K'); }
-        if self.castling_rights.white_queenside { castling.push('Q'); }
-        if self.castling_rights.black_kingside { castling.push('k'); }
-        if self.castling_rights.black_queenside { castling.push('q'); }
+        for (color, kingside) in CASTLING_SIDES {
+            if !self.castling_rights.can_castle(color, kingside) {
+                continue;
+            }
+            // Chess960 rooks off the h- and a-files are named by their file
+            let symbol = match (kingside, self.castling_rights.rook_file(color, kingside)) {
+                (true, 7) => 'k',
+                (false, 0) => 'q',
+                (_, file) => (b'a' + file) as char,
+            };
+            castling.push(match color {
+                Color::White => symbol.to_ascii_uppercase(),
+                Color::Black => symbol,
+            });
+        }
         if castling.is_empty() { castling.push('-'); }
         fen.push_str(&castling);
         
         // En passant target
         fen.push(' ');
         if let Some(target) = self.en_passant_target {
-            fen.push_str(&target.to_algebraic());
+            fen.push_str(&notation::square_to_algebraic(target));
         } else {
             fen.push('-');
         }
@@ -540,10 +1566,472 @@ impl GameState {
         
         fen
     }
+
+    /// Takes back the last move by replaying the rest of the history from
+    /// the initial position. Players, time control and clocks are kept;
+    /// any pending draw offer is withdrawn.
+    pub fn undo_move(&mut self) -> Result<(), ChessError> {
+        let (_, earlier) = self.history.split_last().ok_or(ChessError::NothingToUndo)?;
+
+        let mut replay = self.initial_position()?;
+        replay.apply_moves(earlier).map_err(|(_, e)| e)?;
+
+        self.board = replay.board;
+        self.current_player = replay.current_player;
+        self.castling_rights = replay.castling_rights;
+        self.en_passant_target = replay.en_passant_target;
+        self.halfmove_clock = replay.halfmove_clock;
+        self.fullmove_number = replay.fullmove_number;
+        self.status = replay.status;
+        self.history = replay.history;
+        self.position_counts = replay.position_counts;
+        self.captured_pieces = replay.captured_pieces;
+        self.pending_draw_offer = None;
+
+        Ok(())
+    }
+
+    /// Takes the next event ID for a change about to be published. Moves
+    /// played since the last event are tagged with it; moves taken back
+    /// lose theirs.
+    pub fn advance_event_id(&mut self) {
+        self.event_id += 1;
+        self.move_event_ids.truncate(self.history.len());
+        self.move_event_ids.resize(self.history.len(), self.event_id);
+    }
+
+    /// The position after the first `ply` moves of `history`, or `None`
+    /// if the game hasn't got that far
+    pub fn position_at(&self, ply: usize) -> Option<GameState> {
+        let moves = self.history.get(..ply)?;
+
+        let mut replay = self.initial_position().ok()?;
+        replay.apply_moves(moves).ok()?;
+        Some(replay)
+    }
+
+    /// Plays `moves` in order. If one is rejected, the error comes with
+    /// its index and the position is left after the moves before it.
+    /// Moves given only by their squares, as UCI sends them, are flagged
+    /// as castling or en passant where that is what they are.
+    pub fn apply_moves(&mut self, moves: &[Move]) -> Result<(), (usize, ChessError)> {
+        self.apply_moves_with(moves, |state, chess_move| {
+            let chess_move = state.infer_castling(chess_move.clone());
+            Ok(state.infer_en_passant(chess_move))
+        })
+    }
+
+    /// Like `apply_moves`, for moves that can only be turned into a `Move`
+    /// in the position they are played in, such as SAN
+    pub fn apply_moves_with<T>(
+        &mut self,
+        moves: &[T],
+        mut resolve: impl FnMut(&GameState, &T) -> Result<Move, ChessError>,
+    ) -> Result<(), (usize, ChessError)> {
+        for (index, chess_move) in moves.iter().enumerate() {
+            resolve(self, chess_move)
+                .and_then(|chess_move| self.make_move(chess_move))
+                .map_err(|e| (index, e))?;
+        }
+        Ok(())
+    }
+
+    /// The standard starting position with `moves` played from it
+    pub fn from_moves(moves: &[Move]) -> Result<GameState, (usize, ChessError)> {
+        let mut state = GameState::new();
+        state.apply_moves(moves)?;
+        Ok(state)
+    }
+
+    /// The position this game started from, before any moves in `history`
+    pub fn initial_position(&self) -> Result<GameState, ChessError> {
+        let mut state = match self.starting_fen {
+            Some(ref fen) => GameState::from_fen_with_variant(fen, self.variant)?,
+            None if self.variant == Variant::Antichess => GameState::new_antichess(),
+            None => GameState::new(),
+        };
+        state.variant = self.variant;
+        Ok(state)
+    }
+
+    pub fn to_pgn(&self, metadata: PgnMetadata) -> String {
+        let result = match self.winner() {
+            Some(Color::White) => "1-0".to_string(),
+            Some(Color::Black) => "0-1".to_string(),
+            None if self.is_draw() => "1/2-1/2".to_string(),
+            None => metadata.result.clone(),
+        };
+
+        let mut pgn = String::new();
+
+        // Seven tag roster
+        let tags = [
+            ("Event", &metadata.event),
+            ("Site", &metadata.site),
+            ("Date", &metadata.date),
+            ("Round", &metadata.round),
+            ("White", &metadata.white),
+            ("Black", &metadata.black),
+            ("Result", &result),
+        ];
+        for (name, value) in tags {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escaped));
+        }
+        match self.variant {
+            Variant::Chess960 => pgn.push_str("[Variant \"Chess960\"]\n"),
+            Variant::Antichess => pgn.push_str("[Variant \"Antichess\"]\n"),
+            Variant::Standard => {}
+        }
+        if let Some(ref fen) = self.starting_fen {
+            pgn.push_str("[SetUp \"1\"]\n");
+            pgn.push_str(&format!("[FEN \"{}\"]\n", fen));
+        }
+        pgn.push('\n');
+
+        // Replay the game from its starting position to produce SAN. Without
+        // a usable starting position only the result is written.
+        let mut tokens = Vec::new();
+        if let Ok(mut replay) = self.initial_position() {
+            for (i, chess_move) in self.history.iter().enumerate() {
+                if replay.current_player == Color::White {
+                    tokens.push(format!("{}.", replay.fullmove_number));
+                } else if i == 0 {
+                    tokens.push(format!("{}...", replay.fullmove_number));
+                }
+                tokens.push(notation::move_to_san(chess_move, &replay));
+                if replay.make_move(chess_move.clone()).is_err() {
+                    break;
+                }
+            }
+        }
+        tokens.push(result);
+
+        // Wrap movetext at 80 columns
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 80 {
+                pgn.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                pgn.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            pgn.push_str(&token);
+        }
+        pgn.push('\n');
+
+        pgn
+    }
 }
 
 impl Default for GameState {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The perft positions from the Chess Programming Wiki with their leaf
+    /// node counts at depths 1 through 5
+    const PERFT_POSITIONS: [(&str, [u64; 5]); 5] = [
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            [20, 400, 8_902, 197_281, 4_865_609],
+        ),
+        (
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            [48, 2_039, 97_862, 4_085_603, 193_690_690],
+        ),
+        (
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            [14, 191, 2_812, 43_238, 674_624],
+        ),
+        (
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            [6, 264, 9_467, 422_333, 15_833_292],
+        ),
+        (
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            [44, 1_486, 62_379, 2_103_487, 89_941_194],
+        ),
+    ];
+
+    /// Larger trees take too long to walk in unoptimized test builds
+    const QUICK_PERFT_NODES: u64 = 10_000;
+
+    /// Checks every depth of every position whose node count `include` accepts
+    fn assert_perft(include: impl Fn(u64) -> bool) {
+        for (position, (fen, expected)) in PERFT_POSITIONS.iter().enumerate() {
+            let mut state = GameState::from_fen(fen).unwrap();
+            for (depth, &nodes) in (1..).zip(expected) {
+                if include(nodes) {
+                    assert_eq!(state.perft(depth), nodes, "position {} at depth {}", position + 1, depth);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn perft_quick() {
+        assert_perft(|nodes| nodes <= QUICK_PERFT_NODES);
+    }
+
+    #[test]
+    #[ignore = "slow outside release builds; run with `cargo test --release -- --ignored`"]
+    fn perft_deep() {
+        assert_perft(|nodes| nodes > QUICK_PERFT_NODES);
+    }
+
+    /// Plays moves given in UCI notation, panicking on any that is rejected
+    fn play(state: &mut GameState, moves: &[&str]) {
+        for uci in moves {
+            let chess_move = notation::uci_to_move(uci).unwrap();
+            if let Err(e) = state.make_move(chess_move) {
+                panic!("{} was rejected: {}", uci, e);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_diagram_marks_en_passant_and_side_to_move() {
+        let mut state = GameState::new();
+        play(&mut state, &["e2e4"]);
+        let expected = [
+            "> 8 r n b q k b n r",
+            "  7 p p p p p p p p",
+            "  6 . . . . . . . .",
+            "  5 . . . . . . . .",
+            "  4 . . . . P . . .",
+            "  3 . . . . * . . .",
+            "  2 P P P P . P P P",
+            "  1 R N B Q K B N R",
+            "    a b c d e f g h",
+        ];
+        assert_eq!(state.to_ascii(), expected.join("\n") + "\n");
+        assert!(state.to_unicode().starts_with("> 8 ♜ ♞ ♝ ♛ ♚ ♝ ♞ ♜\n"));
+    }
+
+    #[test]
+    fn antichess_fen_drops_castling_and_allows_a_missing_king() {
+        let fen = "r3k2r/8/8/8/8/8/8/R6R w KQkq - 0 1";
+        assert!(GameState::from_fen(fen).is_err());
+
+        let state = GameState::from_fen_with_variant(fen, Variant::Antichess).unwrap();
+        assert_eq!(state.status, GameStatus::InProgress);
+        for (color, kingside) in CASTLING_SIDES {
+            assert!(!state.castling_rights.can_castle(color, kingside));
+        }
+        assert!(state.to_fen().contains(" w - - "));
+    }
+
+    #[test]
+    fn antichess_rejects_castling_even_with_castling_rights() {
+        let mut state = GameState::from_fen("4k3/8/8/8/8/8/p7/R3K2R w KQ - 0 1").unwrap();
+        let castling = Move::castling(
+            notation::algebraic_to_square("e1").unwrap(),
+            notation::algebraic_to_square("g1").unwrap(),
+        );
+        assert!(state.validate_move(&castling).is_ok());
+
+        // Rxa2 is a compulsory capture, but castling must fail on its own account
+        state.variant = Variant::Antichess;
+        assert!(matches!(state.validate_move(&castling), Err(ChessError::InvalidMove(_))));
+    }
+
+    #[test]
+    fn checkmate_outranks_the_seventy_five_move_rule() {
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 149 80";
+
+        let mut mated = GameState::from_fen(fen).unwrap();
+        play(&mut mated, &["a1a8"]);
+        assert_eq!(mated.halfmove_clock, 150);
+        assert_eq!(mated.status, GameStatus::Checkmate(Color::White));
+
+        let mut drawn = GameState::from_fen(fen).unwrap();
+        play(&mut drawn, &["a1a2"]);
+        assert_eq!(drawn.status, GameStatus::Draw);
+    }
+
+    #[test]
+    fn fifty_moves_can_be_claimed_and_seventy_five_draw_automatically() {
+        let mut claimable = GameState::from_fen("7k/8/8/8/8/8/8/R6K w - - 99 60").unwrap();
+        play(&mut claimable, &["a1a2"]);
+        assert_eq!(claimable.status, GameStatus::InProgress);
+        assert_eq!(claimable.can_claim_draw(), Some(DrawClaimReason::FiftyMoveRule));
+        assert!(matches!(claimable.claim_draw(Color::White), Err(ChessError::NotYourTurn)));
+        claimable.claim_draw(Color::Black).unwrap();
+        assert_eq!(claimable.status, GameStatus::Draw);
+
+        let mut automatic = GameState::from_fen("7k/8/8/8/8/8/8/R6K w - - 149 80").unwrap();
+        play(&mut automatic, &["a1a2"]);
+        assert_eq!(automatic.status, GameStatus::Draw);
+        assert_eq!(automatic.can_claim_draw(), None);
+    }
+
+    #[test]
+    fn threefold_repetition_can_be_claimed_and_fivefold_draws_automatically() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let mut state = GameState::new();
+
+        // The starting position for the third time
+        play(&mut state, &shuffle);
+        play(&mut state, &shuffle);
+        assert_eq!(state.status, GameStatus::InProgress);
+        assert_eq!(state.can_claim_draw(), Some(DrawClaimReason::ThreefoldRepetition));
+
+        let mut claimed = state.clone();
+        claimed.claim_draw(Color::White).unwrap();
+        assert_eq!(claimed.status, GameStatus::Draw);
+
+        // Declining to claim lets play go on until the fifth occurrence
+        play(&mut state, &shuffle);
+        assert_eq!(state.status, GameStatus::InProgress);
+        play(&mut state, &shuffle);
+        assert_eq!(state.status, GameStatus::Draw);
+        assert_eq!(state.can_claim_draw(), None);
+    }
+
+    #[test]
+    fn fen_with_a_non_ascii_en_passant_square_is_rejected() {
+        let fen = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq é 0 1";
+        assert!(matches!(GameState::from_fen(fen), Err(ChessError::InvalidFen(_))));
+        assert_eq!(notation::algebraic_to_square("é"), None);
+        assert_eq!(notation::algebraic_to_square("i1"), None);
+        assert_eq!(notation::algebraic_to_square("h8"), Square::new(7, 7));
+    }
+
+    #[test]
+    fn capturing_a_rook_on_its_starting_square_revokes_castling() {
+        let mut state = GameState::from_fen("r3k2r/8/8/8/8/8/6B1/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut state, &["g2a8"]);
+
+        assert!(!state.castling_rights.can_castle(Color::Black, false));
+        assert!(state.castling_rights.can_castle(Color::Black, true));
+        assert!(state.castling_rights.can_castle(Color::White, true));
+        assert!(state.castling_rights.can_castle(Color::White, false));
+    }
+
+    #[test]
+    fn captures_reset_the_halfmove_clock() {
+        // Each knight walks a cycle on which it never gives check, White's
+        // passing e3, a knight's move from the black pawn on d5. Cycles of
+        // 8 and 10 squares keep any position from occurring three times.
+        let mut state = GameState::from_fen("7k/7p/8/n2p4/8/N7/P7/K7 w - - 0 1").unwrap();
+        let white = ["a3", "c4", "d2", "e4", "f2", "g4", "e3", "c2"];
+        let black = ["a5", "b7", "d8", "e6", "f8", "g6", "e7", "c8", "a7", "c6"];
+        let knight_move = |cycle: &[&str], n: usize| {
+            format!("{}{}", cycle[(n - 1) % cycle.len()], cycle[n % cycle.len()])
+        };
+
+        for n in 1..=30 {
+            play(&mut state, &[&knight_move(&white, n), &knight_move(&black, n)]);
+        }
+        assert_eq!(state.halfmove_clock, 60);
+        assert_eq!(state.can_claim_draw(), None);
+
+        play(&mut state, &["e3d5"]);
+        assert_eq!(state.halfmove_clock, 0);
+
+        play(&mut state, &[&knight_move(&black, 31), "d5e3", &knight_move(&black, 32)]);
+        for n in 31..=54 {
+            play(&mut state, &[&knight_move(&white, n), &knight_move(&black, n + 2)]);
+        }
+
+        // 56 knight moves each, but only 51 halfmoves since the capture
+        assert_eq!(state.halfmove_clock, 51);
+        assert_eq!(state.can_claim_draw(), None);
+        assert_eq!(state.status, GameStatus::InProgress);
+    }
+
+    #[test]
+    fn pgn_export_round_trips_the_opera_game() {
+        let movetext = "1. e4 e5 2. Nf3 d6 3. d4 Bg4 4. dxe5 Bxf3 5. Qxf3 dxe5 6. Bc4 Nf6 \
+                        7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5 10. Nxb5 cxb5 11. Bxb5+ Nbd7 \
+                        12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+ Nxd7 \
+                        16. Qb8+ Nxb8 17. Rd8# 1-0";
+
+        let mut state = GameState::new();
+        let sans = movetext.split_whitespace().filter(|token| !token.ends_with('.') && *token != "1-0");
+        for san in sans {
+            let chess_move = notation::san_to_move(san, &state).unwrap();
+            state.make_move(chess_move).unwrap();
+        }
+        assert_eq!(state.status, GameStatus::Checkmate(Color::White));
+
+        let pgn = state.to_pgn(PgnMetadata {
+            event: "Paris".to_string(),
+            site: "Paris FRA".to_string(),
+            date: "1858.??.??".to_string(),
+            round: "?".to_string(),
+            white: "Paul Morphy".to_string(),
+            black: "Duke Karl / Count Isouard".to_string(),
+            result: "*".to_string(),
+        });
+        assert!(pgn.contains("[Result \"1-0\"]\n"));
+
+        let (_, exported) = pgn.split_once("\n\n").unwrap();
+        assert_eq!(
+            exported.split_whitespace().collect::<Vec<_>>(),
+            movetext.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn from_moves_replays_a_game_and_reports_the_failing_move() {
+        let moves = |ucis: &[&str]| -> Vec<Move> {
+            ucis.iter().map(|uci| notation::uci_to_move(uci).unwrap()).collect()
+        };
+
+        let scholars_mate = moves(&["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]);
+        let state = GameState::from_moves(&scholars_mate).unwrap();
+        assert_eq!(state.status, GameStatus::Checkmate(Color::White));
+        assert_eq!(state.history, scholars_mate);
+
+        match GameState::from_moves(&moves(&["e2e4", "e7e5", "e4e5"])) {
+            Err((index, ChessError::InvalidMove(_))) => assert_eq!(index, 2),
+            other => panic!("expected the third move to be rejected, got {:?}", other.map(|_| ())),
+        }
+
+        // Castling given by its squares, as UCI sends it
+        let italian = moves(&["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "f8c5", "e1g1"]);
+        let castled = GameState::from_moves(&italian).unwrap();
+        assert!(castled.history.last().unwrap().is_castling);
+        let f1 = notation::algebraic_to_square("f1").unwrap();
+        assert_eq!(castled.board.get_piece(f1), Some(Piece::new(PieceType::Rook, Color::White)));
+    }
+
+    #[test]
+    fn undo_reports_an_unreadable_starting_position() {
+        let mut state = GameState::new();
+        play(&mut state, &["e2e4"]);
+        state.starting_fen = Some("not a position".to_string());
+
+        assert!(matches!(state.undo_move(), Err(ChessError::InvalidFen(_))));
+        assert_eq!(state.history.len(), 1);
+    }
+
+    #[test]
+    fn event_ids_keep_growing_across_undo() {
+        let mut state = GameState::new();
+        play(&mut state, &["e2e4"]);
+        state.advance_event_id();
+        play(&mut state, &["e7e5", "g1f3"]);
+        state.advance_event_id();
+        assert_eq!(state.move_event_ids, vec![1, 2, 2]);
+
+        state.undo_move().unwrap();
+        state.advance_event_id();
+        assert_eq!(state.event_id, 3);
+        assert_eq!(state.move_event_ids, vec![1, 2]);
+
+        state.resign(Color::Black).unwrap();
+        state.advance_event_id();
+        assert_eq!(state.event_id, 4);
+        assert_eq!(state.move_event_ids, vec![1, 2]);
+    }
+}
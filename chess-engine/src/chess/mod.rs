@@ -1,8 +1,21 @@
 pub mod types;
+pub mod bitboard;
 pub mod board;
+pub mod clock;
 pub mod game;
+pub mod notation;
+pub mod engine;
+pub mod evaluation;
+pub mod pgn;
+pub mod transposition;
+pub mod variants;
+pub mod zobrist;
 
 // Re-export all types for easier access
-pub use types::{Color, Piece, PieceType, Square, Move, CastlingRights, GameStatus};
+pub use types::{Color, Piece, PieceType, Square, Move, CastlingRights, GameMode, GameStatus, MoveAnnotation, Variant};
 pub use board::Board;
-pub use game::{GameState, ChessError};
\ No newline at end of file
+pub use clock::{Clock, TimeControl};
+pub use game::{GameState, ChessError, PgnMetadata};
+pub use engine::Engine;
+pub use pgn::PgnParser;
+pub use transposition::TranspositionTable;
\ No newline at end of file
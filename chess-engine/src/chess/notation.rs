@@ -0,0 +1,231 @@
+//! Conversions between moves and their string forms: square names,
+//! UCI long algebraic (`e2e4`, `e7e8q`) and Standard Algebraic Notation.
+
+use super::{
+    game::{ChessError, GameState},
+    types::{Move, PieceType, Square},
+};
+
+pub fn square_to_algebraic(square: Square) -> String {
+    format!("{}{}", (b'a' + square.file) as char, (b'1' + square.rank) as char)
+}
+
+pub fn algebraic_to_square(notation: &str) -> Option<Square> {
+    // Bytes rather than chars, so multi-byte input can't pass as two characters
+    let [file, rank] = notation.as_bytes() else {
+        return None;
+    };
+    let file = file.checked_sub(b'a')?;
+    let rank = rank.checked_sub(b'1')?;
+
+    Square::new(file, rank)
+}
+
+pub fn move_to_uci(chess_move: &Move) -> String {
+    let mut uci = format!(
+        "{}{}",
+        square_to_algebraic(chess_move.from),
+        square_to_algebraic(chess_move.to)
+    );
+    if let Some(promotion) = chess_move.promotion {
+        uci.push(match promotion {
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Rook => 'r',
+            _ => 'q',
+        });
+    }
+    uci
+}
+
+/// Parses a move in UCI long algebraic form, e.g. `e2e4` or `e7e8q`.
+/// Castling and en passant flags are left for the caller to infer.
+pub fn uci_to_move(uci: &str) -> Option<Move> {
+    if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+        return None;
+    }
+
+    let from = algebraic_to_square(&uci[0..2])?;
+    let to = algebraic_to_square(&uci[2..4])?;
+    let mut chess_move = Move::new(from, to);
+
+    if let Some(c) = uci[4..].chars().next() {
+        let piece_type = match PieceType::try_from(c) {
+            Ok(PieceType::Pawn | PieceType::King) | Err(_) => return None,
+            Ok(piece_type) => piece_type,
+        };
+        chess_move = chess_move.with_promotion(piece_type);
+    }
+
+    Some(chess_move)
+}
+
+fn san_piece_letter(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::Knight => 'N',
+        PieceType::Bishop => 'B',
+        PieceType::Rook => 'R',
+        PieceType::Queen => 'Q',
+        _ => 'K',
+    }
+}
+
+/// Standard Algebraic Notation for `chess_move` played from `state`,
+/// with `+`/`#` suffixes. Falls back to the bare squares if no piece
+/// stands on the source square.
+pub fn move_to_san(chess_move: &Move, state: &GameState) -> String {
+    let piece = match state.board.get_piece(chess_move.from) {
+        Some(piece) => piece,
+        None => return format!("{}{}", chess_move.from, chess_move.to),
+    };
+
+    let mut san = String::new();
+
+    if chess_move.is_castling {
+        if chess_move.to.file == 6 {
+            san.push_str("O-O");
+        } else {
+            san.push_str("O-O-O");
+        }
+    } else {
+        let is_capture = chess_move.is_capture(&state.board);
+
+        if piece.piece_type == PieceType::Pawn {
+            if is_capture {
+                san.push((b'a' + chess_move.from.file) as char);
+            }
+        } else {
+            san.push(san_piece_letter(piece.piece_type));
+
+            // Disambiguate between pieces of the same type reaching the same square
+            let others: Vec<Square> = state
+                .get_legal_moves_for(piece.piece_type)
+                .into_iter()
+                .filter(|m| m.to == chess_move.to && m.from != chess_move.from)
+                .map(|m| m.from)
+                .collect();
+
+            if !others.is_empty() {
+                if others.iter().all(|sq| sq.file != chess_move.from.file) {
+                    san.push((b'a' + chess_move.from.file) as char);
+                } else if others.iter().all(|sq| sq.rank != chess_move.from.rank) {
+                    san.push((b'1' + chess_move.from.rank) as char);
+                } else {
+                    san.push_str(&square_to_algebraic(chess_move.from));
+                }
+            }
+        }
+
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&square_to_algebraic(chess_move.to));
+
+        if let Some(promotion) = chess_move.promotion {
+            san.push('=');
+            san.push(san_piece_letter(promotion));
+        }
+    }
+
+    // Check and checkmate markers
+    let mut next = state.clone();
+    if next.make_move(chess_move.clone()).is_ok() {
+        if next.status.is_checkmate() {
+            san.push('#');
+        } else if next.is_in_check(next.current_player) {
+            san.push('+');
+        }
+    }
+
+    san
+}
+
+/// Resolves a SAN move against the legal moves of `state`. Check and
+/// annotation suffixes are ignored, as are `0-0` style castles and
+/// promotions written without the `=`.
+pub fn san_to_move(san: &str, state: &GameState) -> Result<Move, ChessError> {
+    let trimmed = san
+        .trim()
+        .trim_end_matches(['+', '#', '!', '?']);
+
+    // Castling
+    let castling_side = match trimmed {
+        "O-O" | "0-0" => Some(true),
+        "O-O-O" | "0-0-0" => Some(false),
+        _ => None,
+    };
+    if let Some(kingside) = castling_side {
+        return state
+            .get_legal_moves_for(PieceType::King)
+            .into_iter()
+            .find(|m| m.is_castling && (m.to.file == 6) == kingside)
+            .ok_or_else(|| ChessError::InvalidMove(format!("Castling not allowed: {}", san)));
+    }
+
+    let mut chars: Vec<char> = trimmed.chars().collect();
+
+    // Leading piece letter (pawn moves have none)
+    let piece_type = match chars.first() {
+        Some('N') => PieceType::Knight,
+        Some('B') => PieceType::Bishop,
+        Some('R') => PieceType::Rook,
+        Some('Q') => PieceType::Queen,
+        Some('K') => PieceType::King,
+        _ => PieceType::Pawn,
+    };
+    if piece_type != PieceType::Pawn {
+        chars.remove(0);
+    }
+
+    // Trailing promotion, written as "=Q" or just "Q"
+    let mut promotion = None;
+    if let Some(&last) = chars.last() {
+        let promo = match last {
+            'N' => Some(PieceType::Knight),
+            'B' => Some(PieceType::Bishop),
+            'R' => Some(PieceType::Rook),
+            'Q' => Some(PieceType::Queen),
+            _ => None,
+        };
+        if promo.is_some() {
+            promotion = promo;
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    if chars.len() < 2 {
+        return Err(ChessError::InvalidMove(format!("Invalid SAN: {}", san)));
+    }
+    let dest: String = chars[chars.len() - 2..].iter().collect();
+    let to = algebraic_to_square(&dest)
+        .ok_or_else(|| ChessError::InvalidMove(format!("Invalid SAN: {}", san)))?;
+
+    // Whatever remains is disambiguation and an optional capture marker
+    let mut from_file = None;
+    let mut from_rank = None;
+    for &c in &chars[..chars.len() - 2] {
+        match c {
+            'a'..='h' => from_file = Some(c as u8 - b'a'),
+            '1'..='8' => from_rank = Some(c as u8 - b'1'),
+            'x' | ':' | '-' => {}
+            _ => return Err(ChessError::InvalidMove(format!("Invalid SAN: {}", san))),
+        }
+    }
+
+    let candidates: Vec<Move> = state
+        .get_legal_moves_for(piece_type)
+        .into_iter()
+        .filter(|m| m.to == to && !m.is_castling && m.promotion == promotion)
+        .filter(|m| from_file.is_none_or(|f| m.from.file == f))
+        .filter(|m| from_rank.is_none_or(|r| m.from.rank == r))
+        .collect();
+
+    match candidates.len() {
+        0 => Err(ChessError::InvalidMove(format!("No legal move matches {}", san))),
+        1 => Ok(candidates.into_iter().next().unwrap()),
+        _ => Err(ChessError::InvalidMove(format!("Ambiguous move: {}", san))),
+    }
+}
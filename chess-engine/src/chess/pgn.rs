@@ -0,0 +1,394 @@
+use super::{game::ChessError, game::GameState, notation, types::Color, types::Variant};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PgnError {
+    #[error("Unterminated comment")]
+    UnterminatedComment,
+    #[error("Unterminated tag pair")]
+    UnterminatedTag,
+    #[error("Malformed tag pair: {0}")]
+    MalformedTag(String),
+    #[error("Unbalanced parentheses in variation")]
+    UnbalancedVariation,
+    #[error("Unexpected token: {0}")]
+    UnexpectedToken(String),
+    #[error("No game found")]
+    Empty,
+    #[error("Expected one game, found {count}{}", unreadable_games(.unreadable))]
+    MultipleGames { count: usize, unreadable: Vec<usize> },
+}
+
+fn unreadable_games(unreadable: &[usize]) -> String {
+    if unreadable.is_empty() {
+        return String::new();
+    }
+    let numbers: Vec<String> = unreadable.iter().map(|number| number.to_string()).collect();
+    format!(" (unreadable: game {})", numbers.join(", "))
+}
+
+/// A game as written in PGN: its tag pairs and the SAN of each move
+/// on the main line
+#[derive(Debug, Clone, Default)]
+pub struct PgnGame {
+    pub tags: HashMap<String, String>,
+    pub moves: Vec<String>,
+    pub result: Option<String>,
+}
+
+impl PgnGame {
+    /// Replays the moves from the initial position, or from the `FEN`
+    /// tag if present. An illegal move is reported with its move number.
+    pub fn to_game_state(&self) -> Result<GameState, ChessError> {
+        let variant = match self.tags.get("Variant") {
+            Some(variant) if variant.eq_ignore_ascii_case("chess960") => Variant::Chess960,
+            Some(variant) if variant.eq_ignore_ascii_case("antichess") => Variant::Antichess,
+            _ => Variant::Standard,
+        };
+        let mut state = match self.tags.get("FEN") {
+            Some(fen) => GameState::from_fen_with_variant(fen, variant)?,
+            None if variant == Variant::Antichess => GameState::new_antichess(),
+            None => GameState::new(),
+        };
+        state.variant = variant;
+
+        state
+            .apply_moves_with(&self.moves, |state, san| notation::san_to_move(san, state))
+            .map_err(|(index, e)| rejected_move(&state, &self.moves[index], e))?;
+
+        Ok(state)
+    }
+}
+
+/// Names a move that could not be played by its number and SAN, e.g.
+/// `12... Nf3 (Illegal move for this piece)`. `state` is the position
+/// the move was tried in.
+fn rejected_move(state: &GameState, san: &str, error: ChessError) -> ChessError {
+    let dots = match state.current_player {
+        Color::White => ".",
+        Color::Black => "...",
+    };
+    let reason = match error {
+        ChessError::InvalidMove(reason) => reason,
+        e => e.to_string(),
+    };
+    ChessError::InvalidMove(format!("{}{} {} ({})", state.fullmove_number, dots, san, reason))
+}
+
+const RESULT_TOKENS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Character cursor over the PGN text
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Lexer {
+    fn new(input: &str) -> Self {
+        Self {
+            chars: input.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn at_line_start(&self) -> bool {
+        self.pos == 0 || self.chars[self.pos - 1] == '\n'
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.next_char() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// Everything up to the next whitespace or delimiter
+    fn symbol(&mut self) -> String {
+        let mut symbol = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || "{}()[];".contains(c) {
+                break;
+            }
+            symbol.push(c);
+            self.pos += 1;
+        }
+        symbol
+    }
+
+    /// After an error, moves on to the next tag pair that follows a blank
+    /// line, where the next game starts, so the rest of the file can still be read
+    fn skip_to_next_game(&mut self) {
+        let mut blank_line_seen = false;
+        while let Some(c) = self.peek() {
+            if self.at_line_start() {
+                if c == '[' && blank_line_seen {
+                    return;
+                }
+                let line_is_blank = self.chars[self.pos..]
+                    .iter()
+                    .take_while(|&&c| c != '\n')
+                    .all(|c| c.is_whitespace());
+                blank_line_seen |= line_is_blank;
+            }
+            self.pos += 1;
+        }
+    }
+}
+
+pub struct PgnParser;
+
+impl PgnParser {
+    /// Parses every game in a PGN file. A malformed game produces an error
+    /// in its place and parsing resumes at the next game's tag pairs.
+    pub fn parse_file(input: &str) -> Vec<Result<PgnGame, PgnError>> {
+        let mut lexer = Lexer::new(input);
+        let mut games = Vec::new();
+
+        loop {
+            match Self::next_game(&mut lexer) {
+                Ok(Some(game)) => games.push(Ok(game)),
+                Ok(None) => break,
+                Err(e) => {
+                    games.push(Err(e));
+                    lexer.skip_to_next_game();
+                }
+            }
+        }
+
+        games
+    }
+
+    /// Parses input holding exactly one game. Files with several games are
+    /// rejected, listing by number the ones that could not be parsed.
+    pub fn parse_game(input: &str) -> Result<PgnGame, PgnError> {
+        let mut games = Self::parse_file(input);
+        match games.len() {
+            0 => Err(PgnError::Empty),
+            1 => games.remove(0),
+            count => {
+                let unreadable = games
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, game)| game.is_err())
+                    .map(|(index, _)| index + 1)
+                    .collect();
+                Err(PgnError::MultipleGames { count, unreadable })
+            }
+        }
+    }
+
+    /// Reads one game: its tag pairs, then movetext up to the result token
+    /// or the next game's tags. Comments, variations, move numbers and
+    /// annotation glyphs are skipped; only main-line moves are kept.
+    fn next_game(lexer: &mut Lexer) -> Result<Option<PgnGame>, PgnError> {
+        let mut game = PgnGame::default();
+        let mut in_movetext = false;
+        let mut variation_depth = 0u32;
+
+        while let Some(c) = lexer.peek() {
+            match c {
+                c if c.is_whitespace() => {
+                    lexer.next_char();
+                }
+                '%' if lexer.at_line_start() => lexer.skip_line(),
+                ';' => lexer.skip_line(),
+                '{' => {
+                    lexer.next_char();
+                    loop {
+                        match lexer.next_char() {
+                            Some('}') => break,
+                            Some(_) => {}
+                            None => return Err(PgnError::UnterminatedComment),
+                        }
+                    }
+                }
+                '[' if in_movetext => {
+                    // A game without a result token ends where the next one's tags begin
+                    if variation_depth != 0 {
+                        return Err(PgnError::UnbalancedVariation);
+                    }
+                    return Ok(Some(game));
+                }
+                '[' => {
+                    lexer.next_char();
+                    let (name, value) = parse_tag(lexer)?;
+                    game.tags.insert(name, value);
+                }
+                '(' => {
+                    lexer.next_char();
+                    in_movetext = true;
+                    variation_depth += 1;
+                }
+                ')' => {
+                    lexer.next_char();
+                    if variation_depth == 0 {
+                        return Err(PgnError::UnbalancedVariation);
+                    }
+                    variation_depth -= 1;
+                }
+                ']' | '}' => {
+                    lexer.next_char();
+                    return Err(PgnError::UnexpectedToken(c.to_string()));
+                }
+                _ => {
+                    in_movetext = true;
+                    let symbol = lexer.symbol();
+                    if variation_depth > 0 {
+                        continue;
+                    }
+                    if RESULT_TOKENS.contains(&symbol.as_str()) {
+                        game.result = Some(symbol);
+                        return Ok(Some(game));
+                    }
+                    if let Some(san) = strip_move_token(&symbol) {
+                        game.moves.push(san.to_string());
+                    }
+                }
+            }
+        }
+
+        if variation_depth != 0 {
+            return Err(PgnError::UnbalancedVariation);
+        }
+        if game.tags.is_empty() && game.moves.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(game))
+    }
+}
+
+/// Reads `Name "Value"]` after the opening bracket, unescaping `\"` and `\\`
+fn parse_tag(lexer: &mut Lexer) -> Result<(String, String), PgnError> {
+    let mut raw = String::new();
+    let mut value = None;
+
+    loop {
+        match lexer.next_char() {
+            Some('"') if value.is_none() => {
+                let mut text = String::new();
+                loop {
+                    match lexer.next_char() {
+                        Some('\\') => match lexer.next_char() {
+                            Some('\n') | None => return Err(PgnError::UnterminatedTag),
+                            Some(escaped) => text.push(escaped),
+                        },
+                        Some('"') => break,
+                        Some('\n') | None => return Err(PgnError::UnterminatedTag),
+                        Some(c) => text.push(c),
+                    }
+                }
+                value = Some(text);
+            }
+            Some(']') => break,
+            // Tag pairs never span lines
+            Some('\n') | None => return Err(PgnError::UnterminatedTag),
+            Some(c) => raw.push(c),
+        }
+    }
+
+    let name = raw.trim();
+    match value {
+        Some(value) if !name.is_empty() && !name.contains(char::is_whitespace) => {
+            Ok((name.to_string(), value))
+        }
+        _ => Err(PgnError::MalformedTag(name.to_string())),
+    }
+}
+
+/// Drops a leading move number ("12." or "12...") and trailing
+/// annotations ("!", "?!"), returning the SAN if anything is left.
+/// Numeric and symbolic annotation glyphs like "$1" or "+-" yield nothing.
+fn strip_move_token(token: &str) -> Option<&str> {
+    let after_digits = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let san = if after_digits.starts_with('.') {
+        after_digits.trim_start_matches('.')
+    } else {
+        token
+    };
+
+    let san = san.trim_end_matches(['!', '?']);
+    let is_move = san.starts_with(|c: char| c.is_ascii_alphabetic()) || san.starts_with("0-0");
+    if is_move {
+        Some(san)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::types::GameStatus;
+
+    const FILE: &str = r#"[Event "Casual \"blitz\""]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+[Result "1-0"]
+
+1. e4 e5 {King's pawn} 2. Bc4 (2. Nf3 Nc6 (2... d6)) 2... Nc6
+3. Qh5!? Nf6?? 4. Qxf7# $1 1-0
+
+[Event "Broken"]
+[White "Unterminated
+[Black "Nobody"]
+
+1. d4 d5 *
+
+[Event "Third"]
+
+; A rest-of-line comment
+% An escaped line
+1. d4 d5 2. c4 *
+"#;
+
+    #[test]
+    fn parse_file_reads_every_game_and_recovers_from_errors() {
+        let games = PgnParser::parse_file(FILE);
+        assert_eq!(games.len(), 3);
+
+        let first = games[0].as_ref().unwrap();
+        assert_eq!(first.tags["Event"], "Casual \"blitz\"");
+        assert_eq!(first.moves, ["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"]);
+        assert_eq!(first.result.as_deref(), Some("1-0"));
+        assert_eq!(first.to_game_state().unwrap().status, GameStatus::Checkmate(Color::White));
+
+        assert!(matches!(games[1], Err(PgnError::UnterminatedTag)));
+
+        let third = games[2].as_ref().unwrap();
+        assert_eq!(third.tags["Event"], "Third");
+        assert_eq!(third.moves, ["d4", "d5", "c4"]);
+        assert_eq!(third.result.as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn parse_game_rejects_files_with_several_games() {
+        let error = PgnParser::parse_game(FILE).unwrap_err();
+        assert!(matches!(error, PgnError::MultipleGames { count: 3, .. }));
+        assert_eq!(error.to_string(), "Expected one game, found 3 (unreadable: game 2)");
+
+        let game = PgnParser::parse_game("[Event \"Only\"]\n\n1. e4 e5 *\n").unwrap();
+        assert_eq!(game.moves, ["e4", "e5"]);
+        assert!(matches!(PgnParser::parse_game("\n"), Err(PgnError::Empty)));
+    }
+
+    #[test]
+    fn to_game_state_names_the_rejected_move() {
+        let game = PgnParser::parse_game("1. e4 e5 2. Nf3 Ke6 *").unwrap();
+        match game.to_game_state() {
+            Err(ChessError::InvalidMove(reason)) => assert!(reason.starts_with("2... Ke6 ("), "{}", reason),
+            other => panic!("expected Ke6 to be rejected, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use super::types::Move;
+
+/// Default number of entries; a power of two so the index is a mask
+pub const DEFAULT_TABLE_SIZE: usize = 1 << 16;
+
+/// How a stored score relates to the position's true value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundType {
+    Exact,
+    LowerBound, // Search failed high; true score is at least this
+    UpperBound, // Search failed low; true score is at most this
+}
+
+#[derive(Debug, Clone)]
+pub struct TranspositionEntry {
+    pub hash: u64,
+    pub depth: u8,
+    pub score: i32,
+    pub flag: BoundType,
+    pub best_move: Option<Move>,
+}
+
+/// Fixed-size table of search results keyed by Zobrist hash. Each slot
+/// holds one entry; collisions are resolved by replacement.
+pub struct TranspositionTable {
+    entries: Vec<Option<TranspositionEntry>>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    /// Creates a table with `size` entries, rounded up to a power of two
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1).next_power_of_two();
+        Self {
+            entries: vec![None; size],
+            mask: size - 1,
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        hash as usize & self.mask
+    }
+
+    /// The entry for this exact position, if one is stored
+    pub fn probe(&self, hash: u64) -> Option<&TranspositionEntry> {
+        self.entries[self.index(hash)]
+            .as_ref()
+            .filter(|entry| entry.hash == hash)
+    }
+
+    /// Stores an entry, keeping an existing deeper result for the same position
+    pub fn store(&mut self, entry: TranspositionEntry) {
+        let index = self.index(entry.hash);
+        if let Some(existing) = &self.entries[index] {
+            if existing.hash == entry.hash && existing.depth > entry.depth {
+                return;
+            }
+        }
+        self.entries[index] = Some(entry);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_SIZE)
+    }
+}
@@ -1,7 +1,17 @@
+use super::board::Board;
+use super::notation;
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// A color, piece or castling token that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{0}")]
+pub struct ParseError(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 pub enum Color {
     White,
     Black,
@@ -16,7 +26,29 @@ impl Color {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::White => write!(f, "White"),
+            Color::Black => write!(f, "Black"),
+        }
+    }
+}
+
+/// The active color field of a FEN: `w` or `b`
+impl TryFrom<char> for Color {
+    type Error = ParseError;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'w' => Ok(Color::White),
+            'b' => Ok(Color::Black),
+            _ => Err(ParseError(format!("invalid color '{}'", c))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 pub enum PieceType {
     Pawn,
     Rook,
@@ -26,6 +58,87 @@ pub enum PieceType {
     King,
 }
 
+impl PieceType {
+    /// Lowercase FEN letter for the piece type
+    pub fn to_char(self) -> char {
+        match self {
+            PieceType::Pawn => 'p',
+            PieceType::Rook => 'r',
+            PieceType::Knight => 'n',
+            PieceType::Bishop => 'b',
+            PieceType::Queen => 'q',
+            PieceType::King => 'k',
+        }
+    }
+
+    /// Value in centipawns. The king is never traded, so it is worth more
+    /// than everything else together and always the most valuable victim;
+    /// material totals leave it out.
+    pub fn centipawn_value(self) -> i32 {
+        match self {
+            PieceType::Pawn => 100,
+            PieceType::Knight => 320,
+            PieceType::Bishop => 330,
+            PieceType::Rook => 500,
+            PieceType::Queen => 900,
+            PieceType::King => 20_000,
+        }
+    }
+}
+
+impl fmt::Display for PieceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PieceType::Pawn => "Pawn",
+            PieceType::Rook => "Rook",
+            PieceType::Knight => "Knight",
+            PieceType::Bishop => "Bishop",
+            PieceType::Queen => "Queen",
+            PieceType::King => "King",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A FEN/PGN piece letter in either case: P, R, N, B, Q or K
+impl TryFrom<char> for PieceType {
+    type Error = ParseError;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c.to_ascii_lowercase() {
+            'p' => Ok(PieceType::Pawn),
+            'r' => Ok(PieceType::Rook),
+            'n' => Ok(PieceType::Knight),
+            'b' => Ok(PieceType::Bishop),
+            'q' => Ok(PieceType::Queen),
+            'k' => Ok(PieceType::King),
+            _ => Err(ParseError(format!("unknown piece '{}'", c))),
+        }
+    }
+}
+
+/// A piece letter or name in any case, e.g. "n", "Knight" or "knight"
+impl FromStr for PieceType {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return PieceType::try_from(c);
+        }
+
+        match s.to_ascii_lowercase().as_str() {
+            "pawn" => Ok(PieceType::Pawn),
+            "rook" => Ok(PieceType::Rook),
+            "knight" => Ok(PieceType::Knight),
+            "bishop" => Ok(PieceType::Bishop),
+            "queen" => Ok(PieceType::Queen),
+            "king" => Ok(PieceType::King),
+            _ => Err(ParseError(format!("unknown piece '{}'", s))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Piece {
     pub piece_type: PieceType,
@@ -36,9 +149,44 @@ impl Piece {
     pub fn new(piece_type: PieceType, color: Color) -> Self {
         Self { piece_type, color }
     }
+
+    /// Piece for a FEN letter, uppercase for White; `None` for anything
+    /// else, including the `.` used for empty squares
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        let piece_type = PieceType::try_from(c).ok()?;
+        let color = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        Some(Piece::new(piece_type, color))
+    }
+
+    /// FEN letter for the piece, uppercase for White
+    pub fn to_fen_char(self) -> char {
+        match self.color {
+            Color::White => self.piece_type.to_char().to_ascii_uppercase(),
+            Color::Black => self.piece_type.to_char(),
+        }
+    }
+
+    pub fn centipawn_value(self) -> i32 {
+        self.piece_type.centipawn_value()
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// "White King", or the FEN letter with the alternate flag (`{:#}`)
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}", self.to_fen_char())
+        } else {
+            write!(f, "{} {}", self.color, self.piece_type)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
 pub struct Square {
     pub file: u8, // 0-7 representing a-h
     pub rank: u8, // 0-7 representing 1-8
@@ -53,34 +201,37 @@ impl Square {
         }
     }
 
-    pub fn from_algebraic(notation: &str) -> Option<Self> {
-        if notation.len() != 2 {
-            return None;
-        }
-
-        let chars: Vec<char> = notation.chars().collect();
-        let file = (chars[0] as u8).checked_sub(b'a')?;
-        let rank = (chars[1] as u8).checked_sub(b'1')?;
+    pub fn is_valid(self) -> bool {
+        self.file < 8 && self.rank < 8
+    }
 
-        Self::new(file, rank)
+    /// Square for a bitboard index 0-63, where a1 = 0, h1 = 7 and a8 = 56
+    pub fn from_index(idx: u8) -> Option<Self> {
+        if idx < 64 {
+            Self::new(idx % 8, idx / 8)
+        } else {
+            None
+        }
     }
 
-    pub fn to_algebraic(self) -> String {
-        format!("{}{}", (b'a' + self.file) as char, (b'1' + self.rank) as char)
+    /// Bitboard index of the square, the inverse of `from_index`
+    pub fn to_index(self) -> u8 {
+        self.rank * 8 + self.file
     }
 
-    pub fn is_valid(self) -> bool {
-        self.file < 8 && self.rank < 8
+    /// All 64 squares in index order, a1 through h8
+    pub fn iter() -> impl Iterator<Item = Square> {
+        (0..64).filter_map(Self::from_index)
     }
 }
 
 impl fmt::Display for Square {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_algebraic())
+        write!(f, "{}", notation::square_to_algebraic(*self))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub struct Move {
     pub from: Square,
     pub to: Square,
@@ -124,14 +275,61 @@ impl Move {
             is_en_passant: true,
         }
     }
+
+    /// Whether the move takes a piece on `board`
+    pub fn is_capture(&self, board: &Board) -> bool {
+        self.captured_piece(board).is_some()
+    }
+
+    /// The piece the move takes on `board`: the pawn beside the destination
+    /// for en passant, and nothing for castling, even when a Chess960 king
+    /// lands on its own rook
+    pub fn captured_piece(&self, board: &Board) -> Option<Piece> {
+        if self.is_castling {
+            None
+        } else if self.is_en_passant {
+            board.get_piece(Square::new(self.to.file, self.from.rank)?)
+        } else {
+            board.get_piece(self.to)
+        }
+    }
+}
+
+/// Source and destination squares, with `=Q` style promotions: `e7e8=Q`.
+/// Castling is written as the king's move, e.g. `e1g1`.
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.from, self.to)?;
+        if let Some(promotion) = self.promotion {
+            write!(f, "={}", promotion.to_char().to_ascii_uppercase())?;
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct CastlingRights {
     pub white_kingside: bool,
     pub white_queenside: bool,
     pub black_kingside: bool,
     pub black_queenside: bool,
+    // Starting files of the castling rooks, which only differ from h and a in Chess960
+    #[serde(default = "default_king_rook_file")]
+    pub white_king_rook_file: u8,
+    #[serde(default = "default_queen_rook_file")]
+    pub white_queen_rook_file: u8,
+    #[serde(default = "default_king_rook_file")]
+    pub black_king_rook_file: u8,
+    #[serde(default = "default_queen_rook_file")]
+    pub black_queen_rook_file: u8,
+}
+
+fn default_king_rook_file() -> u8 {
+    7
+}
+
+fn default_queen_rook_file() -> u8 {
+    0
 }
 
 impl CastlingRights {
@@ -141,6 +339,60 @@ impl CastlingRights {
             white_queenside: true,
             black_kingside: true,
             black_queenside: true,
+            white_king_rook_file: default_king_rook_file(),
+            white_queen_rook_file: default_queen_rook_file(),
+            black_king_rook_file: default_king_rook_file(),
+            black_queen_rook_file: default_queen_rook_file(),
+        }
+    }
+
+    /// No castling allowed, rooks on their standard files
+    pub fn none() -> Self {
+        Self {
+            white_kingside: false,
+            white_queenside: false,
+            black_kingside: false,
+            black_queenside: false,
+            ..Self::new()
+        }
+    }
+
+    /// Rights for a Chess960 setup, with both sides' rooks on the given files
+    pub fn chess960(king_rook_file: u8, queen_rook_file: u8) -> Self {
+        Self {
+            white_king_rook_file: king_rook_file,
+            white_queen_rook_file: queen_rook_file,
+            black_king_rook_file: king_rook_file,
+            black_queen_rook_file: queen_rook_file,
+            ..Self::new()
+        }
+    }
+
+    /// File the castling rook starts on for the given side
+    pub fn rook_file(&self, color: Color, kingside: bool) -> u8 {
+        match (color, kingside) {
+            (Color::White, true) => self.white_king_rook_file,
+            (Color::White, false) => self.white_queen_rook_file,
+            (Color::Black, true) => self.black_king_rook_file,
+            (Color::Black, false) => self.black_queen_rook_file,
+        }
+    }
+
+    pub fn set_rook_file(&mut self, color: Color, kingside: bool, file: u8) {
+        match (color, kingside) {
+            (Color::White, true) => self.white_king_rook_file = file,
+            (Color::White, false) => self.white_queen_rook_file = file,
+            (Color::Black, true) => self.black_king_rook_file = file,
+            (Color::Black, false) => self.black_queen_rook_file = file,
+        }
+    }
+
+    pub fn grant(&mut self, color: Color, kingside: bool) {
+        match (color, kingside) {
+            (Color::White, true) => self.white_kingside = true,
+            (Color::White, false) => self.white_queenside = true,
+            (Color::Black, true) => self.black_kingside = true,
+            (Color::Black, false) => self.black_queenside = true,
         }
     }
 
@@ -179,13 +431,176 @@ impl CastlingRights {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum GameStatus {
     InProgress,
     Check,
     Checkmate(Color), // Winner
+    Resigned(Color),  // Winner
+    Timeout(Color),   // Player who ran out of time
     Stalemate,
     Draw,
+    VariantWin(Color), // Winner under the variant's own rules
+}
+
+/// Grounds on which a player may claim a draw that isn't automatic yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawClaimReason {
+    FiftyMoveRule,
+    ThreefoldRepetition,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum GameMode {
+    Casual,
+    #[default]
+    Competitive,
+    Analysis, // Set up from an arbitrary position
+}
+
+impl GameMode {
+    /// Only competitive games may not take moves back
+    pub fn allows_undo(self) -> bool {
+        self != GameMode::Competitive
+    }
+
+    /// Only competitive games count towards ratings
+    pub fn is_rated(self) -> bool {
+        self == GameMode::Competitive
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Variant {
+    #[default]
+    Standard,
+    Chess960,
+    Antichess,
+}
+
+/// How good a played move was, judged by how many centipawns it lost
+/// against the engine's best move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveAnnotation {
+    Blunder,
+    Mistake,
+    Inaccuracy,
+    Good,
+    Excellent,
+    Brilliant, // The best move when every alternative loses a lot
+}
+
+impl MoveAnnotation {
+    /// Losses over 200 are blunders, 100 to 200 mistakes and 50 to 100
+    /// inaccuracies. `only_move` marks a best move whose alternatives all
+    /// lose more than 200.
+    pub fn from_centipawn_loss(loss: i32, only_move: bool) -> Self {
+        match loss {
+            l if l > 200 => MoveAnnotation::Blunder,
+            l if l >= 100 => MoveAnnotation::Mistake,
+            l if l >= 50 => MoveAnnotation::Inaccuracy,
+            l if l > 10 => MoveAnnotation::Good,
+            _ if only_move => MoveAnnotation::Brilliant,
+            _ => MoveAnnotation::Excellent,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MoveAnnotation::Blunder => "blunder",
+            MoveAnnotation::Mistake => "mistake",
+            MoveAnnotation::Inaccuracy => "inaccuracy",
+            MoveAnnotation::Good => "good",
+            MoveAnnotation::Excellent => "excellent",
+            MoveAnnotation::Brilliant => "brilliant",
+        }
+    }
+}
+
+impl FromStr for MoveAnnotation {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blunder" => Ok(MoveAnnotation::Blunder),
+            "mistake" => Ok(MoveAnnotation::Mistake),
+            "inaccuracy" => Ok(MoveAnnotation::Inaccuracy),
+            "good" => Ok(MoveAnnotation::Good),
+            "excellent" => Ok(MoveAnnotation::Excellent),
+            "brilliant" => Ok(MoveAnnotation::Brilliant),
+            _ => Err(ParseError(format!("unknown annotation '{}'", s))),
+        }
+    }
+}
+
+impl GameStatus {
+    /// The side that won, if the game ended decisively. A timeout is a
+    /// win for the opponent of the side that flagged.
+    pub fn winner(&self) -> Option<Color> {
+        match *self {
+            GameStatus::Checkmate(winner)
+            | GameStatus::Resigned(winner)
+            | GameStatus::VariantWin(winner) => Some(winner),
+            GameStatus::Timeout(flagged) => Some(flagged.opposite()),
+            _ => None,
+        }
+    }
+
+    pub fn is_draw(&self) -> bool {
+        matches!(self, GameStatus::Draw | GameStatus::Stalemate)
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        matches!(self, GameStatus::Checkmate(_))
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.winner().is_some() || self.is_draw()
+    }
+}
+
+impl fmt::Display for GameStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            GameStatus::InProgress => write!(f, "In progress"),
+            GameStatus::Check => write!(f, "Check"),
+            GameStatus::Checkmate(winner) => write!(f, "{} wins by checkmate", winner),
+            GameStatus::Resigned(winner) => write!(f, "{} wins by resignation", winner),
+            GameStatus::Timeout(flagged) => write!(f, "{} wins on time", flagged.opposite()),
+            GameStatus::Stalemate => write!(f, "Draw by stalemate"),
+            GameStatus::Draw => write!(f, "Draw"),
+            GameStatus::VariantWin(winner) => write!(f, "{} wins by the variant's rules", winner),
+        }
+    }
+}
+
+/// The castling field of a standard FEN: any of `KQkq`, or `-` for none
+impl FromStr for CastlingRights {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rights = CastlingRights::none();
+        if s == "-" {
+            return Ok(rights);
+        }
+        if s.is_empty() {
+            return Err(ParseError("empty castling rights".to_string()));
+        }
+
+        for c in s.chars() {
+            match c {
+                'K' => rights.white_kingside = true,
+                'Q' => rights.white_queenside = true,
+                'k' => rights.black_kingside = true,
+                'q' => rights.black_queenside = true,
+                _ => return Err(ParseError(format!("invalid castling right '{}'", c))),
+            }
+        }
+        Ok(rights)
+    }
 }
 
 impl Default for CastlingRights {
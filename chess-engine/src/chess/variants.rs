@@ -0,0 +1,53 @@
+//! Rules of the variants that play differently from standard chess, not
+//! just from a different starting position.
+//!
+//! In Antichess the king is an ordinary piece that can be captured, there
+//! is no check and no castling, and a capture must be made whenever one is
+//! available. The side to move wins once it has no pieces left or no legal
+//! move.
+
+use super::game::GameState;
+use super::types::{CastlingRights, Move, Variant};
+use super::zobrist;
+
+impl GameState {
+    /// The standard starting position without castling, played as Antichess
+    pub fn new_antichess() -> Self {
+        let mut state = Self::new();
+        let previous_castling = zobrist::castling_key(&state.castling_rights);
+        state.castling_rights = CastlingRights::none();
+        let castling = zobrist::castling_key(&state.castling_rights);
+        state.board.xor_hash(previous_castling ^ castling);
+        state.variant = Variant::Antichess;
+        state
+    }
+
+    /// Whether the king must be kept out of check, as in every variant but Antichess
+    pub fn has_royal_king(&self) -> bool {
+        self.variant != Variant::Antichess
+    }
+
+    /// Whether the side to move is obliged to capture, as in Antichess when
+    /// any of its legal moves is a capture
+    pub(super) fn capture_required(&self) -> bool {
+        self.variant == Variant::Antichess
+            && self
+                .board
+                .get_pieces(self.current_player)
+                .into_iter()
+                .any(|(from, piece)| {
+                    let mut moves = Vec::new();
+                    self.add_legal_moves_from(from, piece, &mut moves);
+                    moves
+                        .iter()
+                        .any(|chess_move| chess_move.is_capture(&self.board))
+                })
+    }
+
+    /// Drops the moves a compulsory capture rules out
+    pub(super) fn retain_required_captures(&self, moves: &mut Vec<Move>) {
+        if self.capture_required() {
+            moves.retain(|chess_move| chess_move.is_capture(&self.board));
+        }
+    }
+}
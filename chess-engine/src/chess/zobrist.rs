@@ -0,0 +1,98 @@
+use super::types::{CastlingRights, Color, Piece, PieceType, Square};
+use lazy_static::lazy_static;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// Fixed seed so hashes are stable across restarts and serialized boards
+const ZOBRIST_SEED: u64 = 0x5EED_C0DE_CAFE_F00D;
+
+struct ZobristKeys {
+    pieces: [[[u64; 64]; 6]; 2], // [color][piece type][square]
+    side_to_move: u64,
+    castling: [u64; 4], // white kingside, white queenside, black kingside, black queenside
+    en_passant_file: [u64; 8],
+}
+
+impl ZobristKeys {
+    fn generate() -> Self {
+        let mut rng = StdRng::seed_from_u64(ZOBRIST_SEED);
+
+        let mut pieces = [[[0u64; 64]; 6]; 2];
+        for color_keys in pieces.iter_mut() {
+            for piece_keys in color_keys.iter_mut() {
+                for key in piece_keys.iter_mut() {
+                    *key = rng.gen::<u64>();
+                }
+            }
+        }
+
+        let side_to_move = rng.gen::<u64>();
+
+        let mut castling = [0u64; 4];
+        for key in castling.iter_mut() {
+            *key = rng.gen::<u64>();
+        }
+
+        let mut en_passant_file = [0u64; 8];
+        for key in en_passant_file.iter_mut() {
+            *key = rng.gen::<u64>();
+        }
+
+        Self {
+            pieces,
+            side_to_move,
+            castling,
+            en_passant_file,
+        }
+    }
+}
+
+lazy_static! {
+    static ref KEYS: ZobristKeys = ZobristKeys::generate();
+}
+
+pub fn piece_key(piece: Piece, square: Square) -> u64 {
+    let color = match piece.color {
+        Color::White => 0,
+        Color::Black => 1,
+    };
+    let piece_type = match piece.piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Rook => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    let index = square.to_index() as usize;
+
+    KEYS.pieces[color][piece_type][index]
+}
+
+/// Key XORed in while Black is to move
+pub fn side_key() -> u64 {
+    KEYS.side_to_move
+}
+
+pub fn castling_key(rights: &CastlingRights) -> u64 {
+    let mut key = 0;
+    if rights.white_kingside {
+        key ^= KEYS.castling[0];
+    }
+    if rights.white_queenside {
+        key ^= KEYS.castling[1];
+    }
+    if rights.black_kingside {
+        key ^= KEYS.castling[2];
+    }
+    if rights.black_queenside {
+        key ^= KEYS.castling[3];
+    }
+    key
+}
+
+pub fn en_passant_key(target: Option<Square>) -> u64 {
+    match target {
+        Some(square) => KEYS.en_passant_file[square.file as usize],
+        None => 0,
+    }
+}
@@ -0,0 +1,217 @@
+use crate::chess::{GameState, GameStatus};
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use uuid::Uuid;
+
+pub struct GameRow {
+    pub id: String,
+    pub state: GameState,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Creation date and player usernames of a game, for export headers
+pub struct GameRecord {
+    pub created_at: DateTime<Utc>,
+    pub white_username: Option<String>,
+    pub black_username: Option<String>,
+}
+
+/// Value stored in the `status` column, kept alongside the JSONB state for filtering
+pub fn status_label(status: &GameStatus) -> &'static str {
+    match status {
+        GameStatus::InProgress => "in_progress",
+        GameStatus::Check => "check",
+        GameStatus::Checkmate(_) => "checkmate",
+        GameStatus::Resigned(_) => "resigned",
+        GameStatus::Timeout(_) => "timeout",
+        GameStatus::Stalemate => "stalemate",
+        GameStatus::Draw => "draw",
+        GameStatus::VariantWin(_) => "variant_win",
+    }
+}
+
+#[tracing::instrument(name = "db.load_game", skip_all, fields(game_id = %game_id))]
+pub async fn load_game(pool: &Pool, game_id: &str) -> anyhow::Result<Option<GameState>> {
+    let id = match Uuid::parse_str(game_id) {
+        Ok(id) => id,
+        Err(_) => return Ok(None),
+    };
+    let _timer = metrics::db_timer("load_game");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt("SELECT state FROM games WHERE id = $1", &[&id])
+        .await?;
+
+    match row {
+        Some(row) => {
+            let state_json: serde_json::Value = row.get(0);
+            Ok(Some(serde_json::from_value(state_json)?))
+        }
+        None => Ok(None),
+    }
+}
+
+#[tracing::instrument(name = "db.load_game_record", skip_all, fields(game_id = %game_id))]
+pub async fn load_game_record(pool: &Pool, game_id: &str) -> anyhow::Result<Option<GameRecord>> {
+    let id = match Uuid::parse_str(game_id) {
+        Ok(id) => id,
+        Err(_) => return Ok(None),
+    };
+    let _timer = metrics::db_timer("load_game_record");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt(
+            "SELECT g.created_at, w.username, b.username FROM games g \
+             LEFT JOIN users w ON w.id = (g.state->>'white_player')::INT \
+             LEFT JOIN users b ON b.id = (g.state->>'black_player')::INT \
+             WHERE g.id = $1",
+            &[&id],
+        )
+        .await?;
+
+    Ok(row.map(|row| GameRecord {
+        created_at: row.get(0),
+        white_username: row.get(1),
+        black_username: row.get(2),
+    }))
+}
+
+/// Inserts a new game or overwrites the state of an existing one
+#[tracing::instrument(name = "db.save_game", skip_all, fields(game_id = %game_id))]
+pub async fn save_game(pool: &Pool, game_id: &str, state: &GameState) -> anyhow::Result<()> {
+    let id = Uuid::parse_str(game_id)?;
+    let state_json = serde_json::to_value(state)?;
+    let _timer = metrics::db_timer("save_game");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "INSERT INTO games (id, state, status) VALUES ($1, $2, $3) \
+             ON CONFLICT (id) DO UPDATE \
+             SET state = EXCLUDED.state, status = EXCLUDED.status, updated_at = NOW()",
+            &[&id, &state_json, &status_label(&state.status)],
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "db.delete_game", skip_all, fields(game_id = %game_id))]
+pub async fn delete_game(pool: &Pool, game_id: &str) -> anyhow::Result<bool> {
+    let id = match Uuid::parse_str(game_id) {
+        Ok(id) => id,
+        Err(_) => return Ok(false),
+    };
+    let _timer = metrics::db_timer("delete_game");
+    let client = pool.get().await?;
+
+    let deleted = client
+        .execute("DELETE FROM games WHERE id = $1", &[&id])
+        .await?;
+
+    Ok(deleted > 0)
+}
+
+/// Every unfinished game, oldest first
+#[tracing::instrument(name = "db.list_active_games", skip_all)]
+pub async fn list_active_games(pool: &Pool) -> anyhow::Result<Vec<(String, GameState)>> {
+    let _timer = metrics::db_timer("list_active_games");
+    let client = pool.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT id, state FROM games WHERE status IN ('in_progress', 'check') ORDER BY created_at",
+            &[],
+        )
+        .await?;
+
+    let mut games = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id: Uuid = row.get(0);
+        let state_json: serde_json::Value = row.get(1);
+        games.push((id.to_string(), serde_json::from_value(state_json)?));
+    }
+
+    Ok(games)
+}
+
+/// IDs of unfinished games that have not been saved since `cutoff`, each
+/// with the `event_id` of its stored state
+#[tracing::instrument(name = "db.list_inactive_games", skip_all)]
+pub async fn list_inactive_games(
+    pool: &Pool,
+    cutoff: DateTime<Utc>,
+) -> anyhow::Result<Vec<(String, u64)>> {
+    let _timer = metrics::db_timer("list_inactive_games");
+    let client = pool.get().await?;
+
+    // States saved before events were numbered have no event_id
+    let rows = client
+        .query(
+            "SELECT id, COALESCE((state->>'event_id')::BIGINT, 0) FROM games \
+             WHERE status IN ('in_progress', 'check') AND updated_at < $1",
+            &[&cutoff],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<_, Uuid>(0).to_string(), row.get::<_, i64>(1) as u64))
+        .collect())
+}
+
+/// Lists games `user_id` created or plays in, newest first, optionally filtered by status label
+#[tracing::instrument(name = "db.list_games", skip_all, fields(user_id = user_id))]
+pub async fn list_games(
+    pool: &Pool,
+    user_id: i32,
+    status: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<GameRow>> {
+    let _timer = metrics::db_timer("list_games");
+    let client = pool.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT id, state, created_at FROM games \
+             WHERE $1 IN ((state->>'created_by')::INT, (state->>'white_player')::INT, (state->>'black_player')::INT) \
+             AND ($2::TEXT IS NULL OR status = $2) \
+             ORDER BY created_at DESC LIMIT $3 OFFSET $4",
+            &[&user_id, &status, &limit, &offset],
+        )
+        .await?;
+
+    let mut games = Vec::with_capacity(rows.len());
+    for row in rows {
+        let id: Uuid = row.get(0);
+        let state_json: serde_json::Value = row.get(1);
+        games.push(GameRow {
+            id: id.to_string(),
+            state: serde_json::from_value(state_json)?,
+            created_at: row.get(2),
+        });
+    }
+
+    Ok(games)
+}
+
+#[tracing::instrument(name = "db.count_games", skip_all, fields(user_id = user_id))]
+pub async fn count_games(pool: &Pool, user_id: i32, status: Option<&str>) -> anyhow::Result<i64> {
+    let _timer = metrics::db_timer("count_games");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_one(
+            "SELECT COUNT(*) FROM games \
+             WHERE $1 IN ((state->>'created_by')::INT, (state->>'white_player')::INT, (state->>'black_player')::INT) \
+             AND ($2::TEXT IS NULL OR status = $2)",
+            &[&user_id, &status],
+        )
+        .await?;
+
+    Ok(row.get(0))
+}
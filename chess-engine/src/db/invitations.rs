@@ -0,0 +1,153 @@
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_ACCEPTED: &str = "accepted";
+pub const STATUS_DECLINED: &str = "declined";
+
+pub struct InvitationRow {
+    pub id: String,
+    pub from_user_id: i32,
+    pub from_username: String,
+    pub to_user_id: i32,
+    pub to_username: String,
+    pub status: String,
+    pub game_settings: serde_json::Value,
+    pub game_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl InvitationRow {
+    /// Still pending and not yet expired
+    pub fn is_open(&self) -> bool {
+        self.status == STATUS_PENDING && self.expires_at > Utc::now()
+    }
+}
+
+const SELECT_INVITATIONS: &str =
+    "SELECT i.id, i.from_user_id, f.username, i.to_user_id, t.username, \
+     i.status, i.game_settings, i.game_id, i.created_at, i.expires_at \
+     FROM invitations i \
+     JOIN users f ON f.id = i.from_user_id \
+     JOIN users t ON t.id = i.to_user_id";
+
+fn invitation_from_row(row: &Row) -> InvitationRow {
+    let id: Uuid = row.get(0);
+    let game_id: Option<Uuid> = row.get(7);
+    InvitationRow {
+        id: id.to_string(),
+        from_user_id: row.get(1),
+        from_username: row.get(2),
+        to_user_id: row.get(3),
+        to_username: row.get(4),
+        status: row.get(5),
+        game_settings: row.get(6),
+        game_id: game_id.map(|id| id.to_string()),
+        created_at: row.get(8),
+        expires_at: row.get(9),
+    }
+}
+
+/// Stores a new pending invitation and returns its ID
+#[tracing::instrument(
+    name = "db.create_invitation",
+    skip_all,
+    fields(from_user_id = from_user_id, to_user_id = to_user_id)
+)]
+pub async fn create_invitation(
+    pool: &Pool,
+    from_user_id: i32,
+    to_user_id: i32,
+    game_settings: &serde_json::Value,
+    expires_at: DateTime<Utc>,
+) -> anyhow::Result<String> {
+    let id = Uuid::new_v4();
+    let _timer = metrics::db_timer("create_invitation");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "INSERT INTO invitations (id, from_user_id, to_user_id, game_settings, expires_at) \
+             VALUES ($1, $2, $3, $4, $5)",
+            &[&id, &from_user_id, &to_user_id, game_settings, &expires_at],
+        )
+        .await?;
+
+    Ok(id.to_string())
+}
+
+#[tracing::instrument(name = "db.load_invitation", skip_all, fields(invitation_id = %invitation_id))]
+pub async fn load_invitation(
+    pool: &Pool,
+    invitation_id: &str,
+) -> anyhow::Result<Option<InvitationRow>> {
+    let id = match Uuid::parse_str(invitation_id) {
+        Ok(id) => id,
+        Err(_) => return Ok(None),
+    };
+    let _timer = metrics::db_timer("load_invitation");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt(&format!("{} WHERE i.id = $1", SELECT_INVITATIONS), &[&id])
+        .await?;
+
+    Ok(row.as_ref().map(invitation_from_row))
+}
+
+/// Pending, unexpired invitations sent to or by `user_id`, newest first
+#[tracing::instrument(name = "db.list_open_invitations", skip_all, fields(user_id = user_id))]
+pub async fn list_open_invitations(
+    pool: &Pool,
+    user_id: i32,
+) -> anyhow::Result<Vec<InvitationRow>> {
+    let _timer = metrics::db_timer("list_open_invitations");
+    let client = pool.get().await?;
+
+    let rows = client
+        .query(
+            &format!(
+                "{} WHERE $1 IN (i.from_user_id, i.to_user_id) \
+                 AND i.status = $2 AND i.expires_at > NOW() \
+                 ORDER BY i.created_at DESC",
+                SELECT_INVITATIONS
+            ),
+            &[&user_id, &STATUS_PENDING],
+        )
+        .await?;
+
+    Ok(rows.iter().map(invitation_from_row).collect())
+}
+
+/// Moves a pending, unexpired invitation to `status`. Returns false if it
+/// was already answered or has expired, so two responses can't both win.
+#[tracing::instrument(
+    name = "db.close_invitation",
+    skip_all,
+    fields(invitation_id = %invitation_id, status = status)
+)]
+pub async fn close_invitation(
+    pool: &Pool,
+    invitation_id: &str,
+    status: &str,
+    game_id: Option<&str>,
+) -> anyhow::Result<bool> {
+    let id = Uuid::parse_str(invitation_id)?;
+    let game_id = game_id.map(Uuid::parse_str).transpose()?;
+    let _timer = metrics::db_timer("close_invitation");
+    let client = pool.get().await?;
+
+    let updated = client
+        .execute(
+            "UPDATE invitations SET status = $2, game_id = $3 \
+             WHERE id = $1 AND status = $4 AND expires_at > NOW()",
+            &[&id, &status, &game_id, &STATUS_PENDING],
+        )
+        .await?;
+
+    Ok(updated > 0)
+}
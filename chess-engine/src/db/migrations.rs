@@ -0,0 +1,151 @@
+use deadpool_postgres::Pool;
+
+/// A schema change, applied once and recorded in `schema_migrations`
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub up_sql: &'static str,
+}
+
+pub struct Migrations;
+
+impl Migrations {
+    /// Every migration in the order it must be applied. Versions are
+    /// never reused or reordered once released.
+    pub const ALL: &'static [Migration] = &[
+        Migration {
+            version: 1,
+            description: "create users",
+            up_sql: include_str!("../../migrations/001_create_users.sql"),
+        },
+        Migration {
+            version: 2,
+            description: "create games",
+            up_sql: include_str!("../../migrations/002_create_games.sql"),
+        },
+        Migration {
+            version: 3,
+            description: "create move_history",
+            up_sql: include_str!("../../migrations/003_create_move_history.sql"),
+        },
+        Migration {
+            version: 4,
+            description: "create refresh_tokens",
+            up_sql: include_str!("../../migrations/004_create_refresh_tokens.sql"),
+        },
+        Migration {
+            version: 5,
+            description: "create revoked_tokens",
+            up_sql: include_str!("../../migrations/005_create_revoked_tokens.sql"),
+        },
+        Migration {
+            version: 6,
+            description: "create game_moves",
+            up_sql: include_str!("../../migrations/006_create_game_moves.sql"),
+        },
+        Migration {
+            version: 7,
+            description: "add user ratings",
+            up_sql: include_str!("../../migrations/007_add_user_ratings.sql"),
+        },
+        Migration {
+            version: 8,
+            description: "add peak rating",
+            up_sql: include_str!("../../migrations/008_add_peak_rating.sql"),
+        },
+        Migration {
+            version: 9,
+            description: "add move annotations",
+            up_sql: include_str!("../../migrations/009_add_move_annotations.sql"),
+        },
+        Migration {
+            version: 10,
+            description: "create invitations",
+            up_sql: include_str!("../../migrations/010_create_invitations.sql"),
+        },
+        Migration {
+            version: 11,
+            description: "add email verification",
+            up_sql: include_str!("../../migrations/011_add_email_verification.sql"),
+        },
+        Migration {
+            version: 12,
+            description: "create password reset tokens",
+            up_sql: include_str!("../../migrations/012_create_password_reset_tokens.sql"),
+        },
+        Migration {
+            version: 13,
+            description: "add users is_public",
+            up_sql: include_str!("../../migrations/013_add_users_is_public.sql"),
+        },
+        Migration {
+            version: 14,
+            description: "add account status",
+            up_sql: include_str!("../../migrations/014_add_account_status.sql"),
+        },
+        Migration {
+            version: 15,
+            description: "add totp",
+            up_sql: include_str!("../../migrations/015_add_totp.sql"),
+        },
+        Migration {
+            version: 16,
+            description: "add user roles",
+            up_sql: include_str!("../../migrations/016_add_user_roles.sql"),
+        },
+        Migration {
+            version: 17,
+            description: "add move times",
+            up_sql: include_str!("../../migrations/017_add_move_times.sql"),
+        },
+    ];
+
+    /// Applies every migration not yet recorded in `schema_migrations`, each
+    /// in its own transaction. Returns how many were applied.
+    pub async fn run(pool: &Pool) -> anyhow::Result<usize> {
+        let mut client = pool.get().await?;
+
+        client
+            .batch_execute(
+                "CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version INT PRIMARY KEY,
+                    description TEXT NOT NULL,
+                    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+                )",
+            )
+            .await?;
+
+        let applied: Vec<i32> = client
+            .query("SELECT version FROM schema_migrations", &[])
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        let mut count = 0;
+        for migration in Self::ALL {
+            let version = migration.version as i32;
+            if applied.contains(&version) {
+                continue;
+            }
+
+            let transaction = client.transaction().await?;
+            transaction.batch_execute(migration.up_sql).await?;
+            transaction
+                .execute(
+                    "INSERT INTO schema_migrations (version, description) VALUES ($1, $2)",
+                    &[&version, &migration.description],
+                )
+                .await?;
+            transaction.commit().await?;
+
+            println!(
+                "📦 Applied migration {:03} ({})",
+                migration.version, migration.description
+            );
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
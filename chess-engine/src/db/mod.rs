@@ -1,6 +1,132 @@
+pub mod games;
+pub mod invitations;
+pub mod migrations;
+pub mod moves;
+pub mod ratings;
+pub mod stats;
+pub mod tokens;
+pub mod users;
+pub mod verification;
+
 use deadpool_postgres::{Manager, ManagerConfig, Pool, RecyclingMethod, Runtime};
-use tokio_postgres::NoTls;
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Duration;
 use std::{env, error::Error};
+use tokio_postgres::config::SslMode;
+use tokio_postgres::NoTls;
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+/// Whether connections to Postgres use TLS, from `DATABASE_SSL`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DatabaseSsl {
+    Disable,
+    Prefer,
+    Require,
+}
+
+impl DatabaseSsl {
+    /// Defaults to `disable` when unset
+    fn from_env() -> Result<Self, String> {
+        let value = env::var("DATABASE_SSL").unwrap_or_default();
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "disable" => Ok(DatabaseSsl::Disable),
+            "prefer" => Ok(DatabaseSsl::Prefer),
+            "require" => Ok(DatabaseSsl::Require),
+            other => Err(format!(
+                "DATABASE_SSL must be require, prefer or disable, got '{}'",
+                other
+            )),
+        }
+    }
+}
+
+const DEFAULT_POOL_MAX_SIZE: usize = 10;
+const DEFAULT_POOL_MIN_IDLE: usize = 1;
+const DEFAULT_POOL_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_CONNECT_RETRIES: u32 = 5;
+// Backoff stops doubling at 2^5 = 32 seconds between attempts
+const MAX_BACKOFF_EXPONENT: u32 = 5;
+
+/// Pool sizing from `DB_POOL_MAX_SIZE`, `DB_POOL_MIN_IDLE`,
+/// `DB_POOL_TIMEOUT_SECS` and `DB_POOL_RECYCLING`
+struct PoolSettings {
+    max_size: usize,
+    /// Connections opened at startup; deadpool keeps idle connections
+    /// until they fail a recycle check, so these stay warm
+    min_idle: usize,
+    /// How long a request waits for a free connection, and how long
+    /// opening or recycling one may take
+    timeout: Duration,
+    recycling_method: RecyclingMethod,
+}
+
+impl PoolSettings {
+    fn from_env() -> Result<Self, String> {
+        let max_size = env::var("DB_POOL_MAX_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&size| size > 0)
+            .unwrap_or(DEFAULT_POOL_MAX_SIZE);
+        let min_idle = env::var("DB_POOL_MIN_IDLE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_POOL_MIN_IDLE)
+            .min(max_size);
+        let timeout_secs = env::var("DB_POOL_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_POOL_TIMEOUT_SECS);
+
+        let recycling = env::var("DB_POOL_RECYCLING").unwrap_or_default();
+        let recycling_method = match recycling.trim().to_ascii_lowercase().as_str() {
+            "" | "fast" => RecyclingMethod::Fast,
+            "clean" => RecyclingMethod::Clean,
+            "verified" => RecyclingMethod::Verified,
+            other => {
+                return Err(format!(
+                    "DB_POOL_RECYCLING must be Fast, Clean or Verified, got '{}'",
+                    other
+                ))
+            }
+        };
+
+        Ok(Self {
+            max_size,
+            min_idle,
+            timeout: Duration::from_secs(timeout_secs),
+            recycling_method,
+        })
+    }
+}
+
+/// Verifies the server against the CA in `DATABASE_CA_CERT_PATH`, for
+/// self-signed certificates, or else the system trust store
+fn rustls_connector() -> Result<MakeRustlsConnect, Box<dyn Error>> {
+    let mut roots = rustls::RootCertStore::empty();
+
+    let ca_cert_path = env::var("DATABASE_CA_CERT_PATH").unwrap_or_default();
+    let der_certs = if ca_cert_path.trim().is_empty() {
+        rustls_native_certs::load_native_certs()?
+            .into_iter()
+            .map(|cert| cert.0)
+            .collect()
+    } else {
+        let mut reader = BufReader::new(File::open(ca_cert_path.trim())?);
+        rustls_pemfile::certs(&mut reader)?
+    };
+
+    let (added, _) = roots.add_parsable_certificates(&der_certs);
+    if added == 0 {
+        return Err("no usable CA certificates for the database connection".into());
+    }
+
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(MakeRustlsConnect::new(config))
+}
 
 pub async fn create_pool() -> Result<Pool, Box<dyn Error>> {
     // Fetch DATABASE_URL from environment
@@ -8,25 +134,86 @@ pub async fn create_pool() -> Result<Pool, Box<dyn Error>> {
         .expect("DATABASE_URL must be set in environment variables");
 
     // Parse the DATABASE_URL into a Postgres config
-    let pg_config: tokio_postgres::Config = database_url.parse()?;
+    let mut pg_config: tokio_postgres::Config = database_url.parse()?;
+
+    let settings = PoolSettings::from_env()?;
 
     // Create a manager for the connection pool
     let mgr_config = ManagerConfig {
-        recycling_method: RecyclingMethod::Fast,
+        recycling_method: settings.recycling_method.clone(),
+    };
+    let mgr = match DatabaseSsl::from_env()? {
+        DatabaseSsl::Disable => Manager::from_config(pg_config, NoTls, mgr_config),
+        ssl => {
+            let ssl_mode = if ssl == DatabaseSsl::Require {
+                SslMode::Require
+            } else {
+                SslMode::Prefer
+            };
+            pg_config.ssl_mode(ssl_mode);
+            Manager::from_config(pg_config, rustls_connector()?, mgr_config)
+        }
     };
-    let mgr = Manager::from_config(pg_config, NoTls, mgr_config);
 
     // Build the connection pool
     let pool = Pool::builder(mgr)
-        .max_size(16)
+        .max_size(settings.max_size)
+        .wait_timeout(Some(settings.timeout))
+        .create_timeout(Some(settings.timeout))
+        .recycle_timeout(Some(settings.timeout))
         .runtime(Runtime::Tokio1)
         .build()
         .unwrap();
 
-    // Test the connection
-    let client = pool.get().await?;
-    client.query("SELECT 1", &[]).await?;
+    // The database may still be starting, e.g. under Docker Compose, so
+    // retry with exponential backoff before giving up
+    let retries = env::var("DB_CONNECT_RETRIES")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(DEFAULT_CONNECT_RETRIES)
+        .max(1);
+    let mut attempt = 0;
+    loop {
+        match open_connections(&pool, settings.min_idle.max(1)).await {
+            Ok(()) => break,
+            Err(e) if attempt + 1 < retries => {
+                let delay = Duration::from_secs(2u64.pow(attempt.min(MAX_BACKOFF_EXPONENT)));
+                eprintln!(
+                    "⚠️  Database connection attempt {}/{} failed: {}; retrying in {}s",
+                    attempt + 1,
+                    retries,
+                    e,
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
 
-    println!("✅ Database connection pool established successfully");
+    println!(
+        "✅ Database connection pool established successfully (max {} connections, {} idle, {:?} recycling)",
+        settings.max_size, settings.min_idle, settings.recycling_method
+    );
     Ok(pool)
 }
+
+/// Checks out `count` connections at once and tests one, so they are all
+/// open and idle in the pool afterwards
+async fn open_connections(pool: &Pool, count: usize) -> anyhow::Result<()> {
+    let mut clients = Vec::with_capacity(count);
+    for _ in 0..count {
+        clients.push(pool.get().await?);
+    }
+    clients[0].query("SELECT 1", &[]).await?;
+    Ok(())
+}
+
+/// Checks out a connection and runs a trivial query
+#[tracing::instrument(name = "db.ping", skip_all)]
+pub async fn ping(pool: &Pool) -> anyhow::Result<()> {
+    let client = pool.get().await?;
+    client.query_one("SELECT 1", &[]).await?;
+    Ok(())
+}
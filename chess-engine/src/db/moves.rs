@@ -0,0 +1,153 @@
+use crate::chess::{notation, Color, Move, MoveAnnotation};
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use uuid::Uuid;
+
+/// A move as stored in `game_moves`
+pub struct MoveRecord {
+    pub move_number: i32,
+    pub color: Color,
+    pub from_square: String,
+    pub to_square: String,
+    pub san: String,
+    pub uci: String,
+    pub fen_after: String,
+    pub played_at: DateTime<Utc>,
+    /// How long handling the move took; unknown for moves recorded before this was tracked
+    pub move_time_ms: Option<i64>,
+    pub annotation: Option<MoveAnnotation>,
+}
+
+fn color_label(color: Color) -> &'static str {
+    match color {
+        Color::White => "white",
+        Color::Black => "black",
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "db.save_move", skip_all, fields(game_id = %game_id, move_number = move_number))]
+pub async fn save_move(
+    pool: &Pool,
+    game_id: &str,
+    move_number: i32,
+    color: Color,
+    chess_move: &Move,
+    san: &str,
+    fen_after: &str,
+    move_time_ms: i64,
+) -> anyhow::Result<()> {
+    let id = Uuid::parse_str(game_id)?;
+    let _timer = metrics::db_timer("save_move");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "INSERT INTO game_moves (game_id, move_number, color, from_square, to_square, san, uci, fen_after, move_time_ms)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            &[
+                &id,
+                &move_number,
+                &color_label(color),
+                &notation::square_to_algebraic(chess_move.from),
+                &notation::square_to_algebraic(chess_move.to),
+                &san,
+                &notation::move_to_uci(chess_move),
+                &fen_after,
+                &move_time_ms,
+            ],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Removes the most recently recorded move of a game, after a take-back
+#[tracing::instrument(name = "db.delete_last_move", skip_all, fields(game_id = %game_id))]
+pub async fn delete_last_move(pool: &Pool, game_id: &str) -> anyhow::Result<()> {
+    let id = Uuid::parse_str(game_id)?;
+    let _timer = metrics::db_timer("delete_last_move");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "DELETE FROM game_moves WHERE id = (SELECT MAX(id) FROM game_moves WHERE game_id = $1)",
+            &[&id],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Every recorded move of a game in the order played
+#[tracing::instrument(name = "db.load_game_history", skip_all, fields(game_id = %game_id))]
+pub async fn load_game_history(pool: &Pool, game_id: &str) -> anyhow::Result<Vec<MoveRecord>> {
+    let id = match Uuid::parse_str(game_id) {
+        Ok(id) => id,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let _timer = metrics::db_timer("load_game_history");
+    let client = pool.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT move_number, color, from_square, to_square, san, uci, fen_after, played_at, annotation, move_time_ms
+             FROM game_moves WHERE game_id = $1 ORDER BY id",
+            &[&id],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let color: String = row.get(1);
+            let annotation: Option<String> = row.get(8);
+            MoveRecord {
+                move_number: row.get(0),
+                color: if color == "black" { Color::Black } else { Color::White },
+                from_square: row.get(2),
+                to_square: row.get(3),
+                san: row.get(4),
+                uci: row.get(5),
+                fen_after: row.get(6),
+                played_at: row.get(7),
+                move_time_ms: row.get(9),
+                annotation: annotation.and_then(|a| a.parse().ok()),
+            }
+        })
+        .collect())
+}
+
+/// Stores one annotation per recorded move, in the order played. Moves
+/// beyond the end of `annotations` are left untouched.
+#[tracing::instrument(name = "db.save_annotations", skip_all, fields(game_id = %game_id))]
+pub async fn save_annotations(
+    pool: &Pool,
+    game_id: &str,
+    annotations: &[Option<MoveAnnotation>],
+) -> anyhow::Result<()> {
+    let id = Uuid::parse_str(game_id)?;
+    let _timer = metrics::db_timer("save_annotations");
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    let move_ids: Vec<i32> = transaction
+        .query("SELECT id FROM game_moves WHERE game_id = $1 ORDER BY id", &[&id])
+        .await?
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+
+    for (move_id, annotation) in move_ids.iter().zip(annotations) {
+        transaction
+            .execute(
+                "UPDATE game_moves SET annotation = $1 WHERE id = $2",
+                &[&annotation.map(MoveAnnotation::as_str), move_id],
+            )
+            .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use crate::metrics;
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// How far a single game can move a rating
+const K_FACTOR: f64 = 32.0;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub username: String,
+    pub rating: i32,
+}
+
+/// Points the winner gains, and the loser gives up, under the Elo formula
+fn rating_change(winner_rating: i32, loser_rating: i32) -> i32 {
+    let expected = 1.0 / (1.0 + 10f64.powf((loser_rating - winner_rating) as f64 / 400.0));
+    (K_FACTOR * (1.0 - expected)).round() as i32
+}
+
+/// Applies the result of a decisive game to both players' ratings
+#[tracing::instrument(name = "db.update_ratings", skip_all, fields(winner_id = winner_id, loser_id = loser_id))]
+pub async fn update_ratings(winner_id: i32, loser_id: i32, pool: &Pool) -> anyhow::Result<()> {
+    let _timer = metrics::db_timer("update_ratings");
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    // Lock both rows so concurrent results for either player apply in turn
+    let rows = transaction
+        .query(
+            "SELECT id, rating FROM users WHERE id = ANY($1) FOR UPDATE",
+            &[&vec![winner_id, loser_id]],
+        )
+        .await?;
+
+    let rating_of = |user_id: i32| {
+        rows.iter()
+            .find(|row| row.get::<_, i32>(0) == user_id)
+            .map(|row| row.get::<_, i32>(1))
+            .ok_or_else(|| anyhow::anyhow!("User {} not found", user_id))
+    };
+    let change = rating_change(rating_of(winner_id)?, rating_of(loser_id)?);
+
+    transaction
+        .execute(
+            "UPDATE users SET rating = rating + $1, peak_rating = GREATEST(peak_rating, rating + $1)
+             WHERE id = $2",
+            &[&change, &winner_id],
+        )
+        .await?;
+    transaction
+        .execute(
+            "UPDATE users SET rating = rating - $1 WHERE id = $2",
+            &[&change, &loser_id],
+        )
+        .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// The highest-rated players, best first
+#[tracing::instrument(name = "db.leaderboard", skip_all)]
+pub async fn leaderboard(pool: &Pool, limit: i64) -> anyhow::Result<Vec<LeaderboardEntry>> {
+    let _timer = metrics::db_timer("leaderboard");
+    let client = pool.get().await?;
+
+    let rows = client
+        .query(
+            "SELECT RANK() OVER (ORDER BY rating DESC), username, rating
+             FROM users ORDER BY rating DESC, username LIMIT $1",
+            &[&limit],
+        )
+        .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| LeaderboardEntry {
+            rank: row.get(0),
+            username: row.get(1),
+            rating: row.get(2),
+        })
+        .collect())
+}
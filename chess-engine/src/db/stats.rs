@@ -0,0 +1,196 @@
+use crate::metrics;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ColorStats {
+    pub games_played: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub draws: i64,
+    pub win_rate: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlayerStats {
+    pub username: String,
+    pub games_played: i64,
+    pub wins: i64,
+    pub losses: i64,
+    pub draws: i64,
+    pub win_rate: f64,
+    pub rating: i32,
+    pub peak_rating: i32,
+    pub average_game_length: f64,
+    pub games_as_white: ColorStats,
+    pub games_as_black: ColorStats,
+}
+
+/// What anyone can see about a player, without their contact details
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PublicProfile {
+    pub username: String,
+    pub rating: i32,
+    pub games_played: u32,
+    pub win_rate: f64,
+    pub member_since: DateTime<Utc>,
+}
+
+/// Share of games won, between 0 and 1
+fn win_rate(wins: i64, games_played: i64) -> f64 {
+    if games_played == 0 {
+        0.0
+    } else {
+        wins as f64 / games_played as f64
+    }
+}
+
+/// Results of every finished game the player was assigned a color in,
+/// or `None` if no user has that username
+#[tracing::instrument(name = "db.player_stats", skip_all, fields(username = %username))]
+pub async fn player_stats(pool: &Pool, username: &str) -> anyhow::Result<Option<PlayerStats>> {
+    let _timer = metrics::db_timer("player_stats");
+    let client = pool.get().await?;
+
+    let user = client
+        .query_opt(
+            "SELECT id, username, rating, peak_rating FROM users WHERE username = $1",
+            &[&username],
+        )
+        .await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let user_id: i32 = user.get(0);
+
+    // The status JSON is {"Checkmate": winner}, {"Resigned": winner},
+    // {"VariantWin": winner} or {"Timeout": player who flagged}; draws and
+    // stalemates have no winner
+    let rows = client
+        .query(
+            "WITH player_games AS (
+                SELECT
+                    CASE WHEN (state->>'white_player')::INT = $1 THEN 'White' ELSE 'Black' END AS color,
+                    CASE
+                        WHEN state->'status' ? 'Checkmate' THEN state->'status'->>'Checkmate'
+                        WHEN state->'status' ? 'Resigned' THEN state->'status'->>'Resigned'
+                        WHEN state->'status' ? 'VariantWin' THEN state->'status'->>'VariantWin'
+                        WHEN state->'status'->>'Timeout' = 'White' THEN 'Black'
+                        WHEN state->'status'->>'Timeout' = 'Black' THEN 'White'
+                    END AS winner,
+                    jsonb_array_length(state->'history') AS plies
+                FROM games
+                WHERE $1 IN ((state->>'white_player')::INT, (state->>'black_player')::INT)
+                  AND status IN ('checkmate', 'resigned', 'timeout', 'stalemate', 'draw', 'variant_win')
+            )
+            SELECT
+                color,
+                COUNT(*),
+                COUNT(*) FILTER (WHERE winner = color),
+                COUNT(*) FILTER (WHERE winner <> color),
+                COUNT(*) FILTER (WHERE winner IS NULL),
+                COALESCE(SUM(plies), 0)
+            FROM player_games
+            GROUP BY color",
+            &[&user_id],
+        )
+        .await?;
+
+    let mut games_as_white = ColorStats::default();
+    let mut games_as_black = ColorStats::default();
+    let mut total_plies: i64 = 0;
+
+    for row in &rows {
+        let color: String = row.get(0);
+        let stats = if color == "White" {
+            &mut games_as_white
+        } else {
+            &mut games_as_black
+        };
+        stats.games_played = row.get(1);
+        stats.wins = row.get(2);
+        stats.losses = row.get(3);
+        stats.draws = row.get(4);
+        stats.win_rate = win_rate(stats.wins, stats.games_played);
+        total_plies += row.get::<_, i64>(5);
+    }
+
+    let games_played = games_as_white.games_played + games_as_black.games_played;
+    let wins = games_as_white.wins + games_as_black.wins;
+    let average_game_length = if games_played == 0 {
+        0.0
+    } else {
+        // A move is one ply by each side
+        total_plies as f64 / 2.0 / games_played as f64
+    };
+
+    Ok(Some(PlayerStats {
+        username: user.get(1),
+        games_played,
+        wins,
+        losses: games_as_white.losses + games_as_black.losses,
+        draws: games_as_white.draws + games_as_black.draws,
+        win_rate: win_rate(wins, games_played),
+        rating: user.get(2),
+        peak_rating: user.get(3),
+        average_game_length,
+        games_as_white,
+        games_as_black,
+    }))
+}
+
+/// The player's public profile, or `None` if no user has that username or
+/// they have hidden their profile or closed their account
+#[tracing::instrument(name = "db.public_profile", skip_all, fields(username = %username))]
+pub async fn public_profile(pool: &Pool, username: &str) -> anyhow::Result<Option<PublicProfile>> {
+    let _timer = metrics::db_timer("public_profile");
+    let client = pool.get().await?;
+
+    // Winners are worked out from the status JSON as in `player_stats`
+    let row = client
+        .query_opt(
+            "SELECT
+                u.username,
+                u.rating,
+                u.created_at,
+                COUNT(g.id),
+                COUNT(g.id) FILTER (WHERE g.winner = g.color)
+            FROM users u
+            LEFT JOIN LATERAL (
+                SELECT
+                    id,
+                    CASE WHEN (state->>'white_player')::INT = u.id THEN 'White' ELSE 'Black' END AS color,
+                    CASE
+                        WHEN state->'status' ? 'Checkmate' THEN state->'status'->>'Checkmate'
+                        WHEN state->'status' ? 'Resigned' THEN state->'status'->>'Resigned'
+                        WHEN state->'status' ? 'VariantWin' THEN state->'status'->>'VariantWin'
+                        WHEN state->'status'->>'Timeout' = 'White' THEN 'Black'
+                        WHEN state->'status'->>'Timeout' = 'Black' THEN 'White'
+                    END AS winner
+                FROM games
+                WHERE u.id IN ((state->>'white_player')::INT, (state->>'black_player')::INT)
+                  AND status IN ('checkmate', 'resigned', 'timeout', 'stalemate', 'draw', 'variant_win')
+            ) g ON TRUE
+            WHERE u.username = $1 AND u.is_public AND u.is_active
+            GROUP BY u.id",
+            &[&username],
+        )
+        .await?;
+
+    Ok(row.map(|row| {
+        let created_at: NaiveDateTime = row.get(2);
+        let games_played: i64 = row.get(3);
+        let wins: i64 = row.get(4);
+        PublicProfile {
+            username: row.get(0),
+            rating: row.get(1),
+            games_played: games_played as u32,
+            win_rate: win_rate(wins, games_played),
+            member_since: DateTime::from_naive_utc_and_offset(created_at, Utc),
+        }
+    }))
+}
@@ -0,0 +1,49 @@
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+use uuid::Uuid;
+
+#[tracing::instrument(name = "db.revoke_token", skip_all, fields(user_id = user_id, jti = %jti))]
+pub async fn revoke_token(
+    pool: &Pool,
+    jti: Uuid,
+    user_id: i32,
+    expires_at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let _timer = metrics::db_timer("revoke_token");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "INSERT INTO revoked_tokens (jti, user_id, expires_at) VALUES ($1, $2, $3) ON CONFLICT (jti) DO NOTHING",
+            &[&jti, &user_id, &expires_at],
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(name = "db.is_token_revoked", skip_all, fields(jti = %jti))]
+pub async fn is_token_revoked(pool: &Pool, jti: Uuid) -> anyhow::Result<bool> {
+    let _timer = metrics::db_timer("is_token_revoked");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt("SELECT 1 FROM revoked_tokens WHERE jti = $1", &[&jti])
+        .await?;
+
+    Ok(row.is_some())
+}
+
+/// Deletes revocations for tokens that have expired anyway
+#[tracing::instrument(name = "db.purge_expired_revoked_tokens", skip_all)]
+pub async fn purge_expired_revoked_tokens(pool: &Pool) -> anyhow::Result<u64> {
+    let _timer = metrics::db_timer("purge_expired_revoked_tokens");
+    let client = pool.get().await?;
+
+    let deleted = client
+        .execute("DELETE FROM revoked_tokens WHERE expires_at < NOW()", &[])
+        .await?;
+
+    Ok(deleted)
+}
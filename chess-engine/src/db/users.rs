@@ -0,0 +1,219 @@
+use crate::auth::models::UserRole;
+use crate::metrics;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use deadpool_postgres::Pool;
+use serde::Serialize;
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+/// Everything about an account except its secrets, for admins
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserRecord {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: UserRole,
+    pub is_active: bool,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
+    pub rating: i32,
+    pub created_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
+}
+
+const USER_RECORD_COLUMNS: &str = "id, username, email, role, is_active, email_verified, \
+     totp_enabled, rating, created_at, last_login";
+
+impl From<Row> for UserRecord {
+    fn from(row: Row) -> Self {
+        let created_at: NaiveDateTime = row.get(8);
+        let last_login: Option<NaiveDateTime> = row.get(9);
+        Self {
+            id: row.get(0),
+            username: row.get(1),
+            email: row.get(2),
+            role: UserRole::from_db(row.get(3)),
+            is_active: row.get(4),
+            email_verified: row.get(5),
+            totp_enabled: row.get(6),
+            rating: row.get(7),
+            created_at: DateTime::from_naive_utc_and_offset(created_at, Utc),
+            last_login: last_login
+                .map(|last_login| DateTime::from_naive_utc_and_offset(last_login, Utc)),
+        }
+    }
+}
+
+#[tracing::instrument(name = "db.user_exists", skip_all, fields(user_id = user_id))]
+pub async fn user_exists(pool: &Pool, user_id: i32) -> anyhow::Result<bool> {
+    let _timer = metrics::db_timer("user_exists");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt("SELECT 1 FROM users WHERE id = $1", &[&user_id])
+        .await?;
+
+    Ok(row.is_some())
+}
+
+#[tracing::instrument(name = "db.find_user_id", skip_all, fields(username = %username))]
+pub async fn find_user_id(pool: &Pool, username: &str) -> anyhow::Result<Option<i32>> {
+    let _timer = metrics::db_timer("find_user_id");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt("SELECT id FROM users WHERE username = $1", &[&username])
+        .await?;
+
+    Ok(row.map(|row| row.get(0)))
+}
+
+#[tracing::instrument(name = "db.find_user_id_by_email", skip_all)]
+pub async fn find_user_id_by_email(pool: &Pool, email: &str) -> anyhow::Result<Option<i32>> {
+    let _timer = metrics::db_timer("find_user_id_by_email");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt("SELECT id FROM users WHERE email = $1", &[&email])
+        .await?;
+
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Closes the account without deleting it, and revokes its refresh tokens
+#[tracing::instrument(name = "db.deactivate_user", skip_all, fields(user_id = user_id))]
+pub async fn deactivate_user(pool: &Pool, user_id: i32) -> anyhow::Result<()> {
+    let _timer = metrics::db_timer("deactivate_user");
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    transaction
+        .execute(
+            "UPDATE users SET is_active = FALSE WHERE id = $1",
+            &[&user_id],
+        )
+        .await?;
+    transaction
+        .execute(
+            "UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = $1",
+            &[&user_id],
+        )
+        .await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Deletes the user's row and, through cascades, their tokens and
+/// invitations. Returns whether the user existed.
+#[tracing::instrument(name = "db.delete_user", skip_all, fields(user_id = user_id))]
+pub async fn delete_user(pool: &Pool, user_id: i32) -> anyhow::Result<bool> {
+    let _timer = metrics::db_timer("delete_user");
+    let client = pool.get().await?;
+
+    let deleted = client
+        .execute("DELETE FROM users WHERE id = $1", &[&user_id])
+        .await?;
+
+    Ok(deleted > 0)
+}
+
+/// A page of users, oldest account first, optionally only those whose
+/// username or email contains `search`. Returns the page and the total
+/// number of matching users.
+#[tracing::instrument(name = "db.list_users", skip_all)]
+pub async fn list_users(
+    pool: &Pool,
+    search: Option<&str>,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<(Vec<UserRecord>, i64)> {
+    let _timer = metrics::db_timer("list_users");
+    let client = pool.get().await?;
+
+    let pattern = search.map(|search| {
+        format!(
+            "%{}%",
+            search
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        )
+    });
+    let filter = "$1::TEXT IS NULL OR username ILIKE $1 OR email ILIKE $1";
+
+    let rows = client
+        .query(
+            &format!(
+                "SELECT {} FROM users WHERE {} ORDER BY id LIMIT $2 OFFSET $3",
+                USER_RECORD_COLUMNS, filter
+            ),
+            &[&pattern, &limit, &offset],
+        )
+        .await?;
+    let total = client
+        .query_one(
+            &format!("SELECT COUNT(*) FROM users WHERE {}", filter),
+            &[&pattern],
+        )
+        .await?;
+
+    Ok((
+        rows.into_iter().map(UserRecord::from).collect(),
+        total.get(0),
+    ))
+}
+
+/// Applies an admin's changes to a user. Deactivating an account also
+/// revokes its refresh tokens. Returns the updated user, or `None` if there
+/// is no user with that ID.
+#[tracing::instrument(name = "db.update_user", skip_all, fields(user_id = user_id))]
+pub async fn update_user(
+    pool: &Pool,
+    user_id: i32,
+    is_active: Option<bool>,
+    role: Option<UserRole>,
+) -> anyhow::Result<Option<UserRecord>> {
+    let _timer = metrics::db_timer("update_user");
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    let row = transaction
+        .query_opt(
+            &format!(
+                "UPDATE users SET is_active = COALESCE($2, is_active), role = COALESCE($3, role) \
+                 WHERE id = $1 RETURNING {}",
+                USER_RECORD_COLUMNS
+            ),
+            &[&user_id, &is_active, &role.map(UserRole::as_str)],
+        )
+        .await?;
+
+    if row.is_some() && is_active == Some(false) {
+        transaction
+            .execute(
+                "UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(row.map(UserRecord::from))
+}
+
+/// Makes the user with this email an admin. Returns whether there was one.
+#[tracing::instrument(name = "db.promote_to_admin", skip_all)]
+pub async fn promote_to_admin(pool: &Pool, email: &str) -> anyhow::Result<bool> {
+    let _timer = metrics::db_timer("promote_to_admin");
+    let client = pool.get().await?;
+
+    let updated = client
+        .execute(
+            "UPDATE users SET role = $1 WHERE email = $2",
+            &[&UserRole::Admin.as_str(), &email],
+        )
+        .await?;
+
+    Ok(updated > 0)
+}
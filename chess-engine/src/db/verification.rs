@@ -0,0 +1,109 @@
+use crate::metrics;
+use chrono::{DateTime, Utc};
+use deadpool_postgres::Pool;
+
+/// Stores the hash of a token that verifies the user's email address
+#[tracing::instrument(name = "db.create_email_verification_token", skip_all, fields(user_id = user_id))]
+pub async fn create_email_verification_token(
+    pool: &Pool,
+    user_id: i32,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let _timer = metrics::db_timer("create_email_verification_token");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "INSERT INTO email_verification_tokens (token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+            &[&token_hash, &user_id, &expires_at],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Consumes the token and marks its user's email as verified. Returns the
+/// user's ID, or `None` if the token is unknown or has expired.
+#[tracing::instrument(name = "db.verify_email", skip_all)]
+pub async fn verify_email(pool: &Pool, token_hash: &str) -> anyhow::Result<Option<i32>> {
+    let _timer = metrics::db_timer("verify_email");
+    let client = pool.get().await?;
+
+    let row = client
+        .query_opt(
+            "WITH used AS ( \
+                 DELETE FROM email_verification_tokens WHERE token_hash = $1 \
+                 RETURNING user_id, expires_at \
+             ) \
+             UPDATE users SET email_verified = TRUE FROM used \
+             WHERE users.id = used.user_id AND used.expires_at > NOW() \
+             RETURNING users.id",
+            &[&token_hash],
+        )
+        .await?;
+
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Stores the hash of a token that lets the user choose a new password
+#[tracing::instrument(name = "db.create_password_reset_token", skip_all, fields(user_id = user_id))]
+pub async fn create_password_reset_token(
+    pool: &Pool,
+    user_id: i32,
+    token_hash: &str,
+    expires_at: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let _timer = metrics::db_timer("create_password_reset_token");
+    let client = pool.get().await?;
+
+    client
+        .execute(
+            "INSERT INTO password_reset_tokens (token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+            &[&token_hash, &user_id, &expires_at],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Consumes the token, sets its user's password hash and revokes all of
+/// their refresh tokens. Returns the user's ID, or `None` if the token is
+/// unknown or has expired.
+#[tracing::instrument(name = "db.reset_password", skip_all)]
+pub async fn reset_password(
+    pool: &Pool,
+    token_hash: &str,
+    password_hash: &str,
+) -> anyhow::Result<Option<i32>> {
+    let _timer = metrics::db_timer("reset_password");
+    let mut client = pool.get().await?;
+    let transaction = client.transaction().await?;
+
+    let row = transaction
+        .query_opt(
+            "WITH used AS ( \
+                 DELETE FROM password_reset_tokens WHERE token_hash = $1 \
+                 RETURNING user_id, expires_at \
+             ) \
+             UPDATE users SET password_hash = $2 FROM used \
+             WHERE users.id = used.user_id AND used.expires_at > NOW() \
+             RETURNING users.id",
+            &[&token_hash, &password_hash],
+        )
+        .await?;
+
+    let user_id: Option<i32> = row.map(|row| row.get(0));
+    if let Some(user_id) = user_id {
+        // Sessions opened with the old password end here
+        transaction
+            .execute(
+                "UPDATE refresh_tokens SET revoked = TRUE WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+    }
+
+    transaction.commit().await?;
+    Ok(user_id)
+}
@@ -0,0 +1,44 @@
+//! Machine-readable `code` values for JSON error responses, so clients can
+//! branch on the kind of error instead of matching its English message.
+//! Chess rule violations use the codes from `ChessError::error_code`.
+
+// Requests
+pub const INVALID_REQUEST: &str = "INVALID_REQUEST";
+pub const VALIDATION_FAILED: &str = "VALIDATION_FAILED";
+pub const INVALID_MOVE: &str = "INVALID_MOVE";
+pub const INVALID_PGN: &str = "INVALID_PGN";
+pub const INTERNAL_ERROR: &str = "INTERNAL_ERROR";
+pub const NOT_FOUND: &str = "NOT_FOUND";
+pub const METHOD_NOT_ALLOWED: &str = "METHOD_NOT_ALLOWED";
+pub const PAYLOAD_TOO_LARGE: &str = "PAYLOAD_TOO_LARGE";
+pub const UNSUPPORTED_MEDIA_TYPE: &str = "UNSUPPORTED_MEDIA_TYPE";
+
+// Games
+pub const GAME_NOT_FOUND: &str = "GAME_NOT_FOUND";
+pub const NOT_A_PLAYER: &str = "NOT_A_PLAYER";
+pub const NOT_YOUR_GAME: &str = "NOT_YOUR_GAME";
+pub const NOT_GAME_CREATOR: &str = "NOT_GAME_CREATOR";
+pub const UNDO_NOT_ALLOWED: &str = "UNDO_NOT_ALLOWED";
+pub const NO_LEGAL_MOVES: &str = "NO_LEGAL_MOVES";
+pub const GAME_IN_PROGRESS: &str = "GAME_IN_PROGRESS";
+pub const TOO_MANY_ACTIVE_GAMES: &str = "TOO_MANY_ACTIVE_GAMES";
+
+// Invitations
+pub const INVITATION_NOT_FOUND: &str = "INVITATION_NOT_FOUND";
+pub const INVITATION_CLOSED: &str = "INVITATION_CLOSED";
+pub const NOT_INVITATION_RECIPIENT: &str = "NOT_INVITATION_RECIPIENT";
+
+// Users and authentication
+pub const AUTH_REQUIRED: &str = "AUTH_REQUIRED";
+pub const INVALID_CREDENTIALS: &str = "INVALID_CREDENTIALS";
+pub const INVALID_TOKEN: &str = "INVALID_TOKEN";
+pub const RATE_LIMITED: &str = "RATE_LIMITED";
+pub const USERNAME_TAKEN: &str = "USERNAME_TAKEN";
+pub const EMAIL_TAKEN: &str = "EMAIL_TAKEN";
+pub const USER_NOT_FOUND: &str = "USER_NOT_FOUND";
+pub const EMAIL_NOT_VERIFIED: &str = "EMAIL_NOT_VERIFIED";
+pub const ACCOUNT_DEACTIVATED: &str = "ACCOUNT_DEACTIVATED";
+pub const ADMIN_REQUIRED: &str = "ADMIN_REQUIRED";
+pub const INVALID_TOTP_CODE: &str = "INVALID_TOTP_CODE";
+pub const TOTP_NOT_SET_UP: &str = "TOTP_NOT_SET_UP";
+pub const TOTP_ALREADY_ENABLED: &str = "TOTP_ALREADY_ENABLED";
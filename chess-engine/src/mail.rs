@@ -0,0 +1,85 @@
+//! Outgoing email over SMTP. Configured with `SMTP_HOST`, `SMTP_PORT`,
+//! `SMTP_USERNAME`, `SMTP_PASSWORD` and `MAIL_FROM`; links in emails point
+//! at `APP_BASE_URL`. Without `SMTP_HOST`, emails are logged instead of
+//! sent, which is enough for local development.
+
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+const DEFAULT_FROM: &str = "Chess <no-reply@localhost>";
+const DEFAULT_BASE_URL: &str = "http://localhost:3030";
+
+pub struct Mailer {
+    transport: Option<AsyncSmtpTransport<Tokio1Executor>>,
+    from: Mailbox,
+    base_url: String,
+}
+
+impl Mailer {
+    pub fn from_env() -> Result<Self, String> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+
+        let from = var("MAIL_FROM").unwrap_or_else(|| DEFAULT_FROM.to_string());
+        let from = from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("MAIL_FROM is not a valid address: {}", e))?;
+        let base_url = var("APP_BASE_URL").unwrap_or_else(|| DEFAULT_BASE_URL.to_string());
+
+        let transport = match var("SMTP_HOST") {
+            Some(host) => {
+                let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(host.trim())
+                    .map_err(|e| format!("Invalid SMTP_HOST: {}", e))?;
+                if let Some(port) = var("SMTP_PORT") {
+                    let port = port
+                        .trim()
+                        .parse::<u16>()
+                        .map_err(|_| format!("SMTP_PORT must be a port number, got '{}'", port))?;
+                    builder = builder.port(port);
+                }
+                if let (Some(username), Some(password)) =
+                    (var("SMTP_USERNAME"), var("SMTP_PASSWORD"))
+                {
+                    builder = builder.credentials(Credentials::new(username, password));
+                }
+                Some(builder.build())
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            transport,
+            from,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Whether emails actually leave the server
+    pub fn is_enabled(&self) -> bool {
+        self.transport.is_some()
+    }
+
+    /// Absolute URL for a path on this service, for links in emails
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> anyhow::Result<()> {
+        let Some(transport) = &self.transport else {
+            tracing::info!(to, subject, "SMTP_HOST not set, email not sent:\n{}", body);
+            return Ok(());
+        };
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .body(body)?;
+        transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -2,28 +2,100 @@ mod chess;
 mod api;
 mod auth;
 mod db;
+mod error_codes;
+mod mail;
+mod metrics;
+mod uci;
 
+use api::errors::handle_rejection;
+use api::admin::{delete_user, list_users, update_user, ListUsersQuery, UpdateUserRequest};
 use api::handlers::*;
-use auth::handlers::{login_handler, signup_handler};
-use auth::models::{LoginRequest, SignupRequest};
+use api::invitations::{
+    accept_invitation, create_invitation, decline_invitation, list_invitations, CreateInvitationRequest,
+};
+use api::middleware::{compression_min_bytes_from_env, with_compression, with_request_id};
+use api::openapi::{openapi_json, swagger_ui};
+use api::sse::{game_events, GameEvents};
+use api::store::{DbGameStore, GameBackend, InMemoryGameStore};
+use api::ws::{game_ws, GameSubscriptions};
+use auth::handlers::{
+    change_password_handler, deactivate_account_handler, forgot_password_handler, login_handler,
+    logout_handler, me_handler, refresh_handler, reset_password_handler, signup_handler,
+    totp_confirm_handler, totp_setup_handler, totp_verify_handler, verify_email_handler,
+};
+use auth::jwt::JwtConfig;
+use auth::middleware::{require_admin, require_auth};
+use auth::rate_limit::{client_ip, RateLimiter};
+use auth::models::{
+    ChangePasswordRequest, DeactivateAccountRequest, ForgotPasswordRequest, LoginRequest,
+    RefreshRequest, ResetPasswordRequest, SignupRequest, TotpCodeRequest, TwoFactorVerifyRequest,
+    VerifyEmailQuery,
+};
 use db::create_pool;
+use mail::Mailer;
+use metrics::metrics_handler;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::{Mutex, RwLock};
 use warp::Filter;
 
 #[tokio::main]
 async fn main() {
+    // Speak UCI on stdin/stdout instead of serving HTTP; this runs before
+    // logging is set up so nothing else is written to stdout
+    if std::env::args().skip(1).any(|arg| arg == "--uci") {
+        uci::run().await;
+        return;
+    }
+
     // Initialize logging
     tracing_subscriber::fmt::init();
 
+    // Start the uptime clock reported by /health
+    lazy_static::initialize(&START_TIME);
+
     // Load environment variables
     dotenv::dotenv().ok();
 
-    // Get port from environment variable or use default
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "3030".to_string())
-        .parse::<u16>()
-        .expect("PORT must be a valid number");
+    // Bind address from SERVER_HOST / SERVER_PORT
+    let addr = match server_addr() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("❌ Invalid server address: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Certificate and key for serving HTTPS directly
+    let tls_paths = match tls_paths() {
+        Ok(tls_paths) => tls_paths,
+        Err(e) => {
+            eprintln!("❌ Invalid TLS configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    TLS_ENABLED.store(tls_paths.is_some(), Ordering::Relaxed);
+
+    // Load JWT configuration (panics if JWT_SECRET is missing or weak)
+    let jwt_config = Arc::new(JwtConfig::from_env());
+
+    // SMTP settings for verification emails
+    let mailer = match Mailer::from_env() {
+        Ok(mailer) => Arc::new(mailer),
+        Err(e) => {
+            eprintln!("❌ Invalid mail configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if !mailer.is_enabled() {
+        println!("⚠️  SMTP_HOST not set: emails will be logged instead of sent");
+    }
+
+    // Failed login tracking for brute-force protection
+    let rate_limiter = Arc::new(RateLimiter::from_env());
 
     // Create database connection pool
     let db_pool = match create_pool().await {
@@ -37,18 +109,102 @@ async fn main() {
         }
     };
 
-    // Create shared game storage
-    let games: GameStore = Arc::new(Mutex::new(HashMap::new()));
+    // Bring the schema up to date before serving requests
+    if let Err(e) = db::migrations::Migrations::run(&db_pool).await {
+        eprintln!("❌ Failed to run database migrations: {}", e);
+        std::process::exit(1);
+    }
+
+    // FIRST_ADMIN_EMAIL - Account to make an admin, for bootstrapping a new deployment
+    let first_admin_email = std::env::var("FIRST_ADMIN_EMAIL").unwrap_or_default();
+    let first_admin_email = first_admin_email.trim();
+    if !first_admin_email.is_empty() {
+        match db::users::promote_to_admin(&db_pool, first_admin_email).await {
+            Ok(true) => println!("✅ {} is an admin", first_admin_email),
+            Ok(false) => println!("⚠️  FIRST_ADMIN_EMAIL: no user with email {}", first_admin_email),
+            Err(e) => eprintln!("❌ Failed to promote FIRST_ADMIN_EMAIL: {}", e),
+        }
+    }
+
+    // Periodically drop revocations for tokens that have expired anyway
+    let purge_pool = db_pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            match db::tokens::purge_expired_revoked_tokens(&purge_pool).await {
+                Ok(0) => {}
+                Ok(count) => println!("🧹 Purged {} expired revoked tokens", count),
+                Err(e) => eprintln!("❌ Failed to purge revoked tokens: {}", e),
+            }
+        }
+    });
+
+    // Games are cached in memory over a persistent store; GAME_STORE=memory
+    // skips the database, so games are lost on restart
+    let store: GameBackend = match std::env::var("GAME_STORE").as_deref() {
+        Ok("memory") => {
+            println!("⚠️  GAME_STORE=memory: games will not survive a restart");
+            Arc::new(InMemoryGameStore::new())
+        }
+        _ => Arc::new(DbGameStore::new(db_pool.clone())),
+    };
+
+    // Warm the cache with unfinished games, which are the ones about to be played
+    let mut cached = HashMap::new();
+    match store.list_active_games().await {
+        Ok(stored) => {
+            cached.extend(stored);
+            println!("✅ Loaded {} unfinished games", cached.len());
+        }
+        Err(e) => eprintln!("❌ Failed to load games: {}", e),
+    }
+
+    let games: GameStore = Arc::new(RwLock::new(cached));
+    let subscriptions: GameSubscriptions = Arc::new(Mutex::new(HashMap::new()));
+    let events: GameEvents = Arc::new(Mutex::new(HashMap::new()));
+    let engine_table = EngineTable::default();
+
+    // Hourly, end games nobody has touched in GAME_EXPIRATION_DAYS (default 7)
+    // so abandoned games don't pile up in memory
+    let expiration_days = std::env::var("GAME_EXPIRATION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(7);
+    let expiry_games = games.clone();
+    let expiry_store = store.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let inactive_for = chrono::Duration::days(expiration_days);
+            match expire_inactive_games(&expiry_games, expiry_store.as_ref(), inactive_for).await {
+                Ok(0) => {}
+                Ok(count) => println!("🧹 Expired {} inactive games", count),
+                Err(e) => eprintln!("❌ Failed to expire inactive games: {}", e),
+            }
+        }
+    });
 
     // Create filters
     let games_filter = warp::any().map(move || games.clone());
+    let store_filter = warp::any().map(move || store.clone());
+    let subscriptions_filter = warp::any().map(move || subscriptions.clone());
+    let events_filter = warp::any().map(move || events.clone());
+    let engine_table_filter = warp::any().map(move || engine_table.clone());
+    let auth_filter = require_auth(jwt_config.clone(), db_pool.clone());
+    let admin_filter = require_admin(jwt_config.clone(), db_pool.clone());
     let db_filter = warp::any().map(move || db_pool.clone());
+    let jwt_filter = warp::any().map(move || jwt_config.clone());
+    let rate_limit_filter = warp::any().map(move || rate_limiter.clone());
+    let mailer_filter = warp::any().map(move || mailer.clone());
 
     // CORS configuration
     let cors = warp::cors()
         .allow_any_origin()
-        .allow_headers(vec!["content-type", "authorization"])
-        .allow_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"]);
+        .allow_headers(vec!["content-type", "authorization", "x-request-id"])
+        .expose_headers(vec!["x-request-id"])
+        .allow_methods(vec!["GET", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]);
 
     // ========== AUTH ROUTES ==========
 
@@ -61,8 +217,20 @@ async fn main() {
         .and(warp::path::end())
         .and(warp::body::json::<SignupRequest>())
         .and(db_filter.clone())
+        .and(mailer_filter.clone())
         .and_then(signup_handler);
 
+    // GET /api/v1/auth/verify-email?token=... - Confirm an email address
+    let verify_email = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("verify-email"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(warp::query::<VerifyEmailQuery>())
+        .and(db_filter.clone())
+        .and_then(verify_email_handler);
+
     // POST /api/v1/auth/login - User login
     let login = warp::path("api")
         .and(warp::path("v1"))
@@ -71,9 +239,165 @@ async fn main() {
         .and(warp::post())
         .and(warp::path::end())
         .and(warp::body::json::<LoginRequest>())
+        .and(client_ip())
         .and(db_filter.clone())
+        .and(jwt_filter.clone())
+        .and(rate_limit_filter.clone())
         .and_then(login_handler);
 
+    // POST /api/v1/auth/2fa/verify - Finish logging in with a TOTP code
+    let totp_verify = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("2fa"))
+        .and(warp::path("verify"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::json::<TwoFactorVerifyRequest>())
+        .and(db_filter.clone())
+        .and(jwt_filter.clone())
+        .and(rate_limit_filter.clone())
+        .and_then(totp_verify_handler);
+
+    // POST /api/v1/auth/2fa/setup - Generate a TOTP secret
+    let totp_setup = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("2fa"))
+        .and(warp::path("setup"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(db_filter.clone())
+        .and_then(totp_setup_handler);
+
+    // POST /api/v1/auth/2fa/confirm - Enable 2FA with a first TOTP code
+    let totp_confirm = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("2fa"))
+        .and(warp::path("confirm"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json::<TotpCodeRequest>())
+        .and(db_filter.clone())
+        .and_then(totp_confirm_handler);
+
+    // POST /api/v1/auth/refresh - Exchange a refresh token for new tokens
+    let refresh = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("refresh"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::json::<RefreshRequest>())
+        .and(db_filter.clone())
+        .and(jwt_filter.clone())
+        .and_then(refresh_handler);
+
+    // POST /api/v1/auth/logout - Revoke the current access token
+    let logout = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("logout"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(db_filter.clone())
+        .and_then(logout_handler);
+
+    // POST /api/v1/auth/forgot-password - Email a password reset link
+    let forgot_password = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("forgot-password"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::json::<ForgotPasswordRequest>())
+        .and(db_filter.clone())
+        .and(mailer_filter.clone())
+        .and_then(forgot_password_handler);
+
+    // POST /api/v1/auth/reset-password - Choose a new password with a reset link
+    let reset_password = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("reset-password"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::json::<ResetPasswordRequest>())
+        .and(db_filter.clone())
+        .and_then(reset_password_handler);
+
+    // GET /api/v1/auth/me - Current user's profile
+    let me = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("me"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(db_filter.clone())
+        .and_then(me_handler);
+
+    // DELETE /api/v1/auth/me - Close the current user's account
+    let deactivate_account = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("auth"))
+        .and(warp::path("me"))
+        .and(warp::delete())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json::<DeactivateAccountRequest>())
+        .and(db_filter.clone())
+        .and_then(deactivate_account_handler);
+
+    // PATCH /api/v1/users/me/password - Change the current user's password
+    let change_password = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("users"))
+        .and(warp::path("me"))
+        .and(warp::path("password"))
+        .and(warp::patch())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json::<ChangePasswordRequest>())
+        .and(db_filter.clone())
+        .and_then(change_password_handler);
+
+    // GET /api/v1/users/leaderboard - Highest-rated players
+    let leaderboard = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("users"))
+        .and(warp::path("leaderboard"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(warp::query::<LeaderboardQuery>())
+        .and(db_filter.clone())
+        .and_then(get_leaderboard);
+
+    // GET /api/v1/users/:username/stats - Public player statistics
+    let player_stats = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("users"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("stats"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(db_filter.clone())
+        .and_then(get_player_stats);
+
+    // GET /api/v1/users/:username - A player's public profile
+    let public_profile = warp::path("api")
+        .and(warp::path("v1"))
+        .and(warp::path("users"))
+        .and(warp::path::param::<String>())
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(db_filter.clone())
+        .and_then(get_public_profile);
+
     // ========== CHESS GAME ROUTES ==========
 
     let api = warp::path("api").and(warp::path("v1"));
@@ -83,9 +407,47 @@ async fn main() {
         .and(warp::path("games"))
         .and(warp::post())
         .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::bytes())
         .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(db_filter.clone())
         .and_then(create_new_game);
 
+    // POST /api/v1/games/from-fen - Create an analysis board from a FEN
+    let new_game_from_fen = api
+        .and(warp::path("games"))
+        .and(warp::path("from-fen"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json::<FenImportRequest>())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and_then(create_game_from_fen);
+
+    // POST /api/v1/games/import-pgn - Import a game from PGN (JSON or multipart)
+    let import_pgn_route = api
+        .and(warp::path("games"))
+        .and(warp::path("import-pgn"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(pgn_body())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and_then(import_pgn);
+
+    // GET /api/v1/games - List the current user's games
+    let list_games_route = api
+        .and(warp::path("games"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::query::<ListGamesQuery>())
+        .and(db_filter.clone())
+        .and_then(list_games);
+
     // GET /api/v1/games/:id - Get game state
     let get_game = api
         .and(warp::path("games"))
@@ -93,8 +455,23 @@ async fn main() {
         .and(warp::get())
         .and(warp::path::end())
         .and(games_filter.clone())
+        .and(store_filter.clone())
         .and_then(get_game_state);
 
+    // DELETE /api/v1/games/:id - Delete a game, or resign it if under way (creator only)
+    let delete_game_route = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::delete())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and_then(delete_game);
+
     // POST /api/v1/games/:id/moves - Make a move
     let make_move_route = api
         .and(warp::path("games"))
@@ -102,10 +479,27 @@ async fn main() {
         .and(warp::path("moves"))
         .and(warp::post())
         .and(warp::path::end())
+        .and(auth_filter.clone())
         .and(warp::body::json())
         .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
         .and_then(make_move);
 
+    // POST /api/v1/games/:id/validate-move - Check a move without playing it
+    let validate_move_route = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("validate-move"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and_then(validate_move);
+
     // GET /api/v1/games/:id/moves - Get legal moves
     let get_moves = api
         .and(warp::path("games"))
@@ -114,8 +508,21 @@ async fn main() {
         .and(warp::get())
         .and(warp::path::end())
         .and(games_filter.clone())
+        .and(store_filter.clone())
         .and_then(get_legal_moves);
 
+    // GET /api/v1/games/:id/legal-moves/:square - Get legal moves for one piece
+    let get_square_moves_route = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("legal-moves"))
+        .and(warp::path::param::<String>())
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and_then(get_square_moves);
+
     // GET /api/v1/games/:id/fen - Get game in FEN notation
     let get_fen = api
         .and(warp::path("games"))
@@ -124,47 +531,516 @@ async fn main() {
         .and(warp::get())
         .and(warp::path::end())
         .and(games_filter.clone())
+        .and(store_filter.clone())
         .and_then(get_game_fen);
 
-    // Health check endpoint
+    // POST /api/v1/games/:id/engine-move - Let the engine play a move
+    let engine_move = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("engine-move"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and(engine_table_filter.clone())
+        .and_then(make_engine_move);
+
+    // POST /api/v1/games/:id/analyze - Score the candidate moves in a position
+    let analyze = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("analyze"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(engine_table_filter.clone())
+        .and_then(analyze_game);
+
+    // POST /api/v1/games/:id/annotate - Annotate every move in the background
+    let annotate = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("annotate"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(db_filter.clone())
+        .and(engine_table_filter.clone())
+        .and_then(annotate_game);
+
+    // POST /api/v1/games/:id/resign - Resign the game
+    let resign = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("resign"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and_then(resign_game);
+
+    // POST /api/v1/games/:id/draw-offer - Offer a draw
+    let draw_offer = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("draw-offer"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and_then(offer_draw);
+
+    // POST /api/v1/games/:id/draw-accept - Accept a pending draw offer
+    let draw_accept = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("draw-accept"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and_then(accept_draw);
+
+    // POST /api/v1/games/:id/claim-draw - Claim a draw by the fifty-move rule or repetition
+    let draw_claim = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("claim-draw"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and_then(claim_draw);
+
+    // POST /api/v1/games/:id/undo - Take back the last move (casual and analysis games)
+    let undo = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("undo"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and(events_filter.clone())
+        .and(db_filter.clone())
+        .and_then(undo_move);
+
+    // GET /api/v1/games/:id/pgn - Download the game as PGN
+    let get_pgn = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("pgn"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(db_filter.clone())
+        .and_then(get_game_pgn);
+
+    // GET /api/v1/games/:id/ascii - Text diagram of the position
+    let get_diagram = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("ascii"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(warp::query::<DiagramQuery>())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and_then(get_game_diagram);
+
+    // GET /api/v1/games/:id/ws - Subscribe to live game updates
+    let game_socket = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("ws"))
+        .and(warp::path::end())
+        .and(warp::ws())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(subscriptions_filter.clone())
+        .and_then(game_ws);
+
+    // GET /api/v1/games/:id/events - Live game updates as Server-Sent Events
+    let game_event_stream = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("events"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(warp::header::optional::<u64>("last-event-id"))
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(events_filter.clone())
+        .and_then(game_events);
+
+    // GET /api/v1/games/:id/history - Get move history
+    let get_history = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("history"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(warp::query::<HistoryQuery>())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(db_filter.clone())
+        .and_then(get_game_history);
+
+    // GET /api/v1/games/:id/stats - Time used by each player
+    let get_stats = api
+        .and(warp::path("games"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("stats"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(db_filter.clone())
+        .and_then(get_game_stats);
+
+    // ========== INVITATION ROUTES ==========
+
+    // POST /api/v1/invitations - Invite another player to a game
+    let new_invitation = api
+        .and(warp::path("invitations"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(warp::body::json::<CreateInvitationRequest>())
+        .and(db_filter.clone())
+        .and_then(create_invitation);
+
+    // GET /api/v1/invitations - Pending invitations sent to and by the current user
+    let list_invitations_route = api
+        .and(warp::path("invitations"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(db_filter.clone())
+        .and_then(list_invitations);
+
+    // POST /api/v1/invitations/:id/accept - Accept an invitation and start the game
+    let accept_invitation_route = api
+        .and(warp::path("invitations"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("accept"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(games_filter.clone())
+        .and(store_filter.clone())
+        .and(db_filter.clone())
+        .and_then(accept_invitation);
+
+    // POST /api/v1/invitations/:id/decline - Decline an invitation
+    let decline_invitation_route = api
+        .and(warp::path("invitations"))
+        .and(warp::path::param::<String>())
+        .and(warp::path("decline"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(auth_filter.clone())
+        .and(db_filter.clone())
+        .and_then(decline_invitation);
+
+    // ========== ADMIN ROUTES ==========
+
+    // GET /api/v1/admin/users - List users
+    let list_users_route = api
+        .and(warp::path("admin"))
+        .and(warp::path("users"))
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(admin_filter.clone())
+        .and(warp::query::<ListUsersQuery>())
+        .and(db_filter.clone())
+        .and_then(list_users);
+
+    // PATCH /api/v1/admin/users/:id - Change a user's status or role
+    let update_user_route = api
+        .and(warp::path("admin"))
+        .and(warp::path("users"))
+        .and(warp::path::param::<i32>())
+        .and(warp::patch())
+        .and(warp::path::end())
+        .and(admin_filter.clone())
+        .and(warp::body::json::<UpdateUserRequest>())
+        .and(db_filter.clone())
+        .and_then(update_user);
+
+    // DELETE /api/v1/admin/users/:id - Permanently delete a user
+    let delete_user_route = api
+        .and(warp::path("admin"))
+        .and(warp::path("users"))
+        .and(warp::path::param::<i32>())
+        .and(warp::delete())
+        .and(warp::path::end())
+        .and(admin_filter.clone())
+        .and(db_filter.clone())
+        .and_then(delete_user);
+
+    // POST /api/v1/debug/perft - Count move tree nodes (debug builds only)
+    #[cfg(debug_assertions)]
+    let perft = api
+        .and(warp::path("debug"))
+        .and(warp::path("perft"))
+        .and(warp::post())
+        .and(warp::path::end())
+        .and(warp::body::json())
+        .and_then(run_perft);
+
+    // GET /api-docs/openapi.json and /swagger-ui - API documentation
+    let api_docs = openapi_json();
+    let swagger = swagger_ui();
+
+    // GET /health - Liveness plus database connectivity
     let health = warp::path("health")
         .and(warp::get())
-        .map(|| {
-            warp::reply::json(&serde_json::json!({
-                "status": "healthy",
-                "service": "chess-engine",
-                "version": env!("CARGO_PKG_VERSION")
-            }))
-        });
+        .and(warp::path::end())
+        .and(db_filter.clone())
+        .and_then(health_check);
+
+    // GET /metrics - Prometheus scrape endpoint, optionally gated by METRICS_TOKEN
+    let metrics_token = std::env::var("METRICS_TOKEN").ok().filter(|t| !t.is_empty());
+    let metrics_route = warp::path("metrics")
+        .and(warp::get())
+        .and(warp::path::end())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::any().map(move || metrics_token.clone()))
+        .and_then(metrics_handler);
 
-    // Combine all routes
-    let routes = signup
+    // Combine all routes, boxing each group to keep the filter type shallow
+    let auth_routes = signup
+        .or(verify_email)
         .or(login)
-        .or(new_game)
+        .or(totp_verify)
+        .or(totp_setup)
+        .or(totp_confirm)
+        .or(refresh)
+        .or(logout)
+        .or(forgot_password)
+        .or(reset_password)
+        .or(me)
+        .or(deactivate_account)
+        .or(change_password)
+        .or(leaderboard)
+        .or(player_stats)
+        .or(public_profile)
+        .boxed();
+
+    let game_routes = new_game
+        .or(new_game_from_fen)
+        .or(import_pgn_route)
+        .or(list_games_route)
         .or(get_game)
+        .or(delete_game_route)
         .or(make_move_route)
+        .or(validate_move_route)
         .or(get_moves)
+        .or(get_square_moves_route)
         .or(get_fen)
+        .or(get_history)
+        .or(get_stats)
+        .or(get_pgn)
+        .or(get_diagram)
+        .or(game_socket)
+        .or(game_event_stream)
+        .or(engine_move)
+        .or(analyze)
+        .or(annotate)
+        .or(resign)
+        .or(draw_offer)
+        .or(draw_accept)
+        .or(draw_claim)
+        .or(undo)
+        .boxed();
+
+    let invitation_routes = new_invitation
+        .or(list_invitations_route)
+        .or(accept_invitation_route)
+        .or(decline_invitation_route)
+        .boxed();
+
+    let admin_routes = list_users_route
+        .or(update_user_route)
+        .or(delete_user_route)
+        .boxed();
+
+    let docs_routes = api_docs
+        .or(swagger)
         .or(health)
-        .with(cors)
-        .with(warp::log("chess_engine"));
+        .boxed();
+
+    let routes = auth_routes
+        .or(game_routes)
+        .or(invitation_routes)
+        .or(admin_routes)
+        .or(docs_routes);
 
-    println!("🚀 Chess Engine Server starting on http://0.0.0.0:{}", port);
+    #[cfg(debug_assertions)]
+    let routes = routes.or(perft);
+
+    // Compress responses of at least COMPRESSION_MIN_BYTES (default 1KB)
+    let compression_min_bytes = compression_min_bytes_from_env();
+
+    let routes = routes
+        .recover(handle_rejection)
+        .with(warp::wrap_fn(move |routes| with_compression(routes, compression_min_bytes)))
+        .with(warp::wrap_fn(with_request_id))
+        .with(cors);
+
+    // Scrapers aren't browsers, so metrics sit outside CORS and auth
+    let routes = metrics_route.or(routes);
+
+    let scheme = if tls_paths.is_some() { "https" } else { "http" };
+    println!("🚀 Chess Engine Server starting on {}://{}", scheme, addr);
     println!("📋 API Documentation:");
     println!("\n🔐 Authentication:");
     println!("  POST   /api/v1/auth/signup     - Register new user");
+    println!("  GET    /api/v1/auth/verify-email - Confirm an email address");
     println!("  POST   /api/v1/auth/login      - User login");
+    println!("  POST   /api/v1/auth/2fa/verify - Finish login with a 2FA code");
+    println!("  POST   /api/v1/auth/2fa/setup  - Start enabling 2FA");
+    println!("  POST   /api/v1/auth/2fa/confirm - Enable 2FA");
+    println!("  POST   /api/v1/auth/refresh    - Refresh access token");
+    println!("  POST   /api/v1/auth/logout     - Revoke current token");
+    println!("  POST   /api/v1/auth/forgot-password - Email a password reset link");
+    println!("  POST   /api/v1/auth/reset-password - Reset password");
+    println!("  GET    /api/v1/auth/me         - Current user profile");
+    println!("  DELETE /api/v1/auth/me         - Close account");
+    println!("  PATCH  /api/v1/users/me/password - Change password");
+    println!("  GET    /api/v1/users/leaderboard - Top-rated players");
+    println!("  GET    /api/v1/users/:username/stats - Player statistics");
+    println!("  GET    /api/v1/users/:username - Public profile");
     println!("\n♟️  Chess Game:");
     println!("  POST   /api/v1/games           - Create new game");
+    println!("  POST   /api/v1/games/from-fen  - Create an analysis board from FEN");
+    println!("  POST   /api/v1/games/import-pgn - Import a game from PGN");
+    println!("  GET    /api/v1/games           - List your games");
     println!("  GET    /api/v1/games/:id       - Get game state");
+    println!("  DELETE /api/v1/games/:id       - Delete a game");
     println!("  POST   /api/v1/games/:id/moves - Make a move");
+    println!("  POST   /api/v1/games/:id/validate-move - Check a move without playing it");
     println!("  GET    /api/v1/games/:id/moves - Get legal moves");
+    println!("  GET    /api/v1/games/:id/legal-moves/:square - Get legal moves for one piece");
     println!("  GET    /api/v1/games/:id/fen   - Get FEN notation");
+    println!("  GET    /api/v1/games/:id/history - Get move history");
+    println!("  GET    /api/v1/games/:id/stats - Time used by each player");
+    println!("  GET    /api/v1/games/:id/pgn   - Download as PGN");
+    println!("  GET    /api/v1/games/:id/ascii - Text diagram of the board (?format=unicode)");
+    println!("  GET    /api/v1/games/:id/ws    - Live game updates (WebSocket)");
+    println!("  GET    /api/v1/games/:id/events - Live game updates (SSE)");
+    println!("  POST   /api/v1/games/:id/engine-move - Play an engine move");
+    println!("  POST   /api/v1/games/:id/analyze - Engine analysis of a position");
+    println!("  POST   /api/v1/games/:id/annotate - Annotate every move (runs in background)");
+    println!("  POST   /api/v1/games/:id/resign - Resign the game");
+    println!("  POST   /api/v1/games/:id/draw-offer - Offer a draw");
+    println!("  POST   /api/v1/games/:id/draw-accept - Accept a draw offer");
+    println!("  POST   /api/v1/games/:id/claim-draw - Claim a draw by the fifty-move rule or repetition");
+    println!("  POST   /api/v1/games/:id/undo  - Take back a move (casual/analysis games)");
+    println!("\n✉️  Invitations:");
+    println!("  POST   /api/v1/invitations     - Invite a player to a game");
+    println!("  GET    /api/v1/invitations     - Pending invitations");
+    println!("  POST   /api/v1/invitations/:id/accept - Accept an invitation");
+    println!("  POST   /api/v1/invitations/:id/decline - Decline an invitation");
+    println!("\n🛡️  Admin:");
+    println!("  GET    /api/v1/admin/users     - List users");
+    println!("  PATCH  /api/v1/admin/users/:id - Change a user's status or role");
+    println!("  DELETE /api/v1/admin/users/:id - Permanently delete a user");
+    #[cfg(debug_assertions)]
+    {
+        println!("\n🐞 Debug:");
+        println!("  POST   /api/v1/debug/perft     - Count perft nodes");
+    }
+    println!("\n📖 Documentation:");
+    println!("  GET    /swagger-ui             - Interactive API explorer");
+    println!("  GET    /api-docs/openapi.json  - OpenAPI 3.0 spec");
     println!("\n🏥 Health:");
-    println!("  GET    /health                 - Health check");
+    println!("  GET    /health                 - Health check (includes database)");
+    println!("\n📈 Metrics:");
+    println!("  GET    /metrics                - Prometheus metrics");
+
+    match tls_paths {
+        Some((cert_path, key_path)) => {
+            warp::serve(routes)
+                .tls()
+                .cert_path(cert_path)
+                .key_path(key_path)
+                .run(addr)
+                .await
+        }
+        None => warp::serve(routes).run(addr).await,
+    }
+}
+
+/// Reads `TLS_CERT_PATH` and `TLS_KEY_PATH`, PEM files for the certificate
+/// chain and private key. Without them the server speaks plain HTTP and
+/// expects a reverse proxy to terminate TLS. Either way, HSTS and
+/// certificate pinning are left to the proxy or load balancer in front.
+fn tls_paths() -> Result<Option<(PathBuf, PathBuf)>, String> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+
+    let (cert_path, key_path) = match (var("TLS_CERT_PATH"), var("TLS_KEY_PATH")) {
+        (Some(cert_path), Some(key_path)) => (PathBuf::from(cert_path), PathBuf::from(key_path)),
+        (None, None) => return Ok(None),
+        _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string()),
+    };
+
+    // warp only reads the files once it starts serving, and panics if it can't
+    for path in [&cert_path, &key_path] {
+        std::fs::metadata(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    }
+
+    Ok(Some((cert_path, key_path)))
+}
+
+/// Reads `SERVER_HOST` (default 127.0.0.1) and `SERVER_PORT` (default 3030).
+/// Hosts that inject `PORT` instead, such as Render, are honoured as well.
+fn server_addr() -> Result<SocketAddr, String> {
+    let host = std::env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+    let host = host
+        .trim()
+        .parse::<IpAddr>()
+        .map_err(|_| format!("SERVER_HOST must be an IP address, got '{}'", host))?;
+
+    let port = std::env::var("SERVER_PORT")
+        .or_else(|_| std::env::var("PORT"))
+        .unwrap_or_else(|_| "3030".to_string());
+    let port = port
+        .trim()
+        .parse::<u16>()
+        .map_err(|_| format!("SERVER_PORT must be a number between 0 and 65535, got '{}'", port))?;
 
-    // Bind to 0.0.0.0 to accept connections from any network interface
-    warp::serve(routes)
-        .run(([0, 0, 0, 0], port))
-        .await;
+    Ok(SocketAddr::new(host, port))
 }
\ No newline at end of file
@@ -0,0 +1,108 @@
+//! Prometheus metrics, served in text format at `GET /metrics`.
+//! Everything is registered with the default registry on first use.
+
+use crate::chess::GameStatus;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    Encoder, Histogram, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+use warp::http::StatusCode;
+use warp::Reply;
+
+lazy_static! {
+    pub static ref GAMES_CREATED: IntCounter = register_int_counter!(
+        "chess_games_created_total",
+        "Games created, including FEN and PGN imports"
+    )
+    .unwrap();
+    pub static ref MOVES_MADE: IntCounter = register_int_counter!(
+        "chess_moves_made_total",
+        "Moves played by players and the engine"
+    )
+    .unwrap();
+    pub static ref GAMES_COMPLETED: IntCounterVec = register_int_counter_vec!(
+        "chess_games_completed_total",
+        "Games that reached a final result, by how they ended",
+        &["outcome"]
+    )
+    .unwrap();
+    pub static ref SIGNUPS: IntCounter =
+        register_int_counter!("auth_signups_total", "Accounts created").unwrap();
+    pub static ref LOGIN_ATTEMPTS: IntCounterVec = register_int_counter_vec!(
+        "auth_login_attempts_total",
+        "Login attempts, by whether they succeeded",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref MOVE_LATENCY: Histogram = register_histogram!(
+        "chess_move_latency_seconds",
+        "Time taken to handle a move request"
+    )
+    .unwrap();
+    pub static ref DB_QUERY_LATENCY: HistogramVec = register_histogram_vec!(
+        "db_query_latency_seconds",
+        "Time taken by database calls, by query",
+        &["query"],
+        vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0]
+    )
+    .unwrap();
+}
+
+/// Counts a finished game under its outcome; ongoing games are ignored
+pub fn record_game_completed(status: &GameStatus) {
+    let outcome = match status {
+        GameStatus::Checkmate(_) => "checkmate",
+        GameStatus::Stalemate => "stalemate",
+        GameStatus::Draw => "draw",
+        GameStatus::Timeout(_) => "timeout",
+        GameStatus::Resigned(_) => "resignation",
+        GameStatus::VariantWin(_) => "variant_win",
+        GameStatus::InProgress | GameStatus::Check => return,
+    };
+    GAMES_COMPLETED.with_label_values(&[outcome]).inc();
+}
+
+pub fn record_login_attempt(success: bool) {
+    let result = if success { "success" } else { "failure" };
+    LOGIN_ATTEMPTS.with_label_values(&[result]).inc();
+}
+
+/// Starts a timer that records into `db_query_latency_seconds` when dropped
+pub fn db_timer(query: &str) -> prometheus::HistogramTimer {
+    DB_QUERY_LATENCY.with_label_values(&[query]).start_timer()
+}
+
+/// Renders every registered metric in the Prometheus text format.
+/// When `METRICS_TOKEN` is set, scrapers must send it as a bearer token.
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses(
+        (status = 200, description = "Prometheus metrics", body = String, content_type = "text/plain"),
+        (status = 401, description = "Missing or wrong METRICS_TOKEN"),
+    )
+)]
+pub async fn metrics_handler(
+    authorization: Option<String>,
+    metrics_token: Option<String>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    if let Some(expected) = metrics_token {
+        let provided = authorization
+            .as_deref()
+            .and_then(|header| header.strip_prefix("Bearer "));
+        if provided != Some(expected.as_str()) {
+            return Ok(StatusCode::UNAUTHORIZED.into_response());
+        }
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        eprintln!("Failed to encode metrics: {}", e);
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    Ok(warp::reply::with_header(buffer, "content-type", encoder.format_type()).into_response())
+}
@@ -0,0 +1,124 @@
+//! Universal Chess Interface mode, so GUIs such as Arena or CuteChess can
+//! play against the engine over stdin/stdout. Started with `--uci`.
+//!
+//! Supported commands: `uci`, `isready`, `ucinewgame`, `position` (from
+//! `startpos` or `fen`, with optional `moves`), `go` (with `depth` and/or
+//! `movetime`) and `quit`. Searches run to completion, so `stop` is ignored.
+
+use crate::chess::{notation, ChessError, Engine, GameState, TranspositionTable};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+const ENGINE_AUTHOR: &str = "Silverx-code";
+
+/// Depth searched when `go` gives no limits
+const DEFAULT_DEPTH: u8 = 5;
+
+/// Depth cap when only `movetime` limits the search
+const MAX_DEPTH: u8 = 64;
+
+/// Deadline for searches limited by depth alone
+const UNTIMED_SEARCH: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Reads commands from stdin until `quit` or end of input
+pub async fn run() {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let table = Arc::new(Mutex::new(TranspositionTable::default()));
+    let mut state = GameState::new();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, args)) = tokens.split_first() else {
+            continue;
+        };
+
+        match command {
+            "uci" => {
+                println!(
+                    "id name {} {}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                );
+                println!("id author {}", ENGINE_AUTHOR);
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                table.lock().unwrap_or_else(|e| e.into_inner()).clear();
+                state = GameState::new();
+            }
+            "position" => match parse_position(args) {
+                Ok(position) => state = position,
+                Err(e) => println!("info string {}", e),
+            },
+            "go" => {
+                let best_move = go(&state, args, table.clone()).await;
+                match best_move {
+                    Some(uci) => println!("bestmove {}", uci),
+                    // The protocol's null move, for positions with no legal moves
+                    None => println!("bestmove 0000"),
+                }
+            }
+            "quit" => break,
+            _ => {}
+        }
+    }
+}
+
+/// `position [startpos | fen <fen>] [moves <move>...]`
+fn parse_position(args: &[&str]) -> Result<GameState, String> {
+    let moves_at = args
+        .iter()
+        .position(|&arg| arg == "moves")
+        .unwrap_or(args.len());
+    let (setup, moves) = args.split_at(moves_at);
+
+    let ucis = moves.get(1..).unwrap_or_default();
+    let moves = ucis
+        .iter()
+        .map(|&uci| notation::uci_to_move(uci).ok_or_else(|| format!("invalid move {}", uci)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let illegal = |(index, e): (usize, ChessError)| format!("illegal move {}: {}", ucis[index], e);
+
+    match setup.split_first() {
+        Some((&"startpos", _)) => GameState::from_moves(&moves).map_err(illegal),
+        Some((&"fen", fen)) => {
+            let mut state = GameState::from_fen(&fen.join(" ")).map_err(|e| e.to_string())?;
+            state.apply_moves(&moves).map_err(illegal)?;
+            Ok(state)
+        }
+        _ => Err("expected startpos or fen".to_string()),
+    }
+}
+
+/// Searches `state` within the limits given to `go` and returns the best
+/// move in UCI notation
+async fn go(
+    state: &GameState,
+    args: &[&str],
+    table: Arc<Mutex<TranspositionTable>>,
+) -> Option<String> {
+    let limit = |name: &str| -> Option<u64> {
+        let at = args.iter().position(|&arg| arg == name)?;
+        args.get(at + 1)?.parse().ok()
+    };
+    let depth = limit("depth").map(|depth| depth.clamp(1, MAX_DEPTH as u64) as u8);
+    let move_time = limit("movetime").map(Duration::from_millis);
+
+    let (depth, move_time) = match (depth, move_time) {
+        (Some(depth), move_time) => (depth, move_time.unwrap_or(UNTIMED_SEARCH)),
+        (None, Some(move_time)) => (MAX_DEPTH, move_time),
+        (None, None) => (DEFAULT_DEPTH, UNTIMED_SEARCH),
+    };
+
+    let snapshot = state.clone();
+    let deadline = Instant::now() + move_time;
+    let engine = Engine::new(depth).with_table(table);
+    let best_move =
+        tokio::task::spawn_blocking(move || engine.best_move_timed(&snapshot, deadline))
+            .await
+            .ok()??;
+
+    Some(notation::move_to_uci(&best_move))
+}